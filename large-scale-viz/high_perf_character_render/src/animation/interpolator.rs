@@ -1,17 +1,34 @@
-use crate::data::{AnimationState, CoordinateMapper, Direction, SpriteSequence, SpriteInstance};
+use crate::data::{
+    AnimationState, CoordinateMapper, Direction, InterpolationMode, SpriteInstance, SpriteSequence,
+    WalkCycle,
+};
+use crate::warp_validator::{transition_key, valid_coordinate_pair, valid_coordinate_pair_v2};
 
 pub struct AnimationInterpolator {
     coordinate_mapper: CoordinateMapper,
     interval_ms: f32,
     fps: f32,
+    walk_cycle: WalkCycle,
+    interpolation_mode: InterpolationMode,
+    warp_fade_ms: f32,
 }
 
 impl AnimationInterpolator {
-    pub fn new(coordinate_mapper: CoordinateMapper, interval_ms: f32, fps: f32) -> Self {
+    pub fn new(
+        coordinate_mapper: CoordinateMapper,
+        interval_ms: f32,
+        fps: f32,
+        walk_cycle: WalkCycle,
+        interpolation_mode: InterpolationMode,
+        warp_fade_ms: f32,
+    ) -> Self {
         Self {
             coordinate_mapper,
             interval_ms,
             fps,
+            walk_cycle,
+            interpolation_mode,
+            warp_fade_ms,
         }
     }
 
@@ -64,6 +81,20 @@ impl AnimationInterpolator {
         // Only interpolate if moving contiguously (1 tile = 16 pixels)
         let should_interpolate = pixel_distance <= 16.0;
 
+        // A big jump is either a genuine data gap or a legitimate warp (door,
+        // cave entrance, ladder, ...). Only the latter gets the fade treatment.
+        let is_confirmed_warp = !should_interpolate && {
+            let from_map = current_frame.coords[2] as u8;
+            let to_map = next_frame.coords[2] as u8;
+            let from = [
+                current_frame.coords[0] as u8,
+                current_frame.coords[1] as u8,
+                from_map,
+            ];
+            let to = [next_frame.coords[0] as u8, next_frame.coords[1] as u8, to_map];
+            valid_coordinate_pair(from, to) || valid_coordinate_pair_v2(transition_key(from_map, to_map))
+        };
+
         // If jumping > 16 pixels, don't interpolate - just show at current position
         let interpolation_t = if should_interpolate {
             state.interpolation_t
@@ -71,21 +102,79 @@ impl AnimationInterpolator {
             0.0
         };
 
-        // Linear interpolation (or no interpolation if jumping)
-        let position = [
-            current_pos[0] + (next_pos[0] - current_pos[0]) * interpolation_t,
-            current_pos[1] + (next_pos[1] - current_pos[1]) * interpolation_t,
-        ];
+        let (position, dx, dy, alpha) = if should_interpolate
+            && self.interpolation_mode == InterpolationMode::Spline
+        {
+            // Uniform Catmull-Rom through the previous/current/next/next-next
+            // points, clamping indices at sequence boundaries by duplicating the
+            // end points
+            let prev_index = state.current_frame_index.saturating_sub(1);
+            let after_next_index = (state.next_frame_index + 1).min(sequence.frames.len() - 1);
+
+            let p0 = self
+                .coordinate_mapper
+                .convert_coords(&sequence.frames[prev_index].coords);
+            let p1 = current_pos;
+            let p2 = next_pos;
+            let p3 = self
+                .coordinate_mapper
+                .convert_coords(&sequence.frames[after_next_index].coords);
+
+            let position = catmull_rom_position(p0, p1, p2, p3, interpolation_t);
+            let tangent = catmull_rom_tangent(p0, p1, p2, p3, interpolation_t);
+            (position, tangent[0], tangent[1], 1.0)
+        } else if is_confirmed_warp {
+            // Fade out at the source tile, teleport at the midpoint of this
+            // interval, then fade back in at the destination tile, instead of
+            // either sliding across the map or popping instantaneously.
+            let half_fade_t = (self.warp_fade_ms / self.interval_ms).min(0.5);
+            let t = state.interpolation_t;
+            let switch_t = 0.5;
+
+            let (position, alpha) = if t < switch_t - half_fade_t {
+                (current_pos, 1.0)
+            } else if t < switch_t {
+                let fade = if half_fade_t > 0.0 {
+                    1.0 - (t - (switch_t - half_fade_t)) / half_fade_t
+                } else {
+                    0.0
+                };
+                (current_pos, fade)
+            } else if t < switch_t + half_fade_t {
+                let fade = if half_fade_t > 0.0 { (t - switch_t) / half_fade_t } else { 1.0 };
+                (next_pos, fade)
+            } else {
+                (next_pos, 1.0)
+            };
+
+            (position, next_pos[0] - current_pos[0], next_pos[1] - current_pos[1], alpha)
+        } else {
+            // Linear interpolation (or an unconfirmed jump - just pop in place)
+            let position = [
+                current_pos[0] + (next_pos[0] - current_pos[0]) * interpolation_t,
+                current_pos[1] + (next_pos[1] - current_pos[1]) * interpolation_t,
+            ];
+            (position, next_pos[0] - current_pos[0], next_pos[1] - current_pos[1], 1.0)
+        };
 
         // Determine direction based on movement
-        let dx = next_pos[0] - current_pos[0];
-        let dy = next_pos[1] - current_pos[1];
         let direction = Direction::from_movement(dx, dy);
 
+        // Walk-cycle frame only advances while the sprite is actually moving;
+        // freeze on the idle (first) frame when stationary
+        let age_ms = (state.current_frame_index as f32 + state.interpolation_t) * self.interval_ms;
+        let frame_index = if pixel_distance > 0.0 {
+            self.walk_cycle.frame_index_at(age_ms)
+        } else {
+            self.walk_cycle.first_frame
+        };
+
         Some(SpriteInstance {
             position,
             sprite_id: sequence.sprite_id,
             direction,
+            frame_index,
+            alpha,
         })
     }
 
@@ -110,19 +199,66 @@ impl AnimationInterpolator {
     }
 }
 
+/// Uniform Catmull-Rom position at parameter `t` through P1..P2, using P0 and
+/// P3 as the surrounding control points
+fn catmull_rom_position(p0: [f32; 2], p1: [f32; 2], p2: [f32; 2], p3: [f32; 2], t: f32) -> [f32; 2] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        0.5 * (2.0 * p1[0]
+            + (-p0[0] + p2[0]) * t
+            + (2.0 * p0[0] - 5.0 * p1[0] + 4.0 * p2[0] - p3[0]) * t2
+            + (-p0[0] + 3.0 * p1[0] - 3.0 * p2[0] + p3[0]) * t3),
+        0.5 * (2.0 * p1[1]
+            + (-p0[1] + p2[1]) * t
+            + (2.0 * p0[1] - 5.0 * p1[1] + 4.0 * p2[1] - p3[1]) * t2
+            + (-p0[1] + 3.0 * p1[1] - 3.0 * p2[1] + p3[1]) * t3),
+    ]
+}
+
+/// Derivative of `catmull_rom_position` with respect to `t`, used to derive
+/// facing direction from the curve instead of the straight-line delta
+fn catmull_rom_tangent(p0: [f32; 2], p1: [f32; 2], p2: [f32; 2], p3: [f32; 2], t: f32) -> [f32; 2] {
+    let t2 = t * t;
+    [
+        0.5 * ((-p0[0] + p2[0])
+            + 2.0 * (2.0 * p0[0] - 5.0 * p1[0] + 4.0 * p2[0] - p3[0]) * t
+            + 3.0 * (-p0[0] + 3.0 * p1[0] - 3.0 * p2[0] + p3[0]) * t2),
+        0.5 * ((-p0[1] + p2[1])
+            + 2.0 * (2.0 * p0[1] - 5.0 * p1[1] + 4.0 * p2[1] - p3[1]) * t
+            + 3.0 * (-p0[1] + 3.0 * p1[1] - 3.0 * p2[1] + p3[1]) * t2),
+    ]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::data::{SpriteFrame, SpriteSequence};
+    use crate::data::{InterpolationMode, RepeatMode, SpriteFrame, SpriteSequence};
     use chrono::Utc;
     use std::collections::HashMap;
 
+    fn test_walk_cycle() -> WalkCycle {
+        WalkCycle {
+            frame_count: 4,
+            frame_duration_ms: 150.0,
+            first_frame: 0,
+            repeat_mode: RepeatMode::Repeat,
+        }
+    }
+
     #[test]
     fn test_animation_state_calculation() {
         let mapper = CoordinateMapper {
             regions: HashMap::new(),
         };
-        let interpolator = AnimationInterpolator::new(mapper, 500.0, 60.0);
+        let interpolator = AnimationInterpolator::new(
+            mapper,
+            500.0,
+            60.0,
+            test_walk_cycle(),
+            InterpolationMode::Linear,
+            300.0,
+        );
 
         let sequence = SpriteSequence {
             user: "test".to_string(),
@@ -168,4 +304,61 @@ mod tests {
         assert_eq!(state.current_frame_index, 1);
         assert_eq!(state.next_frame_index, 1);
     }
+
+    #[test]
+    fn test_catmull_rom_spline_matches_linear_at_endpoints() {
+        // At t=0 and t=1 the spline must pass exactly through P1 and P2,
+        // regardless of the surrounding control points
+        let p0 = [0.0, 0.0];
+        let p1 = [16.0, 0.0];
+        let p2 = [32.0, 16.0];
+        let p3 = [48.0, 32.0];
+
+        let start = catmull_rom_position(p0, p1, p2, p3, 0.0);
+        assert!((start[0] - p1[0]).abs() < 0.01);
+        assert!((start[1] - p1[1]).abs() < 0.01);
+
+        let end = catmull_rom_position(p0, p1, p2, p3, 1.0);
+        assert!((end[0] - p2[0]).abs() < 0.01);
+        assert!((end[1] - p2[1]).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_walk_cycle_repeat_modes() {
+        let repeat = WalkCycle {
+            frame_count: 4,
+            frame_duration_ms: 100.0,
+            first_frame: 0,
+            repeat_mode: RepeatMode::Repeat,
+        };
+        assert_eq!(repeat.frame_index_at(0.0), 0);
+        assert_eq!(repeat.frame_index_at(350.0), 3);
+        assert_eq!(repeat.frame_index_at(400.0), 0); // wraps back to start
+
+        let once = WalkCycle {
+            frame_count: 4,
+            frame_duration_ms: 100.0,
+            first_frame: 0,
+            repeat_mode: RepeatMode::Once,
+        };
+        assert_eq!(once.frame_index_at(1000.0), 3); // holds on last frame
+
+        let ping_pong = WalkCycle {
+            frame_count: 3,
+            frame_duration_ms: 100.0,
+            first_frame: 0,
+            repeat_mode: RepeatMode::PingPong,
+        };
+        assert_eq!(ping_pong.frame_index_at(0.0), 0);
+        assert_eq!(ping_pong.frame_index_at(200.0), 2); // end of forward sweep
+        assert_eq!(ping_pong.frame_index_at(300.0), 1); // bouncing back
+
+        let stop = WalkCycle {
+            frame_count: 4,
+            frame_duration_ms: 100.0,
+            first_frame: 2,
+            repeat_mode: RepeatMode::Stop,
+        };
+        assert_eq!(stop.frame_index_at(500.0), 2);
+    }
 }
@@ -1,11 +1,14 @@
 use anyhow::{Context, Result};
 use clap::Parser;
-use sprite_video_renderer::data::{ParquetFilter, CoordinateMapper, ParquetReader};
+use sprite_video_renderer::data::{
+    write_run_delta, CoordinateMapper, ParquetFilter, ParquetReader, RunBundleHeader, RunCoord,
+};
 use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::PathBuf;
 use chrono::Duration;
-use sprite_video_renderer::warp_validator::valid_coordinate_pair_v2;
+use sprite_video_renderer::seekable_zstd;
+use sprite_video_renderer::warp_validator::{valid_coordinate_pair_v2, RouteGraph};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Extract compact runs from parquet files", long_about = None)]
@@ -28,35 +31,37 @@ struct Args {
     #[arg(long, default_value = "60")]
     min_duration_secs: i64,
 
-    /// Maximum coordinates per run
-    // this gets converted to u16 so 2^16 is max safe value! 
-    // 65528 <- safe value with 8 padding
-    // lets try 32768
+    /// Maximum coordinates per run. `RunRecordHeader` packs `coord_count`
+    /// into a u16 field, so this must stay at or below `u16::MAX` (65535) -
+    /// `write_run_delta`'s call to `RunRecordHeader::encode` now enforces
+    /// that bound directly rather than relying on this comment.
     #[arg(long, default_value = "2000")]
     max_coords_per_run: usize,
 
     #[arg(long)]
     pallet_start_only: bool,
 
-}
+    /// Path to a `RouteGraph` JSON file describing the route to split runs
+    /// against - starting maps, their adjacent maps, and the ordered map id
+    /// progression with its allowed-backtrack floor. Replaces the old
+    /// hardcoded single-route arrays so new routes can be defined as data.
+    #[arg(long, default_value = "../../assets/route_kanto_route1.json")]
+    route_spec: PathBuf,
 
-/*
-// original, bigger than needed
-#[repr(C, packed)]
-#[derive(Debug, Clone, Copy)]
-struct CompactCoord {
-    x: u16,
-    y: u16,
-    map_id: u16,
-}
-*/
+    /// `RunBundleHeader::coordinate_scale` recorded in the output's header -
+    /// informational only, `write_compact_run` itself always writes raw
+    /// tile coordinates.
+    #[arg(long, default_value = "1.0")]
+    coordinate_scale: f32,
+
+    /// `RunBundleHeader::sprite_atlas_id` recorded in the output's header.
+    #[arg(long, default_value = "0")]
+    sprite_atlas_id: u8,
+
+    /// `RunBundleHeader::fps_hint` recorded in the output's header.
+    #[arg(long, default_value = "30")]
+    fps_hint: u16,
 
-#[repr(C, packed)]
-#[derive(Debug, Clone, Copy)]
-struct UltraCompactCoord {
-    x: u8,
-    y: u8,
-    map_id: u8,
 }
 
 fn main() -> Result<()> {
@@ -98,6 +103,13 @@ fn main() -> Result<()> {
     }
 
     let coordinate_mapper = CoordinateMapper::load("../../assets/map_data.json").unwrap();
+    let route_graph = RouteGraph::load(&args.route_spec)
+        .context("Failed to load --route-spec")?;
+
+    // A bundle's header only ever goes at the very start of the file, so
+    // only write it the first time this output file is created - reruns
+    // that resume via `--progress-file` just append more run records after it.
+    let output_is_new = !args.output.exists();
 
     // Open output file in append mode
     let mut output_file = BufWriter::new(
@@ -107,6 +119,17 @@ fn main() -> Result<()> {
             .open(&args.output)?
     );
 
+    if output_is_new {
+        let header = RunBundleHeader {
+            flags: 0,
+            coordinate_scale: args.coordinate_scale,
+            sprite_atlas_id: args.sprite_atlas_id,
+            fps_hint: args.fps_hint,
+            map_ids: coordinate_mapper.map_ids(),
+        };
+        header.write(&mut output_file)?;
+    }
+
     // Open progress file in append mode
     let mut progress_writer = BufWriter::new(
         OpenOptions::new()
@@ -116,15 +139,11 @@ fn main() -> Result<()> {
     );
 
     let mut total_runs_extracted = 0;
-    let starting_maps = vec![0u8, 37, 40, 38, 39];
-    let starting_and_adjacent_maps = vec![0u8, 37, 40, 39, 38, 12, 32];
-
-    let required_order_init_idx = 5;
-        // route 1, viridian city, route 2, viridian forrest, pewter city, pewter gym, 
-        // route 3, mt moon route 3, mt moon B1F, mt moon B2F, route 4, cerulean city, 
-        // route 24, route 25, bills house, route 5, route 6, vermillion city
-    let map_id_order_required = [0u8, 37, 40, 38, 39, /**/ 12, 1, 13, 51, 2, 54, 14, 59, 60, 61, 15, 3, 35, 36, 88, 16, 17, 5];
 /*
+route 1, viridian city, route 2, viridian forrest, pewter city, pewter gym,
+route 3, mt moon route 3, mt moon B1F, mt moon B2F, route 4, cerulean city,
+route 24, route 25, bills house, route 5, route 6, vermillion city
+- see `route_graph.map_id_order` in `--route-spec`
 maps: [40]
 maps: [40, 0]
 maps: [40, 0, 39]
@@ -227,7 +246,7 @@ just entered first mt moon cave room
             // Split into runs
             let mut run_start = user_env_start;
 
-            let mut run_map_id_progress = required_order_init_idx;
+            let mut run_map_id_progress = route_graph.progress_init_idx;
 
             for j in (user_env_start + 1)..user_env_end {
                 let time_gap = frames[j].timestamp - frames[j-1].timestamp;
@@ -282,23 +301,8 @@ just entered first mt moon cave room
                 let coordinate_change_valid = warp_valid || contiguous_local_coords_valid;
 
 
-                //let previous_progress_idx_res = map_id_order_required.iter().position(|&x| x == previous_coord[2]);
-                let current_progress_idx_res = map_id_order_required.iter().position(|&x| x == current_coord[2]);
-                let mut legal_backtrack = false;
-                let mut illegal_skip_ahead = false;
-                //if let Some(previous_progress_idx) = previous_progress_idx_res {
-                    if let Some(current_progress_idx) = current_progress_idx_res {
-                        // if have warped backwards but to no further back than viridian city, let this run continue
-                        if current_progress_idx < run_map_id_progress && current_progress_idx > 5 {
-                            legal_backtrack = true;
-                        }
-                        if (current_progress_idx as i32) - (run_map_id_progress as i32) > 1 {
-                            illegal_skip_ahead = true;
-                        } else {
-                            run_map_id_progress = usize::max(run_map_id_progress, current_progress_idx);
-                        }
-                    }
-                //}
+                let (legal_backtrack, illegal_skip_ahead) =
+                    route_graph.evaluate_transition(current_coord[2] as u8, &mut run_map_id_progress);
                 let full_transition_invalid = !(coordinate_change_valid || legal_backtrack);
                 if full_transition_invalid {
                     //if !warp_valid {
@@ -307,11 +311,11 @@ just entered first mt moon cave room
                 }
 
                 let should_split = illegal_skip_ahead || full_transition_invalid || time_gap >= gap_threshold || early_big_jump_fail
-                    || (starting_maps.contains(&curr_map) && !starting_and_adjacent_maps.contains(&prev_map));
+                    || (route_graph.is_starting_map(curr_map as u8) && !route_graph.is_starting_or_adjacent(prev_map as u8));
 
                 if should_split {
                     let duration = frames[j-1].timestamp - frames[run_start].timestamp;
-                    let pallet_start_ok = if args.pallet_start_only { starting_maps.contains(&frames[run_start].coords[2]) } else { true };
+                    let pallet_start_ok = if args.pallet_start_only { route_graph.is_starting_map(frames[run_start].coords[2] as u8) } else { true };
                     if duration >= min_duration && pallet_start_ok && /*coordinate_change_valid &&*/ !early_big_jump_fail {
                         // Write this run
                         write_compact_run(
@@ -325,15 +329,15 @@ just entered first mt moon cave room
                     }
 
                     run_start = j;
-                    run_map_id_progress = required_order_init_idx;
+                    run_map_id_progress = route_graph.progress_init_idx;
                 }
             }
 
             // Final run
             if run_start < user_env_end {
                 let duration = frames[user_env_end - 1].timestamp - frames[run_start].timestamp;
-                let pallet_start_ok = if args.pallet_start_only { starting_maps.contains(&frames[run_start].coords[2]) } else { true };
-                
+                let pallet_start_ok = if args.pallet_start_only { route_graph.is_starting_map(frames[run_start].coords[2] as u8) } else { true };
+
                 if duration >= min_duration && pallet_start_ok {
                     write_compact_run(
                         &mut output_file,
@@ -378,54 +382,34 @@ fn write_compact_run<W: Write>(
     frames: &[sprite_video_renderer::data::SpriteFrame],
     max_coords: usize,
 ) -> Result<()> {
-    let coord_count = frames.len().min(max_coords) as u16;
-
-    // Write sprite_id
-    writer.write_all(&[sprite_id])?;
-
-    // Write coord_count
-    writer.write_all(&coord_count.to_le_bytes())?;
-
-    // Write coords
-    for frame in frames.iter().take(max_coords) {
-
-        // flag out invalid map id or coordinates
-        let compact = match (
-            u8::try_from(frame.coords[0]),
-            u8::try_from(frame.coords[1]),
-            u8::try_from(frame.coords[2]),
-        ) {
-            (Ok(x), Ok(y), Ok(map_id)) => UltraCompactCoord { x, y, map_id },
-            _ => UltraCompactCoord {
-                x: 0,
-                y: 0,
-                map_id: 255,
-            },
-        };
-
-        let bytes = unsafe {
-            std::slice::from_raw_parts(
-                &compact as *const UltraCompactCoord as *const u8,
-                std::mem::size_of::<UltraCompactCoord>(),
-            )
-        };
-
-        writer.write_all(bytes)?;
-    }
+    // Delta/zigzag/varint-encoded at full i64 precision - no more collapsing
+    // warps or large map ids into a (0,0,255) sentinel the way the old
+    // fixed u8-per-field layout did.
+    let coords: Vec<RunCoord> = frames
+        .iter()
+        .take(max_coords)
+        .map(|frame| RunCoord {
+            x: frame.coords[0],
+            y: frame.coords[1],
+            map_id: frame.coords[2],
+        })
+        .collect();
 
-    Ok(())
+    write_run_delta(writer, sprite_id, frames[0].timestamp, &coords)
 }
 
 fn compress_file(path: &PathBuf) -> Result<()> {
     let input = File::open(path)?;
-    let mut reader = BufReader::new(input);
+    let reader = BufReader::new(input);
 
     let output_path = path.with_extension("bin.zst");
-    let output = File::create(&output_path)?;
-    let mut encoder = zstd::Encoder::new(output, 3)?; // Compression level 3 (fast)
+    let mut output = BufWriter::new(File::create(&output_path)?);
 
-    std::io::copy(&mut reader, &mut encoder)?;
-    encoder.finish()?;
+    // Write independent per-page frames plus a seek-table footer (see
+    // `seekable_zstd`) instead of one continuous stream, so a renderer can
+    // decompress a single page of a chunk without re-streaming the whole file.
+    seekable_zstd::encode_seekable(reader, &mut output, 3)?; // Compression level 3 (fast)
+    output.flush()?;
 
     log::info!("Original size: {} MB", path.metadata()?.len() / 1_000_000);
     log::info!("Compressed size: {} MB", output_path.metadata()?.len() / 1_000_000);
@@ -1,18 +1,34 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use rand::Rng;
-use sprite_video_renderer::data::{CoordinateMapper, INVALID_MAP_ID_FLAG};
-use sprite_video_renderer::rendering::{GpuContext, SpriteInstance, SpriteRenderer, TextureAtlas};
-use sprite_video_renderer::video::ProResEncoder;
+use rayon::prelude::*;
+use sprite_video_renderer::data::{
+    decode_delta_coords, BundleCoord, CoordinateMapper, RepeatMode, RunBundleHeader, RunCoord,
+    RunRecordHeader, WalkCycle, INVALID_MAP_ID_FLAG, RUN_ENCODING_DELTA_VARINT,
+};
+use sprite_video_renderer::rendering::{GpuContext, MapRenderer, SpriteInstance, SpriteRenderer, TextureAtlas};
+use sprite_video_renderer::seekable_zstd::{self, SeekTableEntry};
+use sprite_video_renderer::video::{BlockCodecEncoder, ProResEncoder};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufReader, Read};
-use std::path::PathBuf;
-use std::collections::HashSet;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_CANVAS_SIZE: u32 = 8192;
+
+/// Round `value` up to the next multiple of 4 - `GpuContext::read_pixels_split`'s
+/// writeback compute pass packs 4 pixels per row-word and requires it.
+fn round_up_to_4(value: u32) -> u32 {
+    (value + 3) / 4 * 4
+}
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Render compact runs to video", long_about = None)]
 struct Args {
-    /// Input compact runs file (compressed or uncompressed)
+    /// Input compact runs file (compressed or uncompressed), or a directory of
+    /// them - when a directory is given, every `.compact`/`.zst` file inside
+    /// is rendered to its own video under `--output-dir`
     #[arg(long)]
     input: PathBuf,
 
@@ -24,21 +40,32 @@ struct Args {
     #[arg(long, default_value = "../../assets/map_data.json")]
     map_data: PathBuf,
 
-    /// Output video file path
+    /// Output video file path (single-file mode only; ignored when `--input`
+    /// is a directory, see `--output-dir`)
     #[arg(long, default_value = "compact_runs_output.mov")]
     output: PathBuf,
 
+    /// Directory to write one video per input into (directory mode only)
+    #[arg(long, default_value = "compact_runs_batch_output")]
+    output_dir: PathBuf,
+
     /// Frame rate
     #[arg(long, default_value = "60")]
     fps: u32,
 
-    /// Canvas width
-    #[arg(long, default_value = "8192")]
-    width: u32,
+    /// Canvas width. Overrides `--scale` when set.
+    #[arg(long)]
+    width: Option<u32>,
 
-    /// Canvas height
-    #[arg(long, default_value = "8192")]
-    height: u32,
+    /// Canvas height. Overrides `--scale` when set.
+    #[arg(long)]
+    height: Option<u32>,
+
+    /// Multiplier applied to the default 8192x8192 canvas when `--width`/
+    /// `--height` aren't given, e.g. `--scale 0.25` renders 2048x2048
+    /// thumbnails
+    #[arg(long, default_value = "1.0")]
+    scale: f32,
 
     /// Interval between coordinate points in milliseconds (base interval)
     #[arg(long, default_value = "500")]
@@ -55,6 +82,109 @@ struct Args {
     /// Maximum number of frames to render (for testing)
     #[arg(long)]
     max_frames: Option<usize>,
+
+    /// Optional path to also write frames through a 4x4 temporal block-skip
+    /// codec (see BlockCodecEncoder) - a side-channel encoding that exploits
+    /// how little changes frame-to-frame when the map background is static
+    /// and only a few sprites are moving. Single-file mode only.
+    #[arg(long)]
+    block_codec_output: Option<PathBuf>,
+
+    /// Quality for --block-codec-output: 0 skips/fills blocks as
+    /// aggressively as the thresholds allow, 100 (default) only skips
+    /// byte-identical blocks and only fills already-solid-color blocks
+    #[arg(long, default_value = "100")]
+    quality: u8,
+
+    /// For --block-codec-output: force every block to be re-sent (never
+    /// `CMD_COPY_PREVIOUS`) every N frames, so a decoder can seek or recover
+    /// from a dropped frame. 0 disables forced keyframes.
+    #[arg(long, default_value = "300")]
+    keyframe_interval: u64,
+
+    /// Number of frames in the walk-cycle animation
+    #[arg(long, default_value = "4")]
+    walk_frame_count: u32,
+
+    /// Duration of each walk-cycle frame in milliseconds
+    #[arg(long, default_value = "150")]
+    walk_frame_duration_ms: f32,
+
+    /// First frame index of the walk-cycle within the sprite sheet column
+    #[arg(long, default_value = "0")]
+    walk_first_frame: u32,
+
+    /// How the walk-cycle frame repeats once it reaches the end
+    #[arg(long, value_enum, default_value = "repeat")]
+    walk_repeat_mode: WalkRepeatModeArg,
+
+    /// Optional map background image, stretched across the full canvas behind
+    /// sprites (the canvas already represents the whole world in the same
+    /// coordinate space CoordinateMapper maps sprite positions into)
+    #[arg(long)]
+    map_image: Option<PathBuf>,
+
+    /// Opacity of the map background layer, 0.0 (invisible) to 1.0 (opaque)
+    #[arg(long, default_value = "1.0")]
+    map_opacity: f32,
+
+    /// Tint multiplier applied to the map background's red channel, e.g.
+    /// 0.5 to dim it so sprite trails read clearly on top
+    #[arg(long, default_value = "1.0")]
+    map_tint_r: f32,
+
+    /// Tint multiplier applied to the map background's green channel
+    #[arg(long, default_value = "1.0")]
+    map_tint_g: f32,
+
+    /// Tint multiplier applied to the map background's blue channel
+    #[arg(long, default_value = "1.0")]
+    map_tint_b: f32,
+
+    /// Zoom factor for the map background: 1.0 (default) frames the whole
+    /// map, >1.0 crops to a 1/zoom-sized sub-region centered on
+    /// `--map-center-x`/`--map-center-y`
+    #[arg(long, default_value = "1.0")]
+    map_zoom: f32,
+
+    /// Center-x of the zoomed map sub-region, in 0..1 texture-space; only
+    /// relevant when `--map-zoom` > 1.0
+    #[arg(long, default_value = "0.5")]
+    map_center_x: f32,
+
+    /// Center-y of the zoomed map sub-region, in 0..1 texture-space; only
+    /// relevant when `--map-zoom` > 1.0
+    #[arg(long, default_value = "0.5")]
+    map_center_y: f32,
+
+    /// MSAA sample count for the offscreen render target, e.g. 4. Silently
+    /// lowered to the highest count the adapter supports; 1 disables MSAA.
+    #[arg(long, default_value = "4")]
+    sample_count: u32,
+
+    /// Render on the CPU instead of a GPU (software rasterizer), for headless
+    /// boxes with no GPU adapter. Produces identical pixels, just slower.
+    #[arg(long, default_value_t = false)]
+    force_cpu: bool,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum WalkRepeatModeArg {
+    Once,
+    Repeat,
+    PingPong,
+    Stop,
+}
+
+impl From<WalkRepeatModeArg> for RepeatMode {
+    fn from(mode: WalkRepeatModeArg) -> Self {
+        match mode {
+            WalkRepeatModeArg::Once => RepeatMode::Once,
+            WalkRepeatModeArg::Repeat => RepeatMode::Repeat,
+            WalkRepeatModeArg::PingPong => RepeatMode::PingPong,
+            WalkRepeatModeArg::Stop => RepeatMode::Stop,
+        }
+    }
 }
 
 /*
@@ -68,41 +198,150 @@ struct CompactCoord {
 }
 */
 
-#[repr(C, packed)]
-#[derive(Debug, Clone, Copy)]
-struct UltraCompactCoordMem {
-    x: u8,
-    y: u8,
-    map_id: u8,
-}
-
 #[derive(Debug)]
 struct CompactRun {
     sprite_id: u8,
-    coords: Vec<UltraCompactCoordMem>,
+    coords: Vec<RunCoord>,
 }
 
 #[derive(Debug, Clone)]
 struct CompactRunMetadata {
     sprite_id: u8,
     coord_count: usize,
+    /// For `RUN_ENCODING_FIXED` runs: the file offset where the fixed-width
+    /// coord payload starts, read a chunk window at a time by
+    /// `load_chunk_coords`. Unused for `RUN_ENCODING_DELTA_VARINT` runs,
+    /// whose coords are already fully decoded into `decoded_delta_coords`
+    /// (varint lengths vary per coord, so byte-offset random access into the
+    /// middle of the stream isn't possible without decoding from the start
+    /// anyway).
     file_offset: u64,
+    decoded_delta_coords: Option<Vec<RunCoord>>,
 }
 
 fn main() -> Result<()> {
-    pollster::block_on(run())
-}
-
-async fn run() -> Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
     let args = Args::parse();
 
+    let (inputs, outputs, block_codec_outputs) = if args.input.is_dir() {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&args.input)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && matches!(
+                        path.extension().and_then(|ext| ext.to_str()),
+                        Some("compact") | Some("zst")
+                    )
+            })
+            .collect();
+        files.sort();
+
+        if files.is_empty() {
+            log::warn!("No .compact/.zst files found in {:?}", args.input);
+            return Ok(());
+        }
+
+        std::fs::create_dir_all(&args.output_dir)?;
+
+        let outputs: Vec<PathBuf> = files
+            .iter()
+            .map(|f| args.output_dir.join(format!("{}.mov", stem_of(f))))
+            .collect();
+
+        if args.block_codec_output.is_some() {
+            log::warn!("--block-codec-output is ignored in directory/batch mode");
+        }
+        let block_codec_outputs: Vec<Option<PathBuf>> = vec![None; files.len()];
+
+        log::info!("Batch mode: found {} files in {:?}", files.len(), args.input);
+        (files, outputs, block_codec_outputs)
+    } else {
+        (
+            vec![args.input.clone()],
+            vec![args.output.clone()],
+            vec![args.block_codec_output.clone()],
+        )
+    };
+
+    let multi_progress = MultiProgress::new();
+    let files_bar = multi_progress.add(ProgressBar::new(inputs.len() as u64));
+    files_bar.set_style(
+        ProgressStyle::with_template("files [{bar:30}] {pos}/{len} {msg}")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+
+    let jobs: Vec<(PathBuf, PathBuf, Option<PathBuf>)> = inputs
+        .into_iter()
+        .zip(outputs)
+        .zip(block_codec_outputs)
+        .map(|((input, output), block_codec_output)| (input, output, block_codec_output))
+        .collect();
+
+    let results: Vec<Result<()>> = jobs
+        .par_iter()
+        .map(|(input, output, block_codec_output)| {
+            let frame_bar = multi_progress.add(ProgressBar::new(0));
+            frame_bar.set_style(
+                ProgressStyle::with_template("  {msg} [{bar:30}] {pos}/{len} ({per_sec}, eta {eta})")
+                    .unwrap()
+                    .progress_chars("=> "),
+            );
+            frame_bar.set_message(stem_of(input));
+
+            let result = pollster::block_on(render_one(
+                &args,
+                input,
+                output,
+                block_codec_output.as_deref(),
+                &frame_bar,
+            ));
+
+            if let Err(e) = &result {
+                log::error!("Failed to render {:?}: {:#}", input, e);
+            }
+
+            frame_bar.finish_and_clear();
+            files_bar.inc(1);
+            result
+        })
+        .collect();
+
+    files_bar.finish_with_message("done");
+
+    let failures = results.iter().filter(|r| r.is_err()).count();
+    if failures > 0 {
+        anyhow::bail!("{} of {} files failed to render", failures, jobs.len());
+    }
+
+    Ok(())
+}
+
+async fn render_one(
+    args: &Args,
+    input: &Path,
+    output: &Path,
+    block_codec_output: Option<&Path>,
+    frame_bar: &ProgressBar,
+) -> Result<()> {
     log::info!("=== Rendering compact runs ===");
-    log::info!("Input file: {:?}", args.input);
-    log::info!("Output: {:?}", args.output);
+    log::info!("Input file: {:?}", input);
+    log::info!("Output: {:?}", output);
     log::info!("Speed multiplier: {}x ({}ms between coords)", args.speed_multiplier, args.interval_ms / args.speed_multiplier);
 
+    // Rounded up to a multiple of 4 - `GpuContext::read_pixels_split` (used
+    // below to feed `ProResEncoder`) requires it for its writeback compute pass.
+    let width = round_up_to_4(
+        args.width
+            .unwrap_or_else(|| (DEFAULT_CANVAS_SIZE as f32 * args.scale).round() as u32),
+    );
+    let height = round_up_to_4(
+        args.height
+            .unwrap_or_else(|| (DEFAULT_CANVAS_SIZE as f32 * args.scale).round() as u32),
+    );
+
     let start_step = args.start_step.unwrap_or(0);
     if start_step > 0 {
         log::info!("Starting from step/coord index: {}", start_step);
@@ -114,7 +353,8 @@ async fn run() -> Result<()> {
 
     // Load run metadata from file
     log::info!("Loading compact run metadata...");
-    let metadata = load_compact_runs_metadata(&args.input)?;
+    let mut page_cache = PageCache::open(input)?;
+    let (_bundle_header, metadata) = load_compact_runs_metadata(&mut page_cache)?;
     log::info!("Loaded {} run metadata entries", metadata.len());
 
     if metadata.is_empty() {
@@ -151,6 +391,7 @@ async fn run() -> Result<()> {
     log::info!("Animation: {:.2} seconds, {} frames @ {} fps (from step {} to end)",
                total_frames as f32 / args.fps as f32, total_frames, args.fps, start_step);
     log::info!("Total runs: {}", metadata.len());
+    frame_bar.set_length(total_frames as u64);
 
     // Calculate chunk size (1/8 of max coords)
     let chunk_size = (max_coords + 7) / 8; // Round up
@@ -159,26 +400,53 @@ async fn run() -> Result<()> {
 
     // Initialize GPU
     log::info!("Initializing GPU...");
-    let gpu = GpuContext::new(args.width, args.height).await?;
+    let gpu = if args.force_cpu {
+        GpuContext::new_cpu(width, height, args.sample_count).await?
+    } else {
+        GpuContext::new(width, height, args.sample_count).await?
+    };
 
     // Load sprite sheet
     log::info!("Loading sprite sheet...");
-    let texture_atlas = TextureAtlas::load(&gpu.device, &gpu.queue, &args.sprite_sheet)?;
+    let texture_atlas = TextureAtlas::load(&gpu.device, &gpu.queue, std::slice::from_ref(&args.sprite_sheet))?;
 
     // Create renderer
     log::info!("Creating renderer...");
-    let renderer = SpriteRenderer::new(
+    let mut renderer = SpriteRenderer::new(
         &gpu.device,
         &gpu.queue,
-        &texture_atlas,
-        args.width,
-        args.height,
+        &[&texture_atlas],
+        width,
+        height,
         metadata.len() + 1000,
+        gpu.sample_count,
+    )?;
+
+    // Create map background renderer
+    log::info!("Creating map background renderer...");
+    let map_renderer = MapRenderer::new(
+        &gpu.device,
+        &gpu.queue,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        args.map_image.as_ref(),
+        args.map_opacity,
+        [args.map_tint_r, args.map_tint_g, args.map_tint_b],
+        args.map_zoom,
+        [args.map_center_x, args.map_center_y],
+        gpu.sample_count,
     )?;
 
     // Create encoder
     log::info!("Starting video encoder...");
-    let mut encoder = ProResEncoder::new(&args.output, args.width, args.height, args.fps)?;
+    let mut encoder = ProResEncoder::new(output, width, height, args.fps)?;
+
+    let mut block_codec_encoder = match block_codec_output {
+        Some(path) => {
+            log::info!("Also writing block-skip codec output to {:?} (quality {})", path, args.quality);
+            Some(BlockCodecEncoder::new(path, width, height, args.quality, args.keyframe_interval)?)
+        }
+        None => None,
+    };
 
     // Track last direction for each run (persists across chunks)
     let mut run_directions: Vec<sprite_video_renderer::data::Direction> =
@@ -191,6 +459,13 @@ async fn run() -> Result<()> {
         .collect();
     log::info!("Generated random offsets for {} runs", run_offsets.len());
 
+    let walk_cycle = WalkCycle {
+        frame_count: args.walk_frame_count,
+        frame_duration_ms: args.walk_frame_duration_ms,
+        first_frame: args.walk_first_frame,
+        repeat_mode: args.walk_repeat_mode.into(),
+    };
+
     // Use sliding window to keep only necessary chunks in memory
     log::info!("Rendering {} frames with sliding chunk window...", total_frames);
     let start_time = std::time::Instant::now();
@@ -222,7 +497,7 @@ async fn run() -> Result<()> {
                        frame_number, needed_chunk_idx + 1, num_chunks,
                        loaded_chunk_start, loaded_chunk_end);
 
-            loaded_runs = load_chunk_coords(&args.input, &metadata, loaded_chunk_start, loaded_chunk_end)?;
+            loaded_runs = load_chunk_coords(&mut page_cache, &metadata, loaded_chunk_start, loaded_chunk_end)?;
             current_chunk_idx = Some(needed_chunk_idx);
         }
 
@@ -265,9 +540,8 @@ async fn run() -> Result<()> {
             let current_coord = &run.coords[local_coord_index];
             let next_coord = &run.coords[local_next_index];
 
-            // Convert to i64 for coordinate mapper
-            let current_coords = [current_coord.x as i64, current_coord.y as i64, current_coord.map_id as i64];
-            let next_coords = [next_coord.x as i64, next_coord.y as i64, next_coord.map_id as i64];
+            let current_coords = [current_coord.x, current_coord.y, current_coord.map_id];
+            let next_coords = [next_coord.x, next_coord.y, next_coord.map_id];
 
             // Convert to pixel positions
             let current_pos = coordinate_mapper.convert_coords(&current_coords);
@@ -317,11 +591,25 @@ async fn run() -> Result<()> {
                 run.sprite_id.min(54)
             };
 
-            let tex_coords = texture_atlas.get_sprite_tex_coords(sprite_index_capped, run_directions[run_idx]);
+            // Walk-cycle frame only advances while the sprite is actually
+            // moving; freeze on the idle (first) frame when stationary, phased
+            // per-run by run_offsets so the crowd doesn't step in lockstep
+            let age_ms = progress * effective_interval_ms;
+            let walk_frame = if pixel_distance > 0.0 {
+                walk_cycle.frame_index_at(age_ms)
+            } else {
+                walk_cycle.first_frame
+            };
+
+            let tex_coords =
+                texture_atlas.get_sprite_tex_coords(0, sprite_index_capped, run_directions[run_idx], walk_frame);
 
             sprite_instances.push(SpriteInstance {
                 position,
                 tex_rect: tex_coords,
+                layer: 0,
+                alpha: 1.0,
+                tint: [1.0, 1.0, 1.0, 1.0],
             });
         }
 
@@ -333,47 +621,39 @@ async fn run() -> Result<()> {
             if !sprite_instances.is_empty() {
                 log::info!("Sample sprite positions (first 10):");
                 for (i, instance) in sprite_instances.iter().take(10).enumerate() {
-                    let in_bounds_x = instance.position[0] >= 0.0 && instance.position[0] < args.width as f32;
-                    let in_bounds_y = instance.position[1] >= 0.0 && instance.position[1] < args.height as f32;
+                    let in_bounds_x = instance.position[0] >= 0.0 && instance.position[0] < width as f32;
+                    let in_bounds_y = instance.position[1] >= 0.0 && instance.position[1] < height as f32;
                     log::info!("  Sprite {}: pos=[{:.1}, {:.1}] in_bounds=({}, {})",
                                i, instance.position[0], instance.position[1], in_bounds_x, in_bounds_y);
                 }
             }
         }
 
-        // Render frame
+        // Draw the map background first, then sprites on top without re-clearing
+        let (color_view, resolve_view) = gpu.color_attachment_target();
+        map_renderer.render(&gpu.device, &gpu.queue, color_view, resolve_view)?;
         renderer.render(
             &gpu.device,
             &gpu.queue,
-            &gpu.render_texture_view,
+            color_view,
+            resolve_view,
             &sprite_instances,
+            false,
         )?;
 
-        // Read pixels
-        let pixels = gpu.read_pixels().await?;
+        // Read pixels, already split into RGB/alpha planes by the GPU writeback pass
+        let (rgb, mask) = gpu.read_pixels_split().await?;
 
         // Write to encoder
-        encoder.write_frame(&pixels)?;
-
-        // Progress logging
-        if frame_number % 60 == 0 || frame_number == total_frames - 1 {
-            let elapsed = start_time.elapsed().as_secs_f32();
-            let fps_actual = (frame_number + 1) as f32 / elapsed;
-            let progress = (frame_number + 1) as f32 / total_frames as f32 * 100.0;
-            let eta = (total_frames - frame_number - 1) as f32 / fps_actual;
-
-            log::info!(
-                "Progress: {:.1}% ({}/{}) | {:.1} fps | ETA: {:.1}s | Sprites: {} | Chunk: {}/{}",
-                progress,
-                frame_number + 1,
-                total_frames,
-                fps_actual,
-                eta,
-                sprite_instances.len(),
-                current_chunk_idx.map(|i| i + 1).unwrap_or(0),
-                num_chunks
-            );
+        encoder.write_frame_split(&rgb, &mask)?;
+
+        if let Some(block_encoder) = &mut block_codec_encoder {
+            // Block codec diffs whole RGBA pixels, so it still needs its own readback
+            let pixels = gpu.read_pixels().await?;
+            block_encoder.write_frame(&pixels)?;
         }
+
+        frame_bar.set_position((frame_number + 1) as u64);
     }
 
     let elapsed = start_time.elapsed();
@@ -387,138 +667,253 @@ async fn run() -> Result<()> {
     log::info!("Finalizing video...");
     encoder.finish()?;
 
-    log::info!("âœ“ Done! Created {:?}", args.output);
+    if let Some(block_encoder) = block_codec_encoder {
+        block_encoder.finish()?;
+    }
+
+    log::info!("âœ“ Done! Created {:?}", output);
 
     Ok(())
 }
 
-fn load_compact_runs_metadata(path: &PathBuf) -> Result<Vec<CompactRunMetadata>> {
-    let mut reader: Box<dyn Read> = if path.extension().and_then(|s| s.to_str()) == Some("zst") {
-        // Decompress
-        log::info!("Decompressing zstd file...");
-        let file = File::open(path)?;
-        Box::new(zstd::Decoder::new(file)?)
-    } else {
-        // Read uncompressed
-        let file = File::open(path)?;
-        Box::new(BufReader::new(file))
+fn stem_of(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "output".to_string())
+}
+
+/// Random-access view over a (possibly zstd-compressed) compact-runs file
+/// that never decompresses the same byte range twice. For `.zst` inputs
+/// written with a seek table (see `seekable_zstd`), each `PAGE_SIZE` page is
+/// decompressed independently and cached by page index on first use - the
+/// metadata scan and every sliding chunk-window read after it hit the cache
+/// instead of restreaming the file from byte zero. Files without a seek
+/// table (plain input, or legacy single-stream `.zst`) fall back to
+/// decompressing once into memory, which still avoids the old behavior of
+/// re-decompressing from scratch on every chunk switch.
+struct PageCache {
+    file: File,
+    seek_table: Option<Vec<SeekTableEntry>>,
+    pages: HashMap<usize, Vec<u8>>,
+    fallback: Option<Vec<u8>>,
+    is_zst: bool,
+}
+
+impl PageCache {
+    fn open(path: &Path) -> Result<Self> {
+        let is_zst = path.extension().and_then(|s| s.to_str()) == Some("zst");
+        let mut file = File::open(path)?;
+
+        let seek_table = if is_zst {
+            seekable_zstd::read_seek_table(&mut file)?
+        } else {
+            None
+        };
+
+        if seek_table.is_some() {
+            log::info!("{:?}: found zstd seek table, paging decompression by page", path);
+        } else if is_zst {
+            log::info!("{:?}: no seek table (legacy stream), decompressing once into memory", path);
+        }
+
+        Ok(Self {
+            file,
+            seek_table,
+            pages: HashMap::new(),
+            fallback: None,
+            is_zst,
+        })
+    }
+
+    /// Total decompressed length of the underlying stream
+    fn total_len(&mut self) -> Result<u64> {
+        if let Some(seek_table) = &self.seek_table {
+            return Ok(seek_table.iter().map(|e| e.decompressed_len).sum());
+        }
+        self.populate_fallback()?;
+        Ok(self.fallback.as_ref().unwrap().len() as u64)
+    }
+
+    fn populate_fallback(&mut self) -> Result<()> {
+        if self.fallback.is_some() {
+            return Ok(());
+        }
+        self.file.seek(SeekFrom::Start(0))?;
+        let mut buffer = Vec::new();
+        if self.is_zst {
+            zstd::Decoder::new(&self.file)?.read_to_end(&mut buffer)?;
+        } else {
+            BufReader::new(&self.file).read_to_end(&mut buffer)?;
+        }
+        self.fallback = Some(buffer);
+        Ok(())
+    }
+
+    /// Read `len` decompressed bytes starting at decompressed byte `offset`
+    fn read_at(&mut self, offset: u64, len: u64) -> Result<Vec<u8>> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let want_end = offset + len;
+
+        if let Some(seek_table) = self.seek_table.clone() {
+            let mut result = vec![0u8; len as usize];
+            let mut page_start = 0u64;
+
+            for (page_index, entry) in seek_table.iter().enumerate() {
+                let page_end = page_start + entry.decompressed_len;
+                if page_end > offset && page_start < want_end {
+                    if !self.pages.contains_key(&page_index) {
+                        let decoded = seekable_zstd::decode_page(&mut self.file, entry)?;
+                        self.pages.insert(page_index, decoded);
+                    }
+                    let page = &self.pages[&page_index];
+                    let overlap_start = offset.max(page_start);
+                    let overlap_end = want_end.min(page_end);
+                    let page_local_start = (overlap_start - page_start) as usize;
+                    let page_local_end = (overlap_end - page_start) as usize;
+                    let result_start = (overlap_start - offset) as usize;
+                    let result_end = (overlap_end - offset) as usize;
+                    result[result_start..result_end]
+                        .copy_from_slice(&page[page_local_start..page_local_end]);
+                }
+                page_start = page_end;
+                if page_start >= want_end {
+                    break;
+                }
+            }
+            return Ok(result);
+        }
+
+        self.populate_fallback()?;
+        let buffer = self.fallback.as_ref().unwrap();
+        Ok(buffer[offset as usize..want_end as usize].to_vec())
+    }
+
+    fn read_exact(&mut self, pos: &mut u64, buf: &mut [u8]) -> Result<()> {
+        let data = self.read_at(*pos, buf.len() as u64)?;
+        buf.copy_from_slice(&data);
+        *pos += buf.len() as u64;
+        Ok(())
+    }
+}
+
+/// Adapts `PageCache`'s offset-based `read_at`/`read_exact` to `std::io::Read`
+/// for the one-shot header parse (`RunBundleHeader::read` wants a `Read`,
+/// while the run-record scan below stays on `PageCache`'s own pos-tracking
+/// API so it can skip coord payloads without reading them into memory).
+struct PageCacheReader<'a> {
+    cache: &'a mut PageCache,
+    pos: u64,
+}
+
+impl<'a> Read for PageCacheReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let data = self
+            .cache
+            .read_at(self.pos, buf.len() as u64)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        buf[..data.len()].copy_from_slice(&data);
+        self.pos += data.len() as u64;
+        Ok(data.len())
+    }
+}
+
+/// Reads the bundle header, then scans every run record's fixed-size header
+/// (sprite id, start timestamp, coord count), recording the file offset of
+/// each run's coord payload. Fixed-width payloads aren't read here - their
+/// coords are loaded lazily, a sliding chunk at a time, by
+/// `load_chunk_coords`; delta/varint payloads are read and decoded in full.
+fn load_compact_runs_metadata(cache: &mut PageCache) -> Result<(RunBundleHeader, Vec<CompactRunMetadata>)> {
+    let total_len = cache.total_len()?;
+
+    let (header, mut pos) = {
+        let mut reader = PageCacheReader { cache: &mut *cache, pos: 0 };
+        let header = RunBundleHeader::read(&mut reader)?;
+        (header, reader.pos)
     };
+    log::info!(
+        "Run bundle: sprite_atlas_id={} fps_hint={} {} known map ids",
+        header.sprite_atlas_id,
+        header.fps_hint,
+        header.map_ids.len()
+    );
 
     let mut metadata = Vec::new();
-    let mut current_offset: u64 = 0;
-    let mut all_sprite_ids = HashSet::new();
-
-    loop {
-        // Read sprite_id
-        let mut sprite_id_buf = [0u8; 1];
-        match reader.read_exact(&mut sprite_id_buf) {
-            Ok(_) => {},
-            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-            Err(e) => return Err(e.into()),
-        }
-        let sprite_id = sprite_id_buf[0];
-        all_sprite_ids.insert(sprite_id);
-        current_offset += 1;
 
-        // Read coord_count
-        let mut count_buf = [0u8; 2];
-        reader.read_exact(&mut count_buf)?;
-        let coord_count = u16::from_le_bytes(count_buf) as usize;
-        current_offset += 2;
+    while pos < total_len {
+        let mut record_len_buf = [0u8; 4];
+        cache.read_exact(&mut pos, &mut record_len_buf)?;
+        let record_len = u32::from_le_bytes(record_len_buf) as u64;
+
+        let mut run_header_buf = [0u8; RunRecordHeader::ENCODED_SIZE];
+        cache.read_exact(&mut pos, &mut run_header_buf)?;
+        let run_header = RunRecordHeader::decode(run_header_buf)?;
+        let payload_len = record_len - RunRecordHeader::ENCODED_SIZE as u64;
+
+        // Delta/varint-encoded coords can't be randomly seeked into (each
+        // coord's byte length varies), so decode the whole run now, while
+        // the fixed-width legacy format keeps lazily reading a chunk window
+        // at a time from `file_offset` in `load_chunk_coords`.
+        let decoded_delta_coords = if run_header.encoding == RUN_ENCODING_DELTA_VARINT {
+            let payload = cache.read_at(pos, payload_len)?;
+            let mut payload_reader = payload.as_slice();
+            Some(decode_delta_coords(&mut payload_reader, run_header.coord_count)?)
+        } else {
+            None
+        };
 
         // Store metadata with the offset where coords start
-        let coords_offset = current_offset;
         metadata.push(CompactRunMetadata {
-            sprite_id,
-            coord_count,
-            file_offset: coords_offset,
+            sprite_id: run_header.sprite_id,
+            coord_count: run_header.coord_count,
+            file_offset: pos,
+            decoded_delta_coords,
         });
 
-        // Skip over the coordinate data
-        let bytes_to_skip = coord_count * std::mem::size_of::<UltraCompactCoordMem>();
-        let mut skip_buffer = vec![0u8; bytes_to_skip];
-        reader.read_exact(&mut skip_buffer)?;
-        current_offset += bytes_to_skip as u64;
+        pos += payload_len;
 
         if metadata.len() % 100000 == 0 {
             log::info!("Loaded {} run metadata entries", metadata.len());
         }
     }
 
-    Ok(metadata)
+    Ok((header, metadata))
 }
 
 fn load_chunk_coords(
-    path: &PathBuf,
+    cache: &mut PageCache,
     metadata: &[CompactRunMetadata],
     chunk_start: usize,
     chunk_end: usize,
 ) -> Result<Vec<CompactRun>> {
-    let mut reader: Box<dyn Read> = if path.extension().and_then(|s| s.to_str()) == Some("zst") {
-        // For compressed files, we need to read from the beginning
-        let file = File::open(path)?;
-        Box::new(zstd::Decoder::new(file)?)
-    } else {
-        let file = File::open(path)?;
-        Box::new(BufReader::new(file))
-    };
-
     let mut runs = Vec::with_capacity(metadata.len());
-    let mut current_file_pos: u64 = 0;
-    let mut buffer = vec![0u8; 1024 * 1024];
+    let coord_size = BundleCoord::ENCODED_SIZE as u64;
 
     for meta in metadata {
-        // Skip to this run's data if needed
-        if current_file_pos < meta.file_offset {
-            let bytes_to_skip = meta.file_offset - current_file_pos;
-            let mut skip_buf = vec![0u8; bytes_to_skip as usize];
-            reader.read_exact(&mut skip_buf)?;
-            current_file_pos = meta.file_offset;
-        }
-
         // Calculate which coords to load for this run
         let run_chunk_start = chunk_start.min(meta.coord_count);
         let run_chunk_end = chunk_end.min(meta.coord_count);
-        let coords_to_load = if run_chunk_start < run_chunk_end {
-            run_chunk_end - run_chunk_start
-        } else {
-            0
-        };
-
-        // Skip coords before chunk_start
-        let skip_before = run_chunk_start * std::mem::size_of::<UltraCompactCoordMem>();
-        if skip_before > 0 {
-            let mut skip_buf = vec![0u8; skip_before];
-            reader.read_exact(&mut skip_buf)?;
-            current_file_pos += skip_before as u64;
-        }
-
-        // Load coords in this chunk
-        let mut coords = Vec::with_capacity(coords_to_load);
-        if coords_to_load > 0 {
-            let bytes_to_read = coords_to_load * std::mem::size_of::<UltraCompactCoordMem>();
-            if buffer.len() < bytes_to_read {
-                buffer.resize(bytes_to_read, 0);
-            }
-            reader.read_exact(&mut buffer[..bytes_to_read])?;
-            current_file_pos += bytes_to_read as u64;
+        let coords_to_load = run_chunk_end.saturating_sub(run_chunk_start);
 
-            for i in 0..coords_to_load {
-                let offset = i * std::mem::size_of::<UltraCompactCoordMem>();
-                let coord = unsafe {
-                    std::ptr::read_unaligned(buffer[offset..].as_ptr() as *const UltraCompactCoordMem)
-                };
-                coords.push(coord);
+        let coords = if let Some(decoded) = &meta.decoded_delta_coords {
+            decoded[run_chunk_start..run_chunk_start + coords_to_load].to_vec()
+        } else {
+            let mut coords = Vec::with_capacity(coords_to_load);
+            if coords_to_load > 0 {
+                let byte_offset = meta.file_offset + run_chunk_start as u64 * coord_size;
+                let byte_len = coords_to_load as u64 * coord_size;
+                let buffer = cache.read_at(byte_offset, byte_len)?;
+
+                for i in 0..coords_to_load {
+                    let offset = i * BundleCoord::ENCODED_SIZE;
+                    let bytes: [u8; BundleCoord::ENCODED_SIZE] =
+                        buffer[offset..offset + BundleCoord::ENCODED_SIZE].try_into().unwrap();
+                    coords.push(BundleCoord::from_bytes(bytes).into());
+                }
             }
-        }
-
-        // Skip coords after chunk_end
-        let skip_after = (meta.coord_count - run_chunk_end) * std::mem::size_of::<UltraCompactCoordMem>();
-        if skip_after > 0 {
-            let mut skip_buf = vec![0u8; skip_after];
-            reader.read_exact(&mut skip_buf)?;
-            current_file_pos += skip_after as u64;
-        }
+            coords
+        };
 
         runs.push(CompactRun {
             sprite_id: meta.sprite_id,
@@ -528,4 +923,3 @@ fn load_chunk_coords(
 
     Ok(runs)
 }
-
@@ -1,8 +1,10 @@
 use anyhow::Result;
 use clap::Parser;
 use sprite_video_renderer::animation::AnimationInterpolator;
-use sprite_video_renderer::data::{CoordinateMapper, ParquetFilter, ParquetReader};
-use sprite_video_renderer::rendering::{GpuContext, SpriteInstance, SpriteRenderer, TextureAtlas};
+use sprite_video_renderer::data::{
+    CoordinateMapper, InterpolationMode, ParquetFilter, ParquetReader, RepeatMode, WalkCycle,
+};
+use sprite_video_renderer::rendering::{GpuContext, MapRenderer, SpriteInstance, SpriteRenderer, TextureAtlas};
 use sprite_video_renderer::video::ProResEncoder;
 use std::path::PathBuf;
 
@@ -44,6 +46,11 @@ struct Args {
     /// Interval between coordinate points in milliseconds
     #[arg(long, default_value = "500")]
     interval_ms: u32,
+
+    /// Optional map background image, stretched across the full canvas
+    /// behind sprites
+    #[arg(long)]
+    map_image: Option<PathBuf>,
 }
 
 fn main() -> Result<()> {
@@ -102,8 +109,21 @@ async fn run() -> Result<()> {
         }
     }
 
-    // Create animation interpolator
-    let interpolator = AnimationInterpolator::new(coordinate_mapper, interval_ms, fps as f32);
+    // Create animation interpolator (default walk cycle; this tool is for quick previews)
+    let walk_cycle = WalkCycle {
+        frame_count: 4,
+        frame_duration_ms: 150.0,
+        first_frame: 0,
+        repeat_mode: RepeatMode::Repeat,
+    };
+    let interpolator = AnimationInterpolator::new(
+        coordinate_mapper,
+        interval_ms,
+        fps as f32,
+        walk_cycle,
+        InterpolationMode::Linear,
+        300.0, // warp_fade_ms; this tool is for quick previews, no CLI flag for it
+    );
 
     let total_frames = interpolator.calculate_frame_count(&sequences);
     let duration_sec = interpolator.calculate_duration(&sequences) / 1000.0;
@@ -113,21 +133,36 @@ async fn run() -> Result<()> {
 
     // Initialize GPU
     log::info!("Initializing GPU...");
-    let gpu = GpuContext::new(width, height).await?;
+    let gpu = GpuContext::new(width, height, 4).await?;
 
     // Load sprite sheet
     log::info!("Loading sprite sheet...");
-    let texture_atlas = TextureAtlas::load(&gpu.device, &gpu.queue, &args.sprite_sheet)?;
+    let texture_atlas = TextureAtlas::load(&gpu.device, &gpu.queue, std::slice::from_ref(&args.sprite_sheet))?;
 
     // Create renderer
     log::info!("Creating renderer...");
-    let renderer = SpriteRenderer::new(
+    let mut renderer = SpriteRenderer::new(
         &gpu.device,
         &gpu.queue,
-        &texture_atlas,
+        &[&texture_atlas],
         width,
         height,
         sequences.len() + 100, // Max sprites
+        gpu.sample_count,
+    )?;
+
+    // Create map background renderer (full opacity/zoom; this tool is for
+    // quick previews, no CLI flags for those)
+    let map_renderer = MapRenderer::new(
+        &gpu.device,
+        &gpu.queue,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        args.map_image.as_ref(),
+        1.0,
+        [1.0, 1.0, 1.0],
+        1.0,
+        [0.5, 0.5],
+        gpu.sample_count,
     )?;
 
     // Create encoder
@@ -149,8 +184,10 @@ async fn run() -> Result<()> {
                 if let Some(sprite_data) = interpolator.interpolate_sprite(sequence, &state) {
                     // Get texture coordinates
                     let tex_coords = texture_atlas.get_sprite_tex_coords(
+                        0,
                         sprite_data.sprite_id,
                         sprite_data.direction,
+                        sprite_data.frame_index,
                     );
 
                     // Center sprite (16x16, so offset by -8)
@@ -160,24 +197,31 @@ async fn run() -> Result<()> {
                             sprite_data.position[1] - 8.0,
                         ],
                         tex_rect: tex_coords,
+                        layer: 0,
+                        alpha: sprite_data.alpha,
+                        tint: [1.0, 1.0, 1.0, 1.0],
                     });
                 }
             }
         }
 
         // Render frame
+        let (color_view, resolve_view) = gpu.color_attachment_target();
+        map_renderer.render(&gpu.device, &gpu.queue, color_view, resolve_view)?;
         renderer.render(
             &gpu.device,
             &gpu.queue,
-            &gpu.render_texture_view,
+            color_view,
+            resolve_view,
             &sprite_instances,
+            false,
         )?;
 
-        // Read pixels
-        let pixels = gpu.read_pixels().await?;
+        // Read pixels, already split into RGB/alpha planes by the GPU writeback pass
+        let (rgb, mask) = gpu.read_pixels_split().await?;
 
         // Write to encoder
-        encoder.write_frame(&pixels)?;
+        encoder.write_frame_split(&rgb, &mask)?;
 
         // Progress logging
         if frame_number % 30 == 0 || frame_number == total_frames - 1 {
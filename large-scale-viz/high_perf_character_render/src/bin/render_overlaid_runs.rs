@@ -1,10 +1,15 @@
 use anyhow::Result;
 use chrono::Duration;
-use clap::Parser;
-use sprite_video_renderer::data::{CoordinateMapper, ParquetFilter, ParquetReader};
-use sprite_video_renderer::rendering::{GpuContext, SpriteInstance, SpriteRenderer, TextureAtlas};
-use sprite_video_renderer::video::ProResEncoder;
-use std::path::PathBuf;
+use clap::{Parser, ValueEnum};
+use rand::Rng;
+use sprite_video_renderer::data::{CoordinateMapper, ParquetFilter, ParquetReader, RepeatMode, WalkCycle};
+use sprite_video_renderer::rendering::{
+    GpuContext, MapRenderer, SpriteInstance, SpriteRenderer, SpriteSheetManifest, TextureAtlas,
+};
+use sprite_video_renderer::video::{mux_soundtrack, BlockCodecEncoder, CueEvent, ProResEncoder, SoundtrackManifest};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Render all runs from parquet overlaid", long_about = None)]
@@ -17,6 +22,13 @@ struct Args {
     #[arg(long, default_value = "../../assets/characters_transparent.png")]
     sprite_sheet: PathBuf,
 
+    /// Optional JSON manifest pairing sprite_id ranges with distinct sprite
+    /// sheets (see SpriteSheetManifest), so different agents/models in the
+    /// parquet can render with visually distinct sprites in the same
+    /// overlay. Overrides `--sprite-sheet` when set.
+    #[arg(long)]
+    sprite_sheet_manifest: Option<PathBuf>,
+
     /// Path to map_data.json
     #[arg(long, default_value = "../../assets/map_data.json")]
     map_data: PathBuf,
@@ -48,6 +60,148 @@ struct Args {
     /// Maximum number of frames to render (for testing)
     #[arg(long)]
     max_frames: Option<usize>,
+
+    /// Path to a compiled run-detection cache. Reused on the next invocation
+    /// instead of re-reading and re-splitting the parquet file, as long as
+    /// `--min-duration-secs` and the reset-map list it was built with still
+    /// match; written here (creating it) whenever it's missing or stale
+    #[arg(long)]
+    run_cache: Option<PathBuf>,
+
+    /// Optional path to also write frames through a 4x4 temporal block-skip
+    /// codec (see BlockCodecEncoder) - a side-channel encoding that exploits
+    /// how little changes frame-to-frame at 8192x8192, once most runs have
+    /// finished and only a handful of sprites are still moving
+    #[arg(long)]
+    block_codec_output: Option<PathBuf>,
+
+    /// Quality for --block-codec-output: 0 skips/fills blocks as
+    /// aggressively as the thresholds allow, 100 (default) only skips
+    /// byte-identical blocks and only fills already-solid-color blocks
+    #[arg(long, default_value = "100")]
+    quality: u8,
+
+    /// For --block-codec-output: force every block to be re-sent (never
+    /// `CMD_COPY_PREVIOUS`) every N frames, so a decoder can seek or recover
+    /// from a dropped frame. 0 disables forced keyframes.
+    #[arg(long, default_value = "300")]
+    keyframe_interval: u64,
+
+    /// Number of frames in the walk-cycle animation
+    #[arg(long, default_value = "4")]
+    walk_frame_count: u32,
+
+    /// Duration of each walk-cycle frame in milliseconds
+    #[arg(long, default_value = "150")]
+    walk_frame_duration_ms: f32,
+
+    /// First frame index of the walk-cycle within the sprite sheet column
+    #[arg(long, default_value = "0")]
+    walk_first_frame: u32,
+
+    /// How the walk-cycle frame repeats once it reaches the end
+    #[arg(long, value_enum, default_value = "repeat")]
+    walk_repeat_mode: WalkRepeatModeArg,
+
+    /// Optional map background image, stretched across the full canvas behind
+    /// sprites (the canvas already represents the whole world in the same
+    /// coordinate space CoordinateMapper maps sprite positions into)
+    #[arg(long)]
+    map_image: Option<PathBuf>,
+
+    /// Opacity of the map background layer, 0.0 (invisible) to 1.0 (opaque)
+    #[arg(long, default_value = "1.0")]
+    map_opacity: f32,
+
+    /// Tint multiplier applied to the map background's red channel, e.g.
+    /// 0.5 to dim it so sprite trails read clearly on top
+    #[arg(long, default_value = "1.0")]
+    map_tint_r: f32,
+
+    /// Tint multiplier applied to the map background's green channel
+    #[arg(long, default_value = "1.0")]
+    map_tint_g: f32,
+
+    /// Tint multiplier applied to the map background's blue channel
+    #[arg(long, default_value = "1.0")]
+    map_tint_b: f32,
+
+    /// Zoom factor for the map background: 1.0 (default) frames the whole
+    /// map, >1.0 crops to a 1/zoom-sized sub-region centered on
+    /// `--map-center-x`/`--map-center-y`
+    #[arg(long, default_value = "1.0")]
+    map_zoom: f32,
+
+    /// Center-x of the zoomed map sub-region, in 0..1 texture-space; only
+    /// relevant when `--map-zoom` > 1.0
+    #[arg(long, default_value = "0.5")]
+    map_center_x: f32,
+
+    /// Center-y of the zoomed map sub-region, in 0..1 texture-space; only
+    /// relevant when `--map-zoom` > 1.0
+    #[arg(long, default_value = "0.5")]
+    map_center_y: f32,
+
+    /// Optional JSON manifest naming a looping background soundtrack plus a
+    /// cue table of short stings (see SoundtrackManifest). When set, the
+    /// final muxed video is written to `--output`; without it `--output`
+    /// still gets a silent copy of the rendered video.
+    #[arg(long)]
+    soundtrack_manifest: Option<PathBuf>,
+
+    /// Name of the `--soundtrack-manifest` entry to loop as the background
+    /// track
+    #[arg(long, default_value = "default")]
+    soundtrack: String,
+
+    /// MSAA sample count for the offscreen render target, e.g. 4. Silently
+    /// lowered to the highest count the adapter supports; 1 disables MSAA.
+    #[arg(long, default_value = "4")]
+    sample_count: u32,
+
+    /// Render on the CPU instead of a GPU (software rasterizer), for headless
+    /// boxes with no GPU adapter. Produces identical pixels, just slower.
+    #[arg(long, default_value_t = false)]
+    force_cpu: bool,
+}
+
+/// Distinct colors cycled across overlaid runs (see `run_tint`) so runs from
+/// different sessions/reset cycles stay visually distinguishable instead of
+/// blending into an undifferentiated sprite soup. Cycling by run index is
+/// enough here since run order already decorrelates user/env_id (frames are
+/// grouped by user+env_id before being split into runs above).
+const RUN_TINT_PALETTE: &[[f32; 4]] = &[
+    [1.0, 0.45, 0.45, 1.0],
+    [0.45, 1.0, 0.55, 1.0],
+    [0.45, 0.65, 1.0, 1.0],
+    [1.0, 0.85, 0.35, 1.0],
+    [0.85, 0.45, 1.0, 1.0],
+    [0.35, 1.0, 1.0, 1.0],
+    [1.0, 0.55, 0.85, 1.0],
+    [0.75, 0.85, 0.35, 1.0],
+];
+
+fn run_tint(run_idx: usize) -> [f32; 4] {
+    RUN_TINT_PALETTE[run_idx % RUN_TINT_PALETTE.len()]
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum WalkRepeatModeArg {
+    Once,
+    Repeat,
+    PingPong,
+    Stop,
+}
+
+impl From<WalkRepeatModeArg> for RepeatMode {
+    fn from(mode: WalkRepeatModeArg) -> Self {
+        match mode {
+            WalkRepeatModeArg::Once => RepeatMode::Once,
+            WalkRepeatModeArg::Repeat => RepeatMode::Repeat,
+            WalkRepeatModeArg::PingPong => RepeatMode::PingPong,
+            WalkRepeatModeArg::Stop => RepeatMode::Stop,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -62,6 +216,105 @@ struct RunData {
     duration_ms: f32,
 }
 
+/// Magic bytes identifying a `--run-cache` blob ("OVRLRUN1" as little-endian).
+const RUN_CACHE_MAGIC: u64 = 0x314E_5552_4C52_564F;
+const RUN_CACHE_VERSION: u32 = 1;
+
+/// Load a `--run-cache` blob if it exists and its header still matches the
+/// detection parameters passed on this invocation; returns `None` (not an
+/// error) on any mismatch so the caller falls back to re-detecting runs from
+/// the parquet file and recompiling the cache.
+fn load_run_cache(path: &Path, min_duration_secs: i64, reset_maps: &[u8]) -> Result<Option<Vec<RunData>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut u64_buf = [0u8; 8];
+    reader.read_exact(&mut u64_buf)?;
+    if u64::from_le_bytes(u64_buf) != RUN_CACHE_MAGIC {
+        log::warn!("Run cache {:?} has no valid magic, ignoring", path);
+        return Ok(None);
+    }
+
+    let mut u32_buf = [0u8; 4];
+    reader.read_exact(&mut u32_buf)?;
+    if u32::from_le_bytes(u32_buf) != RUN_CACHE_VERSION {
+        log::warn!("Run cache {:?} is a different version, ignoring", path);
+        return Ok(None);
+    }
+
+    let mut i64_buf = [0u8; 8];
+    reader.read_exact(&mut i64_buf)?;
+    if i64::from_le_bytes(i64_buf) != min_duration_secs {
+        log::info!("Run cache {:?} was built with a different --min-duration-secs, recompiling", path);
+        return Ok(None);
+    }
+
+    reader.read_exact(&mut u32_buf)?;
+    let cached_reset_map_count = u32::from_le_bytes(u32_buf) as usize;
+    let mut cached_reset_maps = vec![0u8; cached_reset_map_count];
+    reader.read_exact(&mut cached_reset_maps)?;
+    if cached_reset_maps != reset_maps {
+        log::info!("Run cache {:?} was built with a different reset-map list, recompiling", path);
+        return Ok(None);
+    }
+
+    reader.read_exact(&mut u32_buf)?;
+    let run_count = u32::from_le_bytes(u32_buf) as usize;
+
+    let mut runs = Vec::with_capacity(run_count);
+    let mut byte_buf = [0u8; 1];
+    let mut f32_buf = [0u8; 4];
+    for _ in 0..run_count {
+        reader.read_exact(&mut byte_buf)?;
+        let sprite_id = byte_buf[0];
+
+        reader.read_exact(&mut f32_buf)?;
+        let duration_ms = f32::from_le_bytes(f32_buf);
+
+        reader.read_exact(&mut u32_buf)?;
+        let frame_count = u32::from_le_bytes(u32_buf) as usize;
+
+        let mut coord_bytes = vec![0u8; frame_count * 3];
+        reader.read_exact(&mut coord_bytes)?;
+        let frames = coord_bytes
+            .chunks_exact(3)
+            .map(|c| CompactFrame { coords: [c[0], c[1], c[2]] })
+            .collect();
+
+        runs.push(RunData { sprite_id, frames, duration_ms });
+    }
+
+    Ok(Some(runs))
+}
+
+/// Serialize detected runs into a `--run-cache` blob so the next invocation
+/// can skip parquet parsing and run-detection entirely.
+fn write_run_cache(path: &Path, min_duration_secs: i64, reset_maps: &[u8], runs: &[RunData]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writer.write_all(&RUN_CACHE_MAGIC.to_le_bytes())?;
+    writer.write_all(&RUN_CACHE_VERSION.to_le_bytes())?;
+    writer.write_all(&min_duration_secs.to_le_bytes())?;
+    writer.write_all(&(reset_maps.len() as u32).to_le_bytes())?;
+    writer.write_all(reset_maps)?;
+    writer.write_all(&(runs.len() as u32).to_le_bytes())?;
+
+    for run in runs {
+        writer.write_all(&[run.sprite_id])?;
+        writer.write_all(&run.duration_ms.to_le_bytes())?;
+        writer.write_all(&(run.frames.len() as u32).to_le_bytes())?;
+        for frame in &run.frames {
+            writer.write_all(&frame.coords)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
 fn main() -> Result<()> {
     pollster::block_on(run())
 }
@@ -80,110 +333,143 @@ async fn run() -> Result<()> {
     log::info!("Loading map data...");
     let coordinate_mapper = CoordinateMapper::load(&args.map_data)?;
 
-    // Read parquet file
-    log::info!("Reading parquet file...");
-    let reader = ParquetReader::new(ParquetFilter::default());
-    let mut frames = reader.read_file(&args.parquet_file)?;
-
-    log::info!("Total frames read: {}", frames.len());
-
-    // Sort all frames by user+env_id for grouping
-    log::info!("Sorting frames...");
-    frames.sort_by(|a, b| {
-        (&a.user, &a.env_id, a.timestamp, a.path_index)
-            .cmp(&(&b.user, &b.env_id, b.timestamp, b.path_index))
-    });
-
-    // Detect runs by scanning through sorted frames and extract compact data
-    log::info!("Detecting runs with reset detection...");
-    let mut runs = Vec::new();
-    let gap_threshold = Duration::minutes(2);
-    let min_duration = Duration::seconds(args.min_duration_secs);
     let reset_maps = vec![0u8, 37, 40];
 
-    let mut i = 0;
-    while i < frames.len() {
-        let run_user = &frames[i].user;
-        let run_env_id = &frames[i].env_id;
-        let run_sprite_id = frames[i].sprite_id;
+    let cached_runs = match &args.run_cache {
+        Some(path) => load_run_cache(path, args.min_duration_secs, &reset_maps)?,
+        None => None,
+    };
+
+    // Reset-map transition keys (same "[from]-[to]" format as
+    // warp_validator::transition_key) seen while splitting runs, deduped so
+    // an overlay of hundreds of runs doesn't layer the same sting dozens of
+    // times. Only available on a fresh parquet parse - runs loaded from
+    // `--run-cache` skip frame-level reset detection, so no cues fire then.
+    let mut reset_cue_keys: Vec<String> = Vec::new();
+
+    let runs = match cached_runs {
+        Some(runs) => {
+            log::info!("Loaded {} runs from run cache {:?}, skipping parquet parsing", runs.len(), args.run_cache.as_ref().unwrap());
+            runs
+        }
+        None => {
+            // Read parquet file
+            log::info!("Reading parquet file...");
+            let reader = ParquetReader::new(ParquetFilter::default());
+            let mut frames = reader.read_file(&args.parquet_file)?;
+
+            log::info!("Total frames read: {}", frames.len());
+
+            // Sort all frames by user+env_id for grouping
+            log::info!("Sorting frames...");
+            frames.sort_by(|a, b| {
+                (&a.user, &a.env_id, a.timestamp, a.path_index)
+                    .cmp(&(&b.user, &b.env_id, b.timestamp, b.path_index))
+            });
 
-        let mut run_start_idx = i;
+            // Detect runs by scanning through sorted frames and extract compact data
+            log::info!("Detecting runs with reset detection...");
+            let mut runs = Vec::new();
+            let gap_threshold = Duration::minutes(2);
+            let min_duration = Duration::seconds(args.min_duration_secs);
 
-        // Find all frames for this user+env_id
-        while i < frames.len() && &frames[i].user == run_user && &frames[i].env_id == run_env_id {
-            i += 1;
-        }
+            let mut i = 0;
+            while i < frames.len() {
+                let run_user = &frames[i].user;
+                let run_env_id = &frames[i].env_id;
+                let run_sprite_id = frames[i].sprite_id;
 
-        let user_env_end_idx = i;
+                let mut run_start_idx = i;
 
-        // Now split this user+env_id into runs
-        let mut run_current_idx = run_start_idx;
+                // Find all frames for this user+env_id
+                while i < frames.len() && &frames[i].user == run_user && &frames[i].env_id == run_env_id {
+                    i += 1;
+                }
 
-        for j in (run_start_idx + 1)..user_env_end_idx {
-            let time_gap = frames[j].timestamp - frames[j-1].timestamp;
-            let curr_map = frames[j].coords[2];
-            let prev_map = frames[j-1].coords[2];
+                let user_env_end_idx = i;
 
-            let mut should_split = false;
+                // Now split this user+env_id into runs
+                let mut run_current_idx = run_start_idx;
 
-            // Split on 2-minute gaps
-            if time_gap >= gap_threshold {
-                should_split = true;
-            }
+                for j in (run_start_idx + 1)..user_env_end_idx {
+                    let time_gap = frames[j].timestamp - frames[j-1].timestamp;
+                    let curr_map = frames[j].coords[2];
+                    let prev_map = frames[j-1].coords[2];
 
-            // Split when jumping TO a reset map
-            if reset_maps.contains(&curr_map) && !reset_maps.contains(&prev_map) {
-                should_split = true;
-            }
+                    let mut should_split = false;
 
-            if should_split {
-                let duration = frames[j-1].timestamp - frames[run_current_idx].timestamp;
+                    // Split on 2-minute gaps
+                    if time_gap >= gap_threshold {
+                        should_split = true;
+                    }
 
-                // Filter by minimum duration
-                if duration >= min_duration {
-                    let duration_ms = duration.num_milliseconds() as f32;
+                    // Split when jumping TO a reset map
+                    if reset_maps.contains(&curr_map) && !reset_maps.contains(&prev_map) {
+                        should_split = true;
 
-                    // Extract only the coords we need (discard all strings)
-                    let compact_frames: Vec<CompactFrame> = frames[run_current_idx..j]
-                        .iter()
-                        .map(|f| CompactFrame { coords: f.coords })
-                        .collect();
+                        let cue_key = format!("[{}]-[{}]", prev_map, curr_map);
+                        if !reset_cue_keys.contains(&cue_key) {
+                            reset_cue_keys.push(cue_key);
+                        }
+                    }
 
-                    runs.push(RunData {
-                        sprite_id: run_sprite_id,
-                        frames: compact_frames,
-                        duration_ms,
-                    });
-                }
+                    if should_split {
+                        let duration = frames[j-1].timestamp - frames[run_current_idx].timestamp;
 
-                run_current_idx = j;
-            }
-        }
+                        // Filter by minimum duration
+                        if duration >= min_duration {
+                            let duration_ms = duration.num_milliseconds() as f32;
 
-        // Process final run for this user+env_id
-        if run_current_idx < user_env_end_idx {
-            let duration = frames[user_env_end_idx - 1].timestamp - frames[run_current_idx].timestamp;
+                            // Extract only the coords we need (discard all strings)
+                            let compact_frames: Vec<CompactFrame> = frames[run_current_idx..j]
+                                .iter()
+                                .map(|f| CompactFrame { coords: f.coords })
+                                .collect();
 
-            if duration >= min_duration {
-                let duration_ms = duration.num_milliseconds() as f32;
+                            runs.push(RunData {
+                                sprite_id: run_sprite_id,
+                                frames: compact_frames,
+                                duration_ms,
+                            });
+                        }
 
-                // Extract only the coords we need
-                let compact_frames: Vec<CompactFrame> = frames[run_current_idx..user_env_end_idx]
-                    .iter()
-                    .map(|f| CompactFrame { coords: f.coords })
-                    .collect();
+                        run_current_idx = j;
+                    }
+                }
 
-                runs.push(RunData {
-                    sprite_id: run_sprite_id,
-                    frames: compact_frames,
-                    duration_ms,
-                });
+                // Process final run for this user+env_id
+                if run_current_idx < user_env_end_idx {
+                    let duration = frames[user_env_end_idx - 1].timestamp - frames[run_current_idx].timestamp;
+
+                    if duration >= min_duration {
+                        let duration_ms = duration.num_milliseconds() as f32;
+
+                        // Extract only the coords we need
+                        let compact_frames: Vec<CompactFrame> = frames[run_current_idx..user_env_end_idx]
+                            .iter()
+                            .map(|f| CompactFrame { coords: f.coords })
+                            .collect();
+
+                        runs.push(RunData {
+                            sprite_id: run_sprite_id,
+                            frames: compact_frames,
+                            duration_ms,
+                        });
+                    }
+                }
             }
-        }
-    }
 
-    // Drop the huge frames vector immediately
-    drop(frames);
+            // Drop the huge frames vector immediately
+            drop(frames);
+
+            if let Some(path) = &args.run_cache {
+                log::info!("Writing {} runs to run cache {:?}", runs.len(), path);
+                write_run_cache(path, args.min_duration_secs, &reset_maps, &runs)?;
+            }
+
+            runs
+        }
+    };
 
     log::info!("Total runs after filtering: {}", runs.len());
 
@@ -228,27 +514,75 @@ async fn run() -> Result<()> {
 
     // Initialize GPU
     log::info!("Initializing GPU...");
-    let gpu = GpuContext::new(args.width, args.height).await?;
-
-    // Load sprite sheet
+    let gpu = if args.force_cpu {
+        GpuContext::new_cpu(args.width, args.height, args.sample_count).await?
+    } else {
+        GpuContext::new(args.width, args.height, args.sample_count).await?
+    };
+
+    // Load sprite sheet(s). A manifest uploads one array layer per sheet and
+    // routes each run to its layer by sprite_id; otherwise everything draws
+    // from a single sheet on layer 0.
     log::info!("Loading sprite sheet...");
-    let texture_atlas = TextureAtlas::load(&gpu.device, &gpu.queue, &args.sprite_sheet)?;
+    let sheet_manifest = args
+        .sprite_sheet_manifest
+        .as_ref()
+        .map(SpriteSheetManifest::load)
+        .transpose()?;
+    let texture_atlas = match &sheet_manifest {
+        Some(manifest) => TextureAtlas::load(&gpu.device, &gpu.queue, &manifest.paths())?,
+        None => TextureAtlas::load(&gpu.device, &gpu.queue, std::slice::from_ref(&args.sprite_sheet))?,
+    };
 
     // Create renderer
     log::info!("Creating renderer...");
-    let renderer = SpriteRenderer::new(
+    let mut renderer = SpriteRenderer::new(
         &gpu.device,
         &gpu.queue,
-        &texture_atlas,
+        &[&texture_atlas],
         args.width,
         args.height,
         runs.len() + 1000, // Max sprites with buffer
+        gpu.sample_count,
+    )?;
+
+    // Create map background renderer
+    log::info!("Creating map background renderer...");
+    let map_renderer = MapRenderer::new(
+        &gpu.device,
+        &gpu.queue,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        args.map_image.as_ref(),
+        args.map_opacity,
+        [args.map_tint_r, args.map_tint_g, args.map_tint_b],
+        args.map_zoom,
+        [args.map_center_x, args.map_center_y],
+        gpu.sample_count,
     )?;
 
     // Create encoder
     log::info!("Starting video encoder...");
     let mut encoder = ProResEncoder::new(&args.output, args.width, args.height, args.fps)?;
 
+    let mut block_codec_encoder = match &args.block_codec_output {
+        Some(path) => {
+            log::info!("Also writing block-skip codec output to {:?} (quality {})", path, args.quality);
+            Some(BlockCodecEncoder::new(path, args.width, args.height, args.quality, args.keyframe_interval)?)
+        }
+        None => None,
+    };
+
+    let walk_cycle = WalkCycle {
+        frame_count: args.walk_frame_count,
+        frame_duration_ms: args.walk_frame_duration_ms,
+        first_frame: args.walk_first_frame,
+        repeat_mode: args.walk_repeat_mode.into(),
+    };
+
+    // Random per-run offset so hundreds of overlaid runs don't step in lockstep
+    let mut rng = rand::thread_rng();
+    let run_offsets: Vec<f32> = (0..runs.len()).map(|_| rng.gen::<f32>()).collect();
+
     // Render frames
     log::info!("Rendering {} frames...", total_frames);
     let start_time = std::time::Instant::now();
@@ -259,7 +593,7 @@ async fn run() -> Result<()> {
         // Calculate sprite instances for this frame
         let mut sprite_instances = Vec::new();
 
-        for run in &runs {
+        for (run_idx, run) in runs.iter().enumerate() {
             // Calculate which frame within this run we should be at
             let run_frame_index = (time_ms / args.interval_ms as f32) as usize;
 
@@ -302,12 +636,33 @@ async fn run() -> Result<()> {
                 else { sprite_video_renderer::data::Direction::Up }
             };
 
+            // Walk-cycle frame only advances while the sprite is actually
+            // moving; freeze on the idle (first) frame when stationary,
+            // phased per-run by run_offsets so the crowd doesn't step in
+            // lockstep
+            let age_ms = (time_ms / args.interval_ms as f32 + run_offsets[run_idx]) * args.interval_ms as f32;
+            let walk_frame = if pixel_distance > 0.0 {
+                walk_cycle.frame_index_at(age_ms)
+            } else {
+                walk_cycle.first_frame
+            };
+
+            // Pick the array layer this sprite draws from: the manifest's
+            // matching sheet range, or layer 0 when rendering a single sheet
+            let layer = sheet_manifest
+                .as_ref()
+                .map(|manifest| manifest.layer_for_sprite_id(run.sprite_id))
+                .unwrap_or(0);
+
             // Get texture coordinates
-            let tex_coords = texture_atlas.get_sprite_tex_coords(run.sprite_id, direction);
+            let tex_coords = texture_atlas.get_sprite_tex_coords(layer, run.sprite_id, direction, walk_frame);
 
             sprite_instances.push(SpriteInstance {
                 position,
                 tex_rect: tex_coords,
+                layer,
+                alpha: 1.0,
+                tint: run_tint(run_idx),
             });
         }
 
@@ -330,18 +685,29 @@ async fn run() -> Result<()> {
         }
 
         // Render frame
+        // Draw the map background first, then sprites on top without re-clearing
+        let (color_view, resolve_view) = gpu.color_attachment_target();
+        map_renderer.render(&gpu.device, &gpu.queue, color_view, resolve_view)?;
         renderer.render(
             &gpu.device,
             &gpu.queue,
-            &gpu.render_texture_view,
+            color_view,
+            resolve_view,
             &sprite_instances,
+            false,
         )?;
 
-        // Read pixels
-        let pixels = gpu.read_pixels().await?;
+        // Read pixels, already split into RGB/alpha planes by the GPU writeback pass
+        let (rgb, mask) = gpu.read_pixels_split().await?;
 
         // Write to encoder
-        encoder.write_frame(&pixels)?;
+        encoder.write_frame_split(&rgb, &mask)?;
+
+        if let Some(block_encoder) = &mut block_codec_encoder {
+            // Block codec diffs whole RGBA pixels, so it still needs its own readback
+            let pixels = gpu.read_pixels().await?;
+            block_encoder.write_frame(&pixels)?;
+        }
 
         // Progress logging
         if frame_number % 30 == 0 || frame_number == total_frames - 1 {
@@ -373,6 +739,34 @@ async fn run() -> Result<()> {
     log::info!("Finalizing video...");
     encoder.finish()?;
 
+    if let Some(block_encoder) = block_codec_encoder {
+        block_encoder.finish()?;
+    }
+
+    // Mux the soundtrack manifest's background track and reset-map cues onto
+    // the silent RGB stream ProResEncoder wrote, producing the single
+    // ready-to-post file at `--output` (the mask stream stays video-only).
+    let soundtrack_manifest = args
+        .soundtrack_manifest
+        .as_ref()
+        .map(SoundtrackManifest::load)
+        .transpose()?;
+    let cues: Vec<CueEvent> = reset_cue_keys
+        .into_iter()
+        .map(|cue_key| CueEvent { time_sec: 0.0, cue_key })
+        .collect();
+    let rgb_stem = args.output.file_stem().unwrap().to_str().unwrap();
+    let mut rgb_path = args.output.clone();
+    rgb_path.set_file_name(format!("{}_rgb.mp4", rgb_stem));
+    mux_soundtrack(
+        &rgb_path,
+        &args.output,
+        soundtrack_manifest.as_ref(),
+        &args.soundtrack,
+        &cues,
+        total_frames as f32 / args.fps as f32,
+    )?;
+
     log::info!("✓ Done! Created {:?}", args.output);
     log::info!("Video: {}x{} @ {} fps, {:.2} seconds",
                args.width, args.height, args.fps, max_duration_ms / 1000.0);
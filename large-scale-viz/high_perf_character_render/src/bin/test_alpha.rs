@@ -1,5 +1,7 @@
 use anyhow::Result;
-use sprite_video_renderer::rendering::{GpuContext, SpriteInstance, SpriteRenderer, TextureAtlas};
+use sprite_video_renderer::rendering::{
+    GpuContext, SpriteAnimator, SpriteInstance, SpriteRenderer, TextureAtlas,
+};
 use sprite_video_renderer::video::ProResEncoder;
 
 fn main() -> Result<()> {
@@ -16,26 +18,34 @@ async fn run() -> Result<()> {
     log::info!("Testing alpha channel rendering...");
 
     // Initialize GPU
-    let gpu = GpuContext::new(width, height).await?;
+    let gpu = GpuContext::new(width, height, 1).await?;
     let texture_atlas = TextureAtlas::load(
         &gpu.device,
         &gpu.queue,
-        "../../assets/characters_transparent.png",
+        &["../../assets/characters_transparent.png"],
     )?;
-    let renderer = SpriteRenderer::new(&gpu.device, &gpu.queue, &texture_atlas, width, height, 10)?;
+    let mut renderer = SpriteRenderer::new(&gpu.device, &gpu.queue, &[&texture_atlas], width, height, 10, 1)?;
     let mut encoder = ProResEncoder::new("test_alpha.mov", width, height, fps)?;
 
-    // Render 30 frames - one sprite
+    // Render 30 frames - one sprite walking a small loop so the walk-cycle
+    // automaton actually has movement to react to, instead of sitting frozen
+    // on Direction::Down/frame 0 like a statue.
     // Sprite is 16x16, so to center it we need to offset by -8,-8 from center
-    let sprite_pos = [
+    let mut sprite_pos = [
         width as f32 / 2.0 - 8.0,   // Center X minus half sprite width
         height as f32 / 2.0 - 8.0,   // Center Y minus half sprite height
     ];
+    let mut animator = SpriteAnimator::new(4, 8.0);
+    let step = [2.0, 1.0];
 
     log::info!("Sprite position: {:?}, canvas: {}x{}", sprite_pos, width, height);
 
     for _frame_num in 0..30 {
-        let tex_coords = texture_atlas.get_sprite_tex_coords(0, sprite_video_renderer::data::Direction::Down);
+        sprite_pos[0] += step[0];
+        sprite_pos[1] += step[1];
+        animator.step(step[0], step[1]);
+
+        let tex_coords = animator.tex_coords(&texture_atlas, 0, 0);
         if _frame_num == 0 {
             log::info!("Texture coords: {:?}", tex_coords);
         }
@@ -43,9 +53,12 @@ async fn run() -> Result<()> {
         let sprites = vec![SpriteInstance {
             position: sprite_pos,
             tex_rect: tex_coords,
+            layer: 0,
+            alpha: 1.0,
+            tint: [1.0, 1.0, 1.0, 1.0],
         }];
 
-        renderer.render(&gpu.device, &gpu.queue, &gpu.render_texture_view, &sprites)?;
+        renderer.render(&gpu.device, &gpu.queue, &gpu.render_texture_view, None, &sprites, true)?;
         let pixels = gpu.read_pixels().await?;
 
         // Check alpha values in a corner (should be 0 - transparent)
@@ -1,5 +1,7 @@
 use anyhow::Result;
-use sprite_video_renderer::rendering::{GpuContext, SpriteInstance, SpriteRenderer, TextureAtlas};
+use sprite_video_renderer::rendering::{
+    default_shader_config, GpuContext, SpriteInstance, SpriteRenderer, TextureAtlas,
+};
 use sprite_video_renderer::video::ProResEncoder;
 
 fn main() -> Result<()> {
@@ -21,25 +23,30 @@ async fn run() -> Result<()> {
 
     // Initialize GPU
     log::info!("Initializing GPU context...");
-    let gpu = GpuContext::new(width, height).await?;
+    let gpu = GpuContext::new(width, height, 1).await?;
 
-    // Load sprite sheet
+    // Load sprite sheet with a full mip chain - this smoke test is also the
+    // one place that exercises `load_with_mipmaps`/`mip_filtering: true` end
+    // to end (see `TextureAtlas::from_images_with_mipmaps`).
     log::info!("Loading sprite sheet...");
-    let texture_atlas = TextureAtlas::load(
+    let texture_atlas = TextureAtlas::load_with_mipmaps(
         &gpu.device,
         &gpu.queue,
-        "../../assets/characters_transparent.png",
+        &["../../assets/characters_transparent.png"],
     )?;
 
     // Create renderer
     log::info!("Creating renderer...");
-    let renderer = SpriteRenderer::new(
+    let mut renderer = SpriteRenderer::with_shader_config(
         &gpu.device,
         &gpu.queue,
-        &texture_atlas,
+        &[&texture_atlas],
         width,
         height,
         100,
+        1,
+        &default_shader_config(),
+        true,
     )?;
 
     // Create encoder
@@ -65,22 +72,25 @@ async fn run() -> Result<()> {
             // Get texture coords for sprite
             let sprite_id = i as u8;
             let direction = sprite_video_renderer::data::Direction::Down;
-            let tex_coords = texture_atlas.get_sprite_tex_coords(sprite_id, direction);
+            let tex_coords = texture_atlas.get_sprite_tex_coords(0, sprite_id, direction, 0);
 
             sprites.push(SpriteInstance {
                 position: [x, y],
                 tex_rect: tex_coords,
+                layer: 0,
+                alpha: 1.0,
+                tint: [1.0, 1.0, 1.0, 1.0],
             });
         }
 
         // Render frame
-        renderer.render(&gpu.device, &gpu.queue, &gpu.render_texture_view, &sprites)?;
+        renderer.render(&gpu.device, &gpu.queue, &gpu.render_texture_view, None, &sprites, true)?;
 
-        // Read pixels
-        let pixels = gpu.read_pixels().await?;
+        // Read pixels, already split into RGB/alpha planes by the GPU writeback pass
+        let (rgb, mask) = gpu.read_pixels_split().await?;
 
         // Write to encoder
-        encoder.write_frame(&pixels)?;
+        encoder.write_frame_split(&rgb, &mask)?;
 
         log::info!("Frame {}/{}", frame_num + 1, total_frames);
     }
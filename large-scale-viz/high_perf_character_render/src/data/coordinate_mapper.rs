@@ -76,6 +76,20 @@ impl CoordinateMapper {
             INVALID_MAP_ID_FLAG // invalid for example 255 is seen sometimes
         }
     }
+
+    /// Every map id known to `map_data.json`, sorted, narrowed to `u8` (ids
+    /// that don't fit are dropped - run bundles only ever store `u8` map
+    /// ids anyway). Used to populate a run bundle's map-id table so a reader
+    /// can validate/pre-load map assets up front.
+    pub fn map_ids(&self) -> Vec<u8> {
+        let mut ids: Vec<u8> = self
+            .regions
+            .keys()
+            .filter_map(|&id| u8::try_from(id).ok())
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,143 @@
+use crate::data::parquet_reader::ParquetFilter;
+use crate::data::sprite_data::SpriteFrame;
+use anyhow::Result;
+use chrono::Utc;
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::io::Read;
+
+/// A single newline-delimited JSON coordinate row, as emitted by a live rollout feed
+#[derive(Deserialize)]
+struct JsonlRow<'a> {
+    #[serde(borrow)]
+    metadata: JsonlMetadata<'a>,
+    coords: Vec<[i64; 3]>,
+}
+
+#[derive(Deserialize)]
+struct JsonlMetadata<'a> {
+    #[serde(borrow)]
+    user: Cow<'a, str>,
+    #[serde(borrow)]
+    color: Cow<'a, str>,
+    #[serde(borrow)]
+    extra: Cow<'a, str>,
+}
+
+/// Reads the same `{metadata:{user,color,extra}, coords:[[x,y,z],...]}` rows as the
+/// newline-delimited stdin counter tool, but yields `SpriteFrame`s compatible with
+/// `ParquetReader::group_into_sequences` so a live coordinate feed can be piped
+/// straight into the renderer without a parquet conversion step in between.
+pub struct JsonlReader {
+    filter: ParquetFilter,
+}
+
+impl JsonlReader {
+    pub fn new(filter: ParquetFilter) -> Self {
+        Self { filter }
+    }
+
+    /// Read and parse every row from `reader` (e.g. stdin), chunking reads and
+    /// carrying a partial trailing line between chunks so rows are never split
+    /// across a read boundary
+    pub fn read<R: Read>(&self, mut reader: R) -> Result<Vec<SpriteFrame>> {
+        let cap = 1024 * 1024;
+        let mut buf = vec![0u8; cap];
+        let mut left_over = Vec::with_capacity(4096);
+        let mut frames = Vec::new();
+
+        loop {
+            let start_fill = left_over.len();
+            if buf.len() < start_fill + cap {
+                buf.resize(start_fill + cap, 0);
+            }
+            buf[0..start_fill].copy_from_slice(&left_over);
+
+            let n = reader.read(&mut buf[start_fill..])?;
+            if n == 0 {
+                if !left_over.is_empty() {
+                    frames.extend(self.process_chunk(&left_over));
+                }
+                break;
+            }
+
+            let valid_data = &buf[0..start_fill + n];
+
+            let split_idx = match memchr::memrchr(b'\n', valid_data) {
+                Some(idx) => idx + 1,
+                None => {
+                    left_over.clear();
+                    left_over.extend_from_slice(valid_data);
+                    continue;
+                }
+            };
+
+            let (chunk, rest) = valid_data.split_at(split_idx);
+            frames.extend(self.process_chunk(chunk));
+
+            left_over.clear();
+            left_over.extend_from_slice(rest);
+        }
+
+        log::info!("Parsed {} frames from JSONL stream", frames.len());
+        Ok(frames)
+    }
+
+    fn process_chunk(&self, chunk: &[u8]) -> Vec<SpriteFrame> {
+        chunk
+            .par_split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .flat_map(|line| self.parse_row(line))
+            .collect()
+    }
+
+    /// Parse one line into zero or more `SpriteFrame`s (one per coordinate in
+    /// its path), applying `self.filter`. Malformed lines are skipped rather
+    /// than failing the whole stream, matching the tolerant-of-bad-rows
+    /// behavior of the existing JSONL counter tool.
+    fn parse_row(&self, line: &[u8]) -> Vec<SpriteFrame> {
+        let row = match serde_json::from_slice::<JsonlRow>(line) {
+            Ok(row) => row,
+            Err(_) => return Vec::new(),
+        };
+
+        if let Some(regex) = &self.filter.user_regex {
+            if !regex.is_match(&row.metadata.user) {
+                return Vec::new();
+            }
+        }
+
+        // Live rows have no event timestamp of their own, so each row is
+        // stamped with its arrival time; `timestamp_start`/`timestamp_end`
+        // still apply, letting a consumer e.g. ignore a stream's backlog.
+        let timestamp = Utc::now();
+        if let Some(start) = self.filter.timestamp_start {
+            if timestamp < start {
+                return Vec::new();
+            }
+        }
+        if let Some(end) = self.filter.timestamp_end {
+            if timestamp > end {
+                return Vec::new();
+            }
+        }
+
+        row.coords
+            .iter()
+            .enumerate()
+            .map(|(path_index, coords)| {
+                SpriteFrame {
+                    timestamp,
+                    user: row.metadata.user.to_string(),
+                    env_id: "live".to_string(),
+                    sprite_id: 0,
+                    color: row.metadata.color.to_string(),
+                    extra: row.metadata.extra.to_string(),
+                    coords: *coords,
+                    path_index,
+                }
+            })
+            .collect()
+    }
+}
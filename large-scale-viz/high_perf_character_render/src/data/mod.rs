@@ -1,8 +1,19 @@
 pub mod coordinate_mapper;
+pub mod jsonl_reader;
 pub mod parquet_reader;
+pub mod run_bundle;
 pub mod sprite_data;
 
 pub use coordinate_mapper::CoordinateMapper;
 pub use coordinate_mapper::INVALID_MAP_ID_FLAG;
+pub use jsonl_reader::JsonlReader;
 pub use parquet_reader::{ParquetFilter, ParquetReader};
-pub use sprite_data::{AnimationState, Direction, SpriteFrame, SpriteInstance, SpriteSequence};
+pub use run_bundle::{
+    decode_delta_coords, write_run, write_run_delta, BundleCoord, RunBundleHeader, RunBundleReader,
+    RunCoord, RunRecord, RunRecordHeader, RUN_BUNDLE_MAGIC, RUN_BUNDLE_VERSION,
+    RUN_ENCODING_DELTA_VARINT, RUN_ENCODING_FIXED,
+};
+pub use sprite_data::{
+    AnimationState, Direction, InterpolationMode, RepeatMode, SpriteFrame, SpriteInstance,
+    SpriteSequence, WalkCycle,
+};
@@ -7,12 +7,19 @@ use arrow::array::{
 use arrow::datatypes::{Int8Type, Int16Type, Int32Type};
 use chrono::{DateTime, TimeZone, Utc};
 use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ProjectionMask;
+use parquet::file::statistics::Statistics;
+use rayon::prelude::*;
 use regex::Regex;
 use std::collections::HashMap;
 use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+/// Columns `read_file` actually consumes - everything else (`color`, `extra`,
+/// and any future additions) is skipped at decode time via `ProjectionMask`.
+const PROJECTED_COLUMNS: &[&str] = &["timestamp", "user", "env_id", "sprite_id", "coords"];
+
 pub struct ParquetFilter {
     pub user_regex: Option<Regex>,
     pub timestamp_start: Option<DateTime<Utc>>,
@@ -31,6 +38,11 @@ impl Default for ParquetFilter {
 
 pub struct ParquetReader {
     filter: ParquetFilter,
+    /// Caps how many files `read_files` decodes concurrently. `None` (the
+    /// default) uses rayon's global pool, sized to the available cores -
+    /// set via `set_max_concurrency` on memory-constrained hosts where
+    /// decoding hundreds of files at once would blow the working set.
+    max_concurrency: Option<usize>,
 }
 
 /// Helper to extract string from column that can be plain StringArray or Dictionary<Int8|Int16|Int32, String>
@@ -91,209 +103,335 @@ fn get_dict_string(col: &dyn Array, row_idx: usize) -> Result<Option<String>> {
 
 impl ParquetReader {
     pub fn new(filter: ParquetFilter) -> Self {
-        Self { filter }
+        Self {
+            filter,
+            max_concurrency: None,
+        }
     }
 
-    /// Read sprite frames from a single parquet file
-    pub fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<SpriteFrame>> {
+    /// Limit how many files `read_files` will decode at once, instead of
+    /// letting rayon fan out across every available core.
+    pub fn set_max_concurrency(&mut self, max_concurrency: usize) {
+        self.max_concurrency = Some(max_concurrency);
+    }
+
+    /// Whether a row group's `timestamp` min/max statistics could contain
+    /// any row inside `[timestamp_start, timestamp_end]`. Returns `true`
+    /// (keep the group) whenever statistics are absent or aren't the
+    /// expected `Int64` (nanosecond-timestamp) variant, since pruning must
+    /// never discard a group it can't actually rule out.
+    fn row_group_overlaps_window(&self, statistics: Option<&Statistics>) -> bool {
+        let Some(statistics) = statistics else {
+            return true;
+        };
+        let Statistics::Int64(statistics) = statistics else {
+            return true;
+        };
+        let (Some(&min_nanos), Some(&max_nanos)) = (statistics.min_opt(), statistics.max_opt()) else {
+            return true;
+        };
+
+        let group_min = Utc.timestamp_nanos(min_nanos);
+        let group_max = Utc.timestamp_nanos(max_nanos);
+
+        if let Some(end) = self.filter.timestamp_end {
+            if group_min > end {
+                return false;
+            }
+        }
+        if let Some(start) = self.filter.timestamp_start {
+            if group_max < start {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Like `read_file`, but yields one `Vec<SpriteFrame>` per Arrow
+    /// `RecordBatch` as it's decoded instead of collecting the whole file
+    /// into memory first - lets a caller bound peak memory on large
+    /// trajectory dumps by processing (or discarding) each batch's frames
+    /// before the next is read off disk.
+    pub fn read_file_batched<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<impl Iterator<Item = Result<Vec<SpriteFrame>>> + '_> {
         let file = File::open(path.as_ref())
             .context(format!("Failed to open parquet file: {:?}", path.as_ref()))?;
 
-        let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+        let mut builder = ParquetRecordBatchReaderBuilder::try_new(file)
             .context("Failed to create parquet reader")?;
 
+        let schema_descr = builder.metadata().file_metadata().schema_descr_ptr();
+
+        // Predicate pushdown: skip whole row groups whose `timestamp`
+        // min/max statistics fall entirely outside the requested window.
+        // Groups with missing/absent statistics are conservatively kept
+        // (scanned in full) rather than skipped.
+        if self.filter.timestamp_start.is_some() || self.filter.timestamp_end.is_some() {
+            if let Some(timestamp_col_idx) = schema_descr
+                .columns()
+                .iter()
+                .position(|col| col.name() == "timestamp")
+            {
+                let keep: Vec<usize> = builder
+                    .metadata()
+                    .row_groups()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, row_group)| {
+                        self.row_group_overlaps_window(row_group.column(timestamp_col_idx).statistics())
+                    })
+                    .map(|(idx, _)| idx)
+                    .collect();
+
+                let skipped = builder.metadata().row_groups().len() - keep.len();
+                if skipped > 0 {
+                    log::info!(
+                        "Skipping {} of {} row group(s) in {:?} outside the timestamp filter window",
+                        skipped,
+                        builder.metadata().row_groups().len(),
+                        path.as_ref()
+                    );
+                }
+
+                builder = builder.with_row_groups(keep);
+            }
+        }
+
+        // Column projection: only decode the columns `read_file` actually
+        // reads. Columns this file's schema doesn't have (e.g. an older
+        // file missing `sprite_id`) are silently dropped from the request
+        // rather than erroring - the per-row logic below already treats
+        // those as "absent".
+        let present_columns: Vec<&str> = PROJECTED_COLUMNS
+            .iter()
+            .copied()
+            .filter(|name| {
+                schema_descr
+                    .columns()
+                    .iter()
+                    .any(|col| col.path().parts().first().map(String::as_str) == Some(*name))
+            })
+            .collect();
+        builder = builder.with_projection(ProjectionMask::columns(&schema_descr, present_columns));
+
         let reader = builder.build()?;
+        let path_buf = path.as_ref().to_path_buf();
 
+        Ok(reader.map(move |batch_result| {
+            let batch = batch_result.with_context(|| format!("Failed to read batch from {:?}", path_buf))?;
+            self.extract_frames_from_batch(&batch)
+        }))
+    }
+
+    /// Read sprite frames from a single parquet file
+    pub fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<SpriteFrame>> {
         let mut frames = Vec::new();
+        for batch_frames in self.read_file_batched(path.as_ref())? {
+            frames.extend(batch_frames?);
+        }
 
-        for batch_result in reader {
-            let batch = batch_result?;
+        log::info!("Read {} frames from {:?}", frames.len(), path.as_ref());
+        Ok(frames)
+    }
 
-            // Extract columns
-            let timestamp_col = batch
-                .column_by_name("timestamp")
-                .context("Missing timestamp column")?
-                .as_any()
-                .downcast_ref::<TimestampNanosecondArray>()
-                .context("Invalid timestamp column type")?;
+    /// Apply the timestamp/user/sprite_id extraction and filtering to one
+    /// already-decoded `RecordBatch`, the unit of work `read_file_batched`
+    /// yields one of at a time.
+    fn extract_frames_from_batch(&self, batch: &arrow::record_batch::RecordBatch) -> Result<Vec<SpriteFrame>> {
+        let mut frames = Vec::new();
 
-            let user_col = batch
-                .column_by_name("user")
-                .context("Missing user column")?;
+        // Extract columns
+        let timestamp_col = batch
+            .column_by_name("timestamp")
+            .context("Missing timestamp column")?
+            .as_any()
+            .downcast_ref::<TimestampNanosecondArray>()
+            .context("Invalid timestamp column type")?;
 
-            let env_id_col = batch
-                .column_by_name("env_id")
-                .context("Missing env_id column")?;
+        let user_col = batch
+            .column_by_name("user")
+            .context("Missing user column")?;
 
-            // sprite_id column is optional - some files may not have it
-            // Default to 0 if missing
-            let sprite_id_dict_opt = batch
-                .column_by_name("sprite_id")
-                .and_then(|col| col.as_any().downcast_ref::<DictionaryArray<Int8Type>>());
+        let env_id_col = batch
+            .column_by_name("env_id")
+            .context("Missing env_id column")?;
 
-            // Skip color and extra - they're not used in extraction
+        // sprite_id column is optional - some files may not have it
+        // Default to 0 if missing
+        let sprite_id_dict_opt = batch
+            .column_by_name("sprite_id")
+            .and_then(|col| col.as_any().downcast_ref::<DictionaryArray<Int8Type>>());
 
-            let coords_col = batch
-                .column_by_name("coords")
-                .context("Missing coords column")?
-                .as_any()
-                .downcast_ref::<ListArray>()
-                .context("Invalid coords column type")?;
+        // Skip color and extra - they're not used in extraction
 
-            // Process each row
-            for i in 0..batch.num_rows() {
-                // Extract timestamp
-                if timestamp_col.is_null(i) {
-                    continue;
-                }
-                let timestamp_nanos = timestamp_col.value(i);
-                let timestamp = Utc.timestamp_nanos(timestamp_nanos);
+        let coords_col = batch
+            .column_by_name("coords")
+            .context("Missing coords column")?
+            .as_any()
+            .downcast_ref::<ListArray>()
+            .context("Invalid coords column type")?;
+
+        // Process each row
+        for i in 0..batch.num_rows() {
+            // Extract timestamp
+            if timestamp_col.is_null(i) {
+                continue;
+            }
+            let timestamp_nanos = timestamp_col.value(i);
+            let timestamp = Utc.timestamp_nanos(timestamp_nanos);
 
-                // Apply timestamp filter
-                if let Some(start) = self.filter.timestamp_start {
-                    if timestamp < start {
-                        continue;
-                    }
+            // Apply timestamp filter
+            if let Some(start) = self.filter.timestamp_start {
+                if timestamp < start {
+                    continue;
                 }
-                if let Some(end) = self.filter.timestamp_end {
-                    if timestamp > end {
-                        continue;
-                    }
+            }
+            if let Some(end) = self.filter.timestamp_end {
+                if timestamp > end {
+                    continue;
                 }
+            }
 
-                // Extract user - skip row if null
-                let user = match get_dict_string(user_col.as_ref(), i)? {
-                    Some(s) => s,
-                    None => continue,
-                };
+            // Extract user - skip row if null
+            let user = match get_dict_string(user_col.as_ref(), i)? {
+                Some(s) => s,
+                None => continue,
+            };
 
-                // Apply user filter
-                if let Some(regex) = &self.filter.user_regex {
-                    if !regex.is_match(&user) {
-                        continue;
-                    }
+            // Apply user filter
+            if let Some(regex) = &self.filter.user_regex {
+                if !regex.is_match(&user) {
+                    continue;
                 }
+            }
 
-                // Extract env_id - skip row if null
-                let env_id = match get_dict_string(env_id_col.as_ref(), i)? {
-                    Some(s) => s,
-                    None => continue,
-                };
+            // Extract env_id - skip row if null
+            let env_id = match get_dict_string(env_id_col.as_ref(), i)? {
+                Some(s) => s,
+                None => continue,
+            };
 
-                // Extract sprite_id - match JS logic exactly:
-                // Default to 0 if column missing, null, or value out of range
-                let sprite_id = if let Some(sprite_id_dict) = sprite_id_dict_opt {
-                    if sprite_id_dict.is_null(i) {
+            // Extract sprite_id - match JS logic exactly:
+            // Default to 0 if column missing, null, or value out of range
+            let sprite_id = if let Some(sprite_id_dict) = sprite_id_dict_opt {
+                if sprite_id_dict.is_null(i) {
+                    0
+                } else {
+                    let key = sprite_id_dict.key(i).context("Invalid sprite_id key")?;
+                    let sprite_id_raw = if let Some(float_values) = sprite_id_dict
+                        .values()
+                        .as_any()
+                        .downcast_ref::<Float64Array>()
+                    {
+                        // Float64 values
+                        float_values.value(key as usize) as i32
+                    } else if let Some(string_values) = sprite_id_dict
+                        .values()
+                        .as_any()
+                        .downcast_ref::<StringArray>()
+                    {
+                        // String values - parse to int
+                        string_values.value(key as usize).parse::<i32>().unwrap_or(0)
+                    } else {
+                        // Unknown type, default to 0
                         0
+                    };
+
+                    if sprite_id_raw > 0 && sprite_id_raw < 50 {
+                        sprite_id_raw as u8
                     } else {
-                        let key = sprite_id_dict.key(i).context("Invalid sprite_id key")?;
-                        let sprite_id_raw = if let Some(float_values) = sprite_id_dict
-                            .values()
-                            .as_any()
-                            .downcast_ref::<Float64Array>()
-                        {
-                            // Float64 values
-                            float_values.value(key as usize) as i32
-                        } else if let Some(string_values) = sprite_id_dict
-                            .values()
-                            .as_any()
-                            .downcast_ref::<StringArray>()
-                        {
-                            // String values - parse to int
-                            string_values.value(key as usize).parse::<i32>().unwrap_or(0)
-                        } else {
-                            // Unknown type, default to 0
-                            0
-                        };
-
-                        if sprite_id_raw > 0 && sprite_id_raw < 50 {
-                            sprite_id_raw as u8
-                        } else {
-                            0
-                        }
+                        0
                     }
-                } else {
-                    // Column doesn't exist, default to 0
-                    0
-                };
-
-                // Skip color and extra - not used
-
-                // Extract coords - nested list structure
-                // Each row has a LIST of coordinates (a path)
-                if coords_col.is_null(i) {
-                    continue;
                 }
+            } else {
+                // Column doesn't exist, default to 0
+                0
+            };
 
-                let coords_list = coords_col.value(i);
-                let inner_list = coords_list
-                    .as_any()
-                    .downcast_ref::<ListArray>()
-                    .context("Invalid inner coords list")?;
+            // Skip color and extra - not used
 
-                if inner_list.len() == 0 {
-                    continue;
-                }
+            // Extract coords - nested list structure
+            // Each row has a LIST of coordinates (a path)
+            if coords_col.is_null(i) {
+                continue;
+            }
 
-                // Iterate through ALL coordinates in the path
-                for coord_idx in 0..inner_list.len() {
-                    let coord_list = inner_list.value(coord_idx);
-                    let coord_values = coord_list
-                        .as_any()
-                        .downcast_ref::<Int64Array>()
-                        .context("Invalid coord values")?;
+            let coords_list = coords_col.value(i);
+            let inner_list = coords_list
+                .as_any()
+                .downcast_ref::<ListArray>()
+                .context("Invalid inner coords list")?;
 
-                    if coord_values.len() < 3 {
-                        continue;
-                    }
+            if inner_list.len() == 0 {
+                continue;
+            }
 
-                    let coords = [
-                        coord_values.value(0),
-                        coord_values.value(1),
-                        coord_values.value(2),
-                    ];
-
-                    let compact = match (
-                        u8::try_from(coords[0]),
-                        u8::try_from(coords[1]),
-                        u8::try_from(coords[2]),
-                    ) {
-                        (Ok(x), Ok(y), Ok(map_id)) => [x, y, map_id],
-                        _ => [
-                            0,
-                            0,
-                            255,
-                        ],
-                    };
+            // Iterate through ALL coordinates in the path
+            for coord_idx in 0..inner_list.len() {
+                let coord_list = inner_list.value(coord_idx);
+                let coord_values = coord_list
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .context("Invalid coord values")?;
 
-                    // Each coordinate in the path gets the same timestamp/user/env_id
-                    // path_index preserves the order within this path
-                    frames.push(SpriteFrame {
-                        timestamp,
-                        user: user.clone(),
-                        env_id: env_id.clone(),
-                        sprite_id,
-                        color: String::new(), // Unused - placeholder
-                        extra: String::new(), // Unused - placeholder
-                        coords: compact,
-                        path_index: coord_idx,
-                    });
+                if coord_values.len() < 3 {
+                    continue;
                 }
+
+                // Keep coords at full i64 precision - they used to get
+                // rounded through `u8::try_from` here (collapsing to a
+                // (0,0,255) sentinel whenever a value didn't fit in a
+                // byte), which silently lost real data since these can
+                // run well past 255. `SpriteFrame::coords` is already
+                // `[i64; 3]`, so there's no need to narrow them this
+                // early - downstream consumers that need a narrower
+                // encoding (see `data::run_bundle`) make that tradeoff
+                // explicitly instead.
+                let coords = [
+                    coord_values.value(0),
+                    coord_values.value(1),
+                    coord_values.value(2),
+                ];
+
+                // Each coordinate in the path gets the same timestamp/user/env_id
+                // path_index preserves the order within this path
+                frames.push(SpriteFrame {
+                    timestamp,
+                    user: user.clone(),
+                    env_id: env_id.clone(),
+                    sprite_id,
+                    color: String::new(), // Unused - placeholder
+                    extra: String::new(), // Unused - placeholder
+                    coords,
+                    path_index: coord_idx,
+                });
             }
         }
 
-        log::info!("Read {} frames from {:?}", frames.len(), path.as_ref());
         Ok(frames)
     }
 
-    /// Read multiple parquet files from a directory
-    pub fn read_files<P: AsRef<Path>>(&self, files: &[P]) -> Result<Vec<SpriteFrame>> {
-        let mut all_frames = Vec::new();
-
-        for file_path in files {
-            let frames = self.read_file(file_path)?;
-            all_frames.extend(frames);
-        }
+    /// Read multiple parquet files from a directory, decoding files
+    /// concurrently (bounded by `max_concurrency`, if set) and merging the
+    /// per-file results. `group_into_sequences` sorts each sequence's frames
+    /// afterwards, so the order files finish in doesn't need to be preserved.
+    pub fn read_files<P: AsRef<Path> + Sync>(&self, files: &[P]) -> Result<Vec<SpriteFrame>> {
+        let read_all = || -> Result<Vec<SpriteFrame>> {
+            let per_file: Result<Vec<Vec<SpriteFrame>>> =
+                files.par_iter().map(|file_path| self.read_file(file_path)).collect();
+            Ok(per_file?.into_iter().flatten().collect())
+        };
+
+        let all_frames = match self.max_concurrency {
+            Some(max_concurrency) => rayon::ThreadPoolBuilder::new()
+                .num_threads(max_concurrency)
+                .build()
+                .context("Failed to build bounded-concurrency thread pool")?
+                .install(read_all)?,
+            None => read_all()?,
+        };
 
         log::info!("Total frames read: {}", all_frames.len());
         Ok(all_frames)
@@ -328,4 +466,42 @@ impl ParquetReader {
         log::info!("Grouped into {} sprite sequences", result.len());
         result
     }
+
+    /// Like `group_into_sequences`, but consumes a streaming source (e.g.
+    /// `read_file_batched`) and builds the `(user, env_id) -> SpriteSequence`
+    /// map online as batches arrive, rather than requiring every frame
+    /// collected into one `Vec` first. Frames within each sequence are still
+    /// sorted once at the end, since batches can interleave timestamps.
+    pub fn group_into_sequences_streaming(
+        batches: impl Iterator<Item = Result<Vec<SpriteFrame>>>,
+    ) -> Result<Vec<SpriteSequence>> {
+        let mut sequences: HashMap<String, SpriteSequence> = HashMap::new();
+
+        for batch in batches {
+            for frame in batch? {
+                let key = format!("{}-{}", frame.user, frame.env_id);
+
+                sequences
+                    .entry(key)
+                    .or_insert_with(|| SpriteSequence {
+                        user: frame.user.clone(),
+                        env_id: frame.env_id.clone(),
+                        sprite_id: frame.sprite_id,
+                        color: frame.color.clone(),
+                        frames: Vec::new(),
+                    })
+                    .frames
+                    .push(frame);
+            }
+        }
+
+        // Sort frames within each sequence by timestamp, then path_index
+        let mut result: Vec<SpriteSequence> = sequences.into_values().collect();
+        for seq in &mut result {
+            seq.frames.sort_by_key(|f| (f.timestamp, f.path_index));
+        }
+
+        log::info!("Grouped into {} sprite sequences", result.len());
+        Ok(result)
+    }
 }
@@ -0,0 +1,476 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use std::io::{Read, Write};
+
+/// Magic bytes identifying a run-bundle container, replacing the old bare
+/// sequence of `UltraCompactCoord` packed structs that `write_compact_run`
+/// used to emit with no header at all - undecodable without reading that
+/// exact source. A bundle is: a fixed header (magic, version, flags, and the
+/// global parameters shared by every run), followed by a sequence of
+/// length-prefixed run records.
+pub const RUN_BUNDLE_MAGIC: [u8; 4] = *b"RNBD";
+pub const RUN_BUNDLE_VERSION: u16 = 1;
+
+/// Fixed-size portion of the header, before the variable-length map-id table:
+/// magic(4) + version(2) + flags(2) + coordinate_scale(4) + sprite_atlas_id(1)
+/// + fps_hint(2) + map_id_count(2).
+const HEADER_FIXED_SIZE: usize = 17;
+
+/// Global parameters that apply to every run in the bundle, so downstream
+/// readers don't have to guess the coordinate scale or which sprite sheet to
+/// sample - written once at the start of the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunBundleHeader {
+    /// Reserved for future use (e.g. "coords are delta-encoded"); 0 today.
+    pub flags: u16,
+    /// Multiplier turning a run's raw `x`/`y` bytes into world-space pixels.
+    pub coordinate_scale: f32,
+    /// Which `TextureAtlas` layer every run's sprite was captured against.
+    pub sprite_atlas_id: u8,
+    /// Frame rate the extractor assumed when spacing out a run's steps.
+    pub fps_hint: u16,
+    /// Map-id table written before any run, so a reader can validate or
+    /// pre-load map assets before streaming runs. `extract_compact_runs`
+    /// fills it with every map id known to `map_data.json`, not only the ids
+    /// its runs end up referencing.
+    pub map_ids: Vec<u8>,
+}
+
+impl RunBundleHeader {
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&RUN_BUNDLE_MAGIC)?;
+        writer.write_all(&RUN_BUNDLE_VERSION.to_le_bytes())?;
+        writer.write_all(&self.flags.to_le_bytes())?;
+        writer.write_all(&self.coordinate_scale.to_le_bytes())?;
+        writer.write_all(&[self.sprite_atlas_id])?;
+        writer.write_all(&self.fps_hint.to_le_bytes())?;
+        writer.write_all(&(self.map_ids.len() as u16).to_le_bytes())?;
+        writer.write_all(&self.map_ids)?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut fixed = [0u8; HEADER_FIXED_SIZE];
+        reader
+            .read_exact(&mut fixed)
+            .context("Failed to read run bundle header")?;
+
+        if fixed[0..4] != RUN_BUNDLE_MAGIC {
+            bail!("Not a run bundle: bad magic {:?}", &fixed[0..4]);
+        }
+        let version = u16::from_le_bytes(fixed[4..6].try_into().unwrap());
+        if version != RUN_BUNDLE_VERSION {
+            bail!("Unsupported run bundle version: {}", version);
+        }
+        let flags = u16::from_le_bytes(fixed[6..8].try_into().unwrap());
+        let coordinate_scale = f32::from_le_bytes(fixed[8..12].try_into().unwrap());
+        let sprite_atlas_id = fixed[12];
+        let fps_hint = u16::from_le_bytes(fixed[13..15].try_into().unwrap());
+        let map_id_count = u16::from_le_bytes(fixed[15..17].try_into().unwrap()) as usize;
+
+        let mut map_ids = vec![0u8; map_id_count];
+        reader
+            .read_exact(&mut map_ids)
+            .context("Failed to read run bundle map-id table")?;
+
+        Ok(Self {
+            flags,
+            coordinate_scale,
+            sprite_atlas_id,
+            fps_hint,
+            map_ids,
+        })
+    }
+}
+
+/// One step of a run: a local tile coordinate plus which map it's on, as
+/// written by the original fixed-width `RUN_ENCODING_FIXED` layout. Stand-in
+/// for `UltraCompactCoord`, but without the `unsafe` packed-struct cast -
+/// every field is written/read explicitly as little-endian bytes. Clamps
+/// x/y/map_id to `u8`, same as `UltraCompactCoord` did - kept only for runs
+/// still using this encoding; new runs use `RUN_ENCODING_DELTA_VARINT`
+/// (see `write_run_delta`) to avoid that truncation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BundleCoord {
+    pub x: u8,
+    pub y: u8,
+    pub map_id: u8,
+}
+
+impl BundleCoord {
+    pub const ENCODED_SIZE: usize = 3;
+
+    pub fn to_bytes(self) -> [u8; Self::ENCODED_SIZE] {
+        [self.x, self.y, self.map_id]
+    }
+
+    pub fn from_bytes(bytes: [u8; Self::ENCODED_SIZE]) -> Self {
+        Self {
+            x: bytes[0],
+            y: bytes[1],
+            map_id: bytes[2],
+        }
+    }
+}
+
+/// A run's coordinate at full precision, regardless of which on-disk
+/// encoding produced it - what every decoded run ends up as. Matches the
+/// range of `SpriteFrame::coords` (`[i64; 3]`), since a delta-encoded run's
+/// running sum can legitimately go well past `u8`/`i32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunCoord {
+    pub x: i64,
+    pub y: i64,
+    pub map_id: i64,
+}
+
+impl From<BundleCoord> for RunCoord {
+    fn from(coord: BundleCoord) -> Self {
+        Self {
+            x: coord.x as i64,
+            y: coord.y as i64,
+            map_id: coord.map_id as i64,
+        }
+    }
+}
+
+/// A run's coordinate payload is either `RUN_ENCODING_FIXED`'s u8 triples
+/// (lossy, kept for runs already written that way) or
+/// `RUN_ENCODING_DELTA_VARINT`'s zigzag/varint delta stream (full precision,
+/// at 3 bytes/coord or more - see `write_run_delta`).
+pub const RUN_ENCODING_FIXED: u8 = 0;
+pub const RUN_ENCODING_DELTA_VARINT: u8 = 1;
+
+/// A single run's header fields, without its coordinate payload - a
+/// `SpriteFrame`-adjacent summary (sprite id, start time) plus however many
+/// coords follow it in the bundle. Mirrors what `SpriteSequence` holds for a
+/// parquet-sourced run, but sized for random-access streaming instead of a
+/// fully-materialized `Vec<SpriteFrame>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunRecordHeader {
+    pub sprite_id: u8,
+    pub start_timestamp: DateTime<Utc>,
+    pub coord_count: usize,
+    /// `RUN_ENCODING_FIXED` or `RUN_ENCODING_DELTA_VARINT` - which of the two
+    /// layouts the coord payload that follows uses.
+    pub encoding: u8,
+}
+
+impl RunRecordHeader {
+    /// sprite_id(1) + start_timestamp_secs(8) + start_timestamp_nanos(4) + coord_count(2) + encoding(1)
+    pub const ENCODED_SIZE: usize = 16;
+
+    fn encode(&self) -> Result<[u8; Self::ENCODED_SIZE]> {
+        anyhow::ensure!(
+            self.coord_count <= u16::MAX as usize,
+            "run has {} coords, but RunRecordHeader packs coord_count into a u16 field (max {})",
+            self.coord_count,
+            u16::MAX
+        );
+
+        let mut bytes = [0u8; Self::ENCODED_SIZE];
+        bytes[0] = self.sprite_id;
+        bytes[1..9].copy_from_slice(&self.start_timestamp.timestamp().to_le_bytes());
+        bytes[9..13].copy_from_slice(&self.start_timestamp.timestamp_subsec_nanos().to_le_bytes());
+        bytes[13..15].copy_from_slice(&(self.coord_count as u16).to_le_bytes());
+        bytes[15] = self.encoding;
+        Ok(bytes)
+    }
+
+    /// Decodes a record header read by a random-access caller (e.g.
+    /// `render_compact_runs`'s `PageCache`) that reads the fixed-size header
+    /// bytes itself rather than going through `RunBundleReader`.
+    pub fn decode(bytes: [u8; Self::ENCODED_SIZE]) -> Result<Self> {
+        let sprite_id = bytes[0];
+        let secs = i64::from_le_bytes(bytes[1..9].try_into().unwrap());
+        let nanos = u32::from_le_bytes(bytes[9..13].try_into().unwrap());
+        let start_timestamp = Utc
+            .timestamp_opt(secs, nanos)
+            .single()
+            .context("Invalid run record timestamp")?;
+        let coord_count = u16::from_le_bytes(bytes[13..15].try_into().unwrap()) as usize;
+        let encoding = bytes[15];
+
+        Ok(Self {
+            sprite_id,
+            start_timestamp,
+            coord_count,
+            encoding,
+        })
+    }
+}
+
+/// Writes one length-prefixed run record using the legacy `RUN_ENCODING_FIXED`
+/// layout: a 4-byte record length (covering everything after this field, so
+/// a reader that only understands an older layout can still skip unknown
+/// trailing fields in a future version), followed by the run header and its
+/// fixed-width coord payload. New runs should prefer `write_run_delta`.
+pub fn write_run<W: Write>(
+    writer: &mut W,
+    sprite_id: u8,
+    start_timestamp: DateTime<Utc>,
+    coords: &[BundleCoord],
+) -> Result<()> {
+    let header = RunRecordHeader {
+        sprite_id,
+        start_timestamp,
+        coord_count: coords.len(),
+        encoding: RUN_ENCODING_FIXED,
+    };
+    let record_len = RunRecordHeader::ENCODED_SIZE + coords.len() * BundleCoord::ENCODED_SIZE;
+
+    writer.write_all(&(record_len as u32).to_le_bytes())?;
+    writer.write_all(&header.encode()?)?;
+    for coord in coords {
+        writer.write_all(&coord.to_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Writes one length-prefixed run record using the `RUN_ENCODING_DELTA_VARINT`
+/// layout: each coord is stored as its `(dx, dy, dmap)` delta from the
+/// previous one (the first coord is implicitly a delta from `(0, 0, 0)`, so
+/// it round-trips as an absolute value with no special-casing), each delta
+/// zigzag-encoded (`(n << 1) ^ (n >> 63)`, the 64-bit generalization of the
+/// textbook 32-bit formula) so negative steps stay small, then LEB128
+/// varint-packed. Most consecutive steps move ±1 on one axis and stay on the
+/// same map, so each delta fits in one varint byte and the common case is
+/// 3 bytes/coord, the same size as the fixed format; no coord takes fewer
+/// than 3 bytes. Warps and large map ids still round-trip exactly, unlike
+/// `write_run`'s `u8`-clamped fixed format.
+pub fn write_run_delta<W: Write>(
+    writer: &mut W,
+    sprite_id: u8,
+    start_timestamp: DateTime<Utc>,
+    coords: &[RunCoord],
+) -> Result<()> {
+    let mut payload = Vec::new();
+    let mut prev = RunCoord { x: 0, y: 0, map_id: 0 };
+    for coord in coords {
+        write_varint(&mut payload, zigzag_encode(coord.x - prev.x));
+        write_varint(&mut payload, zigzag_encode(coord.y - prev.y));
+        write_varint(&mut payload, zigzag_encode(coord.map_id - prev.map_id));
+        prev = *coord;
+    }
+
+    let header = RunRecordHeader {
+        sprite_id,
+        start_timestamp,
+        coord_count: coords.len(),
+        encoding: RUN_ENCODING_DELTA_VARINT,
+    };
+    let record_len = RunRecordHeader::ENCODED_SIZE + payload.len();
+
+    writer.write_all(&(record_len as u32).to_le_bytes())?;
+    writer.write_all(&header.encode()?)?;
+    writer.write_all(&payload)?;
+
+    Ok(())
+}
+
+/// Decodes a `RUN_ENCODING_DELTA_VARINT` payload (everything after a run
+/// record's header) back into `coord_count` absolute `RunCoord`s by running
+/// sum - the inverse of `write_run_delta`.
+pub fn decode_delta_coords<R: Read>(reader: &mut R, coord_count: usize) -> Result<Vec<RunCoord>> {
+    let mut coords = Vec::with_capacity(coord_count);
+    let mut prev = RunCoord { x: 0, y: 0, map_id: 0 };
+    for _ in 0..coord_count {
+        let dx = zigzag_decode(read_varint(reader)?);
+        let dy = zigzag_decode(read_varint(reader)?);
+        let dmap = zigzag_decode(read_varint(reader)?);
+        let coord = RunCoord {
+            x: prev.x + dx,
+            y: prev.y + dy,
+            map_id: prev.map_id + dmap,
+        };
+        coords.push(coord);
+        prev = coord;
+    }
+    Ok(coords)
+}
+
+fn zigzag_encode(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn zigzag_decode(z: u64) -> i64 {
+    ((z >> 1) as i64) ^ -((z & 1) as i64)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value != 0 {
+            out.push(byte | 0x80);
+        } else {
+            out.push(byte);
+            break;
+        }
+    }
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut result = 0u64;
+    let mut shift = 0;
+    loop {
+        let mut byte_buf = [0u8; 1];
+        reader
+            .read_exact(&mut byte_buf)
+            .context("Failed to read varint byte")?;
+        let byte = byte_buf[0];
+        result |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    Ok(result)
+}
+
+/// A fully materialized run, for callers that want everything in one shot
+/// rather than streaming coords on demand (see `RunBundleReader` for the
+/// latter).
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    pub sprite_id: u8,
+    pub start_timestamp: DateTime<Utc>,
+    pub coords: Vec<RunCoord>,
+}
+
+/// Streams a run bundle back out of a plain `Read`, one record at a time,
+/// without hardcoding `UltraCompactCoord`'s old in-memory layout, and
+/// transparently decoding either coord encoding into full-precision
+/// `RunCoord`s. Tools that need random access into a large, possibly-paged
+/// file (e.g. `render_compact_runs`'s `PageCache`) can instead call
+/// `RunBundleHeader`, `RunRecordHeader`, `BundleCoord` and
+/// `decode_delta_coords` directly against whatever byte ranges they've
+/// already fetched.
+pub struct RunBundleReader<R: Read> {
+    reader: R,
+    pub header: RunBundleHeader,
+}
+
+impl<R: Read> RunBundleReader<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        let header = RunBundleHeader::read(&mut reader)?;
+        Ok(Self { reader, header })
+    }
+
+    /// Reads the next run record, or `None` once the bundle is exhausted.
+    pub fn next_run(&mut self) -> Result<Option<RunRecord>> {
+        let mut record_len_buf = [0u8; 4];
+        match self.reader.read_exact(&mut record_len_buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e).context("Failed to read run record length"),
+        }
+        let _record_len = u32::from_le_bytes(record_len_buf);
+
+        let mut header_buf = [0u8; RunRecordHeader::ENCODED_SIZE];
+        self.reader
+            .read_exact(&mut header_buf)
+            .context("Failed to read run record header")?;
+        let header = RunRecordHeader::decode(header_buf)?;
+
+        let coords = match header.encoding {
+            RUN_ENCODING_DELTA_VARINT => decode_delta_coords(&mut self.reader, header.coord_count)?,
+            _ => {
+                let mut coords = Vec::with_capacity(header.coord_count);
+                for _ in 0..header.coord_count {
+                    let mut coord_buf = [0u8; BundleCoord::ENCODED_SIZE];
+                    self.reader
+                        .read_exact(&mut coord_buf)
+                        .context("Failed to read run coord")?;
+                    coords.push(BundleCoord::from_bytes(coord_buf).into());
+                }
+                coords
+            }
+        };
+
+        Ok(Some(RunRecord {
+            sprite_id: header.sprite_id,
+            start_timestamp: header.start_timestamp,
+            coords,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_run_delta_round_trips_through_run_bundle_reader() {
+        let header = RunBundleHeader {
+            flags: 0,
+            coordinate_scale: 1.0,
+            sprite_atlas_id: 3,
+            fps_hint: 30,
+            map_ids: vec![1, 2],
+        };
+        let coords = vec![
+            RunCoord { x: 0, y: 0, map_id: 1 },
+            RunCoord { x: 1, y: 0, map_id: 1 },
+            RunCoord { x: 1, y: 1, map_id: 1 },
+            RunCoord { x: -5, y: 200, map_id: 2 },
+        ];
+        let start_timestamp = Utc.timestamp_opt(1_700_000_000, 123_000_000).single().unwrap();
+
+        let mut buf = Vec::new();
+        header.write(&mut buf).unwrap();
+        write_run_delta(&mut buf, 7, start_timestamp, &coords).unwrap();
+
+        let mut reader = RunBundleReader::new(buf.as_slice()).unwrap();
+        assert_eq!(reader.header, header);
+
+        let run = reader.next_run().unwrap().expect("expected one run");
+        assert_eq!(run.sprite_id, 7);
+        assert_eq!(run.start_timestamp, start_timestamp);
+        assert_eq!(run.coords, coords);
+
+        assert!(reader.next_run().unwrap().is_none());
+    }
+
+    #[test]
+    fn write_run_round_trips_through_run_bundle_reader() {
+        let header = RunBundleHeader {
+            flags: 0,
+            coordinate_scale: 1.0,
+            sprite_atlas_id: 0,
+            fps_hint: 60,
+            map_ids: vec![5],
+        };
+        let coords = vec![
+            BundleCoord { x: 10, y: 20, map_id: 5 },
+            BundleCoord { x: 11, y: 20, map_id: 5 },
+        ];
+        let start_timestamp = Utc.timestamp_opt(1_700_000_000, 0).single().unwrap();
+
+        let mut buf = Vec::new();
+        header.write(&mut buf).unwrap();
+        write_run(&mut buf, 1, start_timestamp, &coords).unwrap();
+
+        let mut reader = RunBundleReader::new(buf.as_slice()).unwrap();
+        let run = reader.next_run().unwrap().expect("expected one run");
+        assert_eq!(run.sprite_id, 1);
+        assert_eq!(run.start_timestamp, start_timestamp);
+        assert_eq!(
+            run.coords,
+            coords.into_iter().map(RunCoord::from).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn encode_rejects_coord_count_above_u16_max() {
+        let header = RunRecordHeader {
+            sprite_id: 0,
+            start_timestamp: Utc.timestamp_opt(0, 0).single().unwrap(),
+            coord_count: u16::MAX as usize + 1,
+            encoding: RUN_ENCODING_DELTA_VARINT,
+        };
+
+        assert!(header.encode().is_err());
+    }
+}
@@ -78,6 +78,68 @@ pub struct SpriteInstance {
     pub position: [f32; 2],      // Screen position in pixels
     pub sprite_id: u8,            // Which character (0-49)
     pub direction: Direction,     // Which direction sprite to use
+    pub frame_index: u32,         // Walk-cycle frame within the sprite sheet column
+    pub alpha: f32,                // Opacity, 0.0 (invisible) to 1.0 (opaque) - used for warp fades
+}
+
+/// How a walk-cycle frame index repeats once it reaches the end of the cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepeatMode {
+    /// Hold on the last frame once the cycle completes
+    Once,
+    /// Loop back to the first frame
+    Repeat,
+    /// Bounce back and forth between the first and last frame
+    PingPong,
+    /// Always the first frame (no animation)
+    Stop,
+}
+
+/// Parameters describing a sprite's walk-cycle frame sequence
+#[derive(Debug, Clone, Copy)]
+pub struct WalkCycle {
+    pub frame_count: u32,
+    pub frame_duration_ms: f32,
+    pub first_frame: u32,
+    pub repeat_mode: RepeatMode,
+}
+
+impl WalkCycle {
+    /// Fractional frame position for a sprite that has been animating for `age_ms`
+    fn frame_at(&self, age_ms: f32) -> f32 {
+        let m = self.frame_count.max(1) as f32;
+        let x = (age_ms / self.frame_duration_ms).max(0.0);
+
+        match self.repeat_mode {
+            RepeatMode::Once => x.min(m - 1.0),
+            RepeatMode::Repeat => x - (x / m).floor() * m,
+            RepeatMode::PingPong => {
+                let period = m * 2.0 - 1.0;
+                let frame = x - (x / period).floor() * period;
+                if frame >= m {
+                    2.0 * (m - 1.0) - frame
+                } else {
+                    frame
+                }
+            }
+            RepeatMode::Stop => 0.0,
+        }
+    }
+
+    /// Walk-cycle frame index within the sprite sheet for a sprite animating for `age_ms`
+    pub fn frame_index_at(&self, age_ms: f32) -> u32 {
+        self.first_frame + self.frame_at(age_ms).floor() as u32
+    }
+}
+
+/// How `AnimationInterpolator::interpolate_sprite` moves a sprite between
+/// coordinate points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterpolationMode {
+    /// Straight line between the current and next point
+    Linear,
+    /// Uniform Catmull-Rom curve through the surrounding four points
+    Spline,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -1,29 +1,43 @@
 mod animation;
 mod data;
+mod profiler;
 mod rendering;
 mod video;
+mod warp_validator;
+#[cfg(target_arch = "wasm32")]
+mod web;
 
 use animation::AnimationInterpolator;
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use clap::Parser;
-use data::{CoordinateMapper, ParquetFilter, ParquetReader};
+use clap::{Parser, ValueEnum};
+use data::{
+    CoordinateMapper, InterpolationMode, JsonlReader, ParquetFilter, ParquetReader, RepeatMode,
+    WalkCycle,
+};
+use profiler::{Profiler, Stage};
 use regex::Regex;
-use rendering::{GpuContext, SpriteInstance, SpriteRenderer, TextureAtlas};
+use rendering::{GpuContext, MapRenderer, PendingPixelReadback, SpriteInstance, SpriteRenderer, PIPELINE_DEPTH, TextureAtlas};
 use std::path::PathBuf;
-use video::ProResEncoder;
+use video::{Av1Encoder, CmafEncoder, GifEncoder, MsVideo1Encoder, ProResEncoder, VideoEncoder};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Directory containing parquet files
+    /// Directory containing parquet files (required unless --jsonl is set)
     #[arg(long)]
-    parquet_dir: PathBuf,
+    parquet_dir: Option<PathBuf>,
 
     /// Specific parquet files to process (optional, defaults to all part_*.parquet files)
     #[arg(long)]
     parquet_files: Vec<String>,
 
+    /// Read newline-delimited JSON coordinate rows from stdin instead of parquet
+    /// files, for near-live visualization of a running rollout. Each line is
+    /// `{"metadata":{"user":...,"color":...,"extra":...},"coords":[[x,y,z],...]}`
+    #[arg(long, default_value = "false")]
+    jsonl: bool,
+
     /// Path to sprite sheet image
     #[arg(long)]
     sprite_sheet: PathBuf,
@@ -67,6 +81,145 @@ struct Args {
     /// Maximum number of simultaneous sprites (for memory management)
     #[arg(long, default_value = "10000")]
     max_sprites: usize,
+
+    /// Number of frames in the walk-cycle animation
+    #[arg(long, default_value = "4")]
+    walk_frame_count: u32,
+
+    /// Duration of each walk-cycle frame in milliseconds
+    #[arg(long, default_value = "150")]
+    walk_frame_duration_ms: f32,
+
+    /// First frame index of the walk-cycle within the sprite sheet column
+    #[arg(long, default_value = "0")]
+    walk_first_frame: u32,
+
+    /// How the walk-cycle frame repeats once it reaches the end
+    #[arg(long, value_enum, default_value = "repeat")]
+    walk_repeat_mode: WalkRepeatModeArg,
+
+    /// Reuse the previous encoded frame when the per-frame change metric (summed
+    /// sprite position/appearance deltas) falls at or below this threshold,
+    /// skipping the GPU render and readback entirely. 0.0 (default) is lossless:
+    /// only frames whose sprites are unchanged are reused; raise it, à la a
+    /// block-codec quality setting, to
+    /// trade fidelity on idle stretches for throughput
+    #[arg(long, default_value = "0.0")]
+    skip_threshold: f32,
+
+    /// Optional map background image, stretched across the full canvas behind
+    /// sprites (the canvas already represents the whole world in the same
+    /// coordinate space CoordinateMapper maps sprite positions into)
+    #[arg(long)]
+    map_image: Option<PathBuf>,
+
+    /// Opacity of the map background layer, 0.0 (invisible) to 1.0 (opaque)
+    #[arg(long, default_value = "1.0")]
+    map_opacity: f32,
+
+    /// Tint multiplier applied to the map background's red channel, e.g.
+    /// 0.5 to dim it so sprite trails read clearly on top
+    #[arg(long, default_value = "1.0")]
+    map_tint_r: f32,
+
+    /// Tint multiplier applied to the map background's green channel
+    #[arg(long, default_value = "1.0")]
+    map_tint_g: f32,
+
+    /// Tint multiplier applied to the map background's blue channel
+    #[arg(long, default_value = "1.0")]
+    map_tint_b: f32,
+
+    /// Zoom factor for the map background: 1.0 (default) frames the whole
+    /// map, >1.0 crops to a 1/zoom-sized sub-region centered on
+    /// `--map-center-x`/`--map-center-y`
+    #[arg(long, default_value = "1.0")]
+    map_zoom: f32,
+
+    /// Center-x of the zoomed map sub-region, in 0..1 texture-space; only
+    /// relevant when `--map-zoom` > 1.0
+    #[arg(long, default_value = "0.5")]
+    map_center_x: f32,
+
+    /// Center-y of the zoomed map sub-region, in 0..1 texture-space; only
+    /// relevant when `--map-zoom` > 1.0
+    #[arg(long, default_value = "0.5")]
+    map_center_y: f32,
+
+    /// How to move sprites between coordinate points: straight lines, or a
+    /// smoother uniform Catmull-Rom spline through the surrounding points
+    #[arg(long, value_enum, default_value = "linear")]
+    interpolation_mode: InterpolationModeArg,
+
+    /// Video container/encoder backend. `prores` writes a conventional mp4
+    /// with the moov at the end; `cmaf` writes a fragmented mp4 (fMP4/CMAF)
+    /// that can be streamed and seeked before encoding finishes; `msvideo1`
+    /// skips ffmpeg and writes our own temporal block codec, tuned for the
+    /// mostly-static-background-plus-moving-sprites shape of this workload;
+    /// `gif` also skips ffmpeg, writing a single dependency-free, directly
+    /// shareable animated GIF; `av1` skips ffmpeg too, encoding straight to a
+    /// bare-IVF AV1 stream via rav1e for much smaller web-deliverable clips.
+    #[arg(long, value_enum, default_value = "prores")]
+    format: VideoFormatArg,
+
+    /// Quality knob for `--format msvideo1`, 0 (smallest/lossiest) to 100
+    /// (largest/most faithful). Ignored by the other formats.
+    #[arg(long, default_value = "100")]
+    quality: u8,
+
+    /// Rasterize the profiler's per-counter graphs into the top-left corner
+    /// of the output video, with a marker line at the 1000/fps frame budget
+    #[arg(long, default_value = "false")]
+    profiler_overlay: bool,
+
+    /// Duration in milliseconds of the fade-out/fade-in applied to a sprite
+    /// when it makes a confirmed warp (door, cave entrance, ladder, ...)
+    /// instead of an instant pop; clamped to half the frame interval
+    #[arg(long, default_value = "300.0")]
+    warp_fade_ms: f32,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum VideoFormatArg {
+    ProRes,
+    Cmaf,
+    MsVideo1,
+    Gif,
+    Av1,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum InterpolationModeArg {
+    Linear,
+    Spline,
+}
+
+impl From<InterpolationModeArg> for InterpolationMode {
+    fn from(mode: InterpolationModeArg) -> Self {
+        match mode {
+            InterpolationModeArg::Linear => InterpolationMode::Linear,
+            InterpolationModeArg::Spline => InterpolationMode::Spline,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum WalkRepeatModeArg {
+    Once,
+    Repeat,
+    PingPong,
+    Stop,
+}
+
+impl From<WalkRepeatModeArg> for RepeatMode {
+    fn from(mode: WalkRepeatModeArg) -> Self {
+        match mode {
+            WalkRepeatModeArg::Once => RepeatMode::Once,
+            WalkRepeatModeArg::Repeat => RepeatMode::Repeat,
+            WalkRepeatModeArg::PingPong => RepeatMode::PingPong,
+            WalkRepeatModeArg::Stop => RepeatMode::Stop,
+        }
+    }
 }
 
 fn main() -> Result<()> {
@@ -79,35 +232,6 @@ fn main() -> Result<()> {
     log::info!("Output: {:?}", args.output);
     log::info!("Canvas: {}x{} @ {} fps", args.width, args.height, args.fps);
 
-    // Build list of parquet files to process
-    let parquet_files: Vec<PathBuf> = if args.parquet_files.is_empty() {
-        // Find all part_*.parquet files
-        std::fs::read_dir(&args.parquet_dir)
-            .context("Failed to read parquet directory")?
-            .filter_map(|entry| {
-                let entry = entry.ok()?;
-                let path = entry.path();
-                if path.is_file() {
-                    if let Some(name) = path.file_name() {
-                        if name.to_string_lossy().starts_with("part_")
-                            && name.to_string_lossy().ends_with(".parquet")
-                        {
-                            return Some(path);
-                        }
-                    }
-                }
-                None
-            })
-            .collect()
-    } else {
-        args.parquet_files
-            .iter()
-            .map(|name| args.parquet_dir.join(name))
-            .collect()
-    };
-
-    log::info!("Processing {} parquet files", parquet_files.len());
-
     // Parse filters
     let user_regex = if let Some(pattern) = &args.user_filter {
         Some(Regex::new(pattern).context("Invalid user filter regex")?)
@@ -131,19 +255,57 @@ fn main() -> Result<()> {
         None
     };
 
+    let filter = ParquetFilter {
+        user_regex,
+        timestamp_start,
+        timestamp_end,
+    };
+
     // Load coordinate mapper
     log::info!("Loading map data from {:?}", args.map_data);
     let coordinate_mapper = CoordinateMapper::load(&args.map_data)?;
 
-    // Read parquet files
-    log::info!("Reading parquet files...");
-    let parquet_reader = ParquetReader::new(ParquetFilter {
-        user_regex,
-        timestamp_start,
-        timestamp_end,
-    });
+    let frames = if args.jsonl {
+        log::info!("Reading JSONL coordinate stream from stdin...");
+        JsonlReader::new(filter).read(std::io::stdin().lock())?
+    } else {
+        let parquet_dir = args
+            .parquet_dir
+            .as_ref()
+            .context("--parquet-dir is required unless --jsonl is set")?;
+
+        // Build list of parquet files to process
+        let parquet_files: Vec<PathBuf> = if args.parquet_files.is_empty() {
+            // Find all part_*.parquet files
+            std::fs::read_dir(parquet_dir)
+                .context("Failed to read parquet directory")?
+                .filter_map(|entry| {
+                    let entry = entry.ok()?;
+                    let path = entry.path();
+                    if path.is_file() {
+                        if let Some(name) = path.file_name() {
+                            if name.to_string_lossy().starts_with("part_")
+                                && name.to_string_lossy().ends_with(".parquet")
+                            {
+                                return Some(path);
+                            }
+                        }
+                    }
+                    None
+                })
+                .collect()
+        } else {
+            args.parquet_files
+                .iter()
+                .map(|name| parquet_dir.join(name))
+                .collect()
+        };
+
+        log::info!("Processing {} parquet files", parquet_files.len());
+        log::info!("Reading parquet files...");
+        ParquetReader::new(filter).read_files(&parquet_files)?
+    };
 
-    let frames = parquet_reader.read_files(&parquet_files)?;
     log::info!("Loaded {} frames", frames.len());
 
     if frames.is_empty() {
@@ -157,10 +319,19 @@ fn main() -> Result<()> {
     log::info!("Created {} sprite sequences", sequences.len());
 
     // Create animation interpolator
+    let walk_cycle = WalkCycle {
+        frame_count: args.walk_frame_count,
+        frame_duration_ms: args.walk_frame_duration_ms,
+        first_frame: args.walk_first_frame,
+        repeat_mode: args.walk_repeat_mode.into(),
+    };
     let interpolator = AnimationInterpolator::new(
         coordinate_mapper,
         args.interval_ms as f32,
         args.fps as f32,
+        walk_cycle,
+        args.interpolation_mode.into(),
+        args.warp_fade_ms,
     );
 
     let total_frames = interpolator.calculate_frame_count(&sequences);
@@ -183,43 +354,130 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Cheap per-frame change metric: sum of sprite position deltas, plus a fixed
+/// penalty per sprite whose texture rect (direction/walk-cycle frame) changed.
+/// A sprite entering or leaving the frame forces a full re-render.
+fn compute_change_metric(previous: Option<&[SpriteInstance]>, current: &[SpriteInstance]) -> f32 {
+    let Some(previous) = previous else {
+        return f32::INFINITY;
+    };
+    if previous.len() != current.len() {
+        return f32::INFINITY;
+    }
+
+    let mut metric = 0.0;
+    for (prev, curr) in previous.iter().zip(current.iter()) {
+        metric += (curr.position[0] - prev.position[0]).abs();
+        metric += (curr.position[1] - prev.position[1]).abs();
+        if curr.tex_rect != prev.tex_rect {
+            metric += 1.0;
+        }
+        metric += (curr.alpha - prev.alpha).abs();
+    }
+    metric
+}
+
 async fn render_video(
     args: Args,
     sequences: Vec<data::SpriteSequence>,
     interpolator: AnimationInterpolator,
     total_frames: usize,
 ) -> Result<()> {
-    // Initialize GPU
+    // Initialize GPU. The pipelined streaming path below always renders into
+    // the single-sample ring (`pipeline_target_view`), a separate mechanism
+    // from `render_texture`/MSAA (see `GpuContext`), so this stays unMSAA'd.
     log::info!("Initializing GPU context...");
-    let gpu = GpuContext::new(args.width, args.height).await?;
+    let gpu = GpuContext::new(args.width, args.height, 1).await?;
 
     // Load texture atlas
     log::info!("Loading sprite sheet from {:?}", args.sprite_sheet);
-    let texture_atlas = TextureAtlas::load(&gpu.device, &gpu.queue, &args.sprite_sheet)?;
+    let texture_atlas = TextureAtlas::load(&gpu.device, &gpu.queue, std::slice::from_ref(&args.sprite_sheet))?;
 
     // Create sprite renderer
     log::info!("Creating sprite renderer...");
-    let sprite_renderer = SpriteRenderer::new(
+    let mut sprite_renderer = SpriteRenderer::new(
         &gpu.device,
         &gpu.queue,
-        &texture_atlas,
+        &[&texture_atlas],
         args.width,
         args.height,
         args.max_sprites,
+        1,
+    )?;
+
+    // Create map background renderer
+    log::info!("Creating map background renderer...");
+    let map_renderer = MapRenderer::new(
+        &gpu.device,
+        &gpu.queue,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        args.map_image.as_ref(),
+        args.map_opacity,
+        [args.map_tint_r, args.map_tint_g, args.map_tint_b],
+        args.map_zoom,
+        [args.map_center_x, args.map_center_y],
+        1,
     )?;
 
     // Create video encoder
     log::info!("Starting video encoder...");
-    let mut encoder = ProResEncoder::new(&args.output, args.width, args.height, args.fps)?;
+    let mut encoder = match args.format {
+        VideoFormatArg::ProRes => {
+            VideoEncoder::ProRes(ProResEncoder::new(&args.output, args.width, args.height, args.fps)?)
+        }
+        VideoFormatArg::Cmaf => {
+            VideoEncoder::Cmaf(CmafEncoder::new(&args.output, args.width, args.height, args.fps)?)
+        }
+        VideoFormatArg::MsVideo1 => VideoEncoder::MsVideo1(MsVideo1Encoder::new(
+            &args.output,
+            args.width,
+            args.height,
+            args.fps,
+            args.quality,
+        )?),
+        VideoFormatArg::Gif => {
+            VideoEncoder::Gif(GifEncoder::new(&args.output, args.width, args.height, args.fps)?)
+        }
+        VideoFormatArg::Av1 => {
+            VideoEncoder::Av1(Av1Encoder::new(&args.output, args.width, args.height, args.fps)?)
+        }
+    };
 
     // Render each frame
     log::info!("Rendering {} frames...", total_frames);
     let start_time = std::time::Instant::now();
+    let mut profiler = Profiler::new(args.fps as f32, 120);
+    // The immediately preceding frame's sprite instances, for `compute_change_metric`.
+    // Updated every frame regardless of skip/pipeline bookkeeping so the skip decision
+    // always compares adjacent frames, independent of when the GPU readback resolves
+    // (see `PIPELINE_DEPTH` - `previous_pixels` below lags behind this by design).
+    let mut last_frame_instances: Option<Vec<SpriteInstance>> = None;
+    let mut previous_pixels: Option<Vec<u8>> = None;
+    let mut skipped_frames = 0usize;
+
+    // Frames are written out on a dedicated thread so the main loop never
+    // blocks on ffmpeg's stdin pipe (or MsVideo1's own file I/O) while the
+    // GPU could already be rendering ahead (see `rendering::PIPELINE_DEPTH`).
+    let (frame_tx, frame_rx) = std::sync::mpsc::sync_channel::<Vec<u8>>(PIPELINE_DEPTH);
+    let encoder_thread = std::thread::spawn(move || -> Result<()> {
+        while let Ok(frame) = frame_rx.recv() {
+            encoder.write_frame(&frame)?;
+        }
+        encoder.finish()
+    });
+
+    // Frames submitted to the GPU but not yet read back. Up to `PIPELINE_DEPTH`
+    // can be in flight at once, letting the GPU keep rendering ahead of the
+    // comparatively slow readback + encode steps instead of stalling on each.
+    let mut in_flight: std::collections::VecDeque<(usize, PendingPixelReadback)> =
+        std::collections::VecDeque::with_capacity(PIPELINE_DEPTH);
 
     for frame_number in 0..total_frames {
+        let frame_start = std::time::Instant::now();
         let time_ms = interpolator.frame_to_time(frame_number);
 
         // Calculate sprite instances for this frame
+        let interp_start = std::time::Instant::now();
         let mut sprite_instances = Vec::new();
 
         for sequence in &sequences {
@@ -227,8 +485,10 @@ async fn render_video(
                 if let Some(sprite_data) = interpolator.interpolate_sprite(sequence, &state) {
                     // Get texture coordinates for this sprite
                     let tex_coords = texture_atlas.get_sprite_tex_coords(
+                        0,
                         sprite_data.sprite_id,
                         sprite_data.direction,
+                        sprite_data.frame_index,
                     );
 
                     // Center the sprite (sprite is 16x16, position is top-left in shader)
@@ -239,24 +499,84 @@ async fn render_video(
                             sprite_data.position[1] - 8.0,
                         ],
                         tex_rect: tex_coords,
+                        layer: 0,
+                        alpha: sprite_data.alpha,
+                        tint: [1.0, 1.0, 1.0, 1.0],
                     });
                 }
             }
         }
+        profiler.record(Stage::Interpolation, interp_start.elapsed().as_secs_f32() * 1000.0);
+        profiler.record(Stage::LiveSpriteCount, sprite_instances.len() as f32);
+
+        let change_metric = compute_change_metric(last_frame_instances.as_deref(), &sprite_instances);
+        let reuse_previous_frame = change_metric <= args.skip_threshold && previous_pixels.is_some();
+        last_frame_instances = Some(sprite_instances.clone());
+
+        let sprite_count = sprite_instances.len();
+        if reuse_previous_frame {
+            // A skipped frame re-encodes `previous_pixels` as-is, which only
+            // reflects the most recently *resolved* render. Drain every
+            // in-flight frame first so this repeat frame can never land ahead
+            // of a render that's still pending in the pipeline.
+            while let Some((in_flight_frame, pending)) = in_flight.pop_front() {
+                let resolved_pixels = gpu.resolve_pixel_readback(pending).await?;
+                if let Some(gpu_ms) = gpu.read_gpu_frame_time_ms_pipelined(in_flight_frame).await? {
+                    profiler.record(Stage::GpuRender, gpu_ms);
+                }
+                previous_pixels = Some(resolved_pixels);
+                encode_frame(&mut profiler, previous_pixels.as_ref().unwrap(), &frame_tx, &args)?;
+            }
+
+            skipped_frames += 1;
+            encode_frame(&mut profiler, &previous_pixels.clone().unwrap(), &frame_tx, &args)?;
+        } else {
+            // Bracket the GPU work with timestamp queries so render time is measured
+            // on-device rather than just around the (non-blocking) submit call
+            let slot = frame_number % PIPELINE_DEPTH;
+            let target_view = gpu.pipeline_target_view(slot);
 
-        // Render frame
-        sprite_renderer.render(
-            &gpu.device,
-            &gpu.queue,
-            &gpu.render_texture_view,
-            &sprite_instances,
-        )?;
+            let mut timing_start_encoder = gpu.device.create_command_encoder(
+                &wgpu::CommandEncoderDescriptor { label: Some("GPU Timing Start") },
+            );
+            gpu.begin_gpu_timing_pipelined(&mut timing_start_encoder, slot);
+            gpu.queue.submit(Some(timing_start_encoder.finish()));
 
-        // Read pixels from GPU
-        let pixels = gpu.read_pixels().await?;
+            // Draw the map background first, then sprites on top without re-clearing
+            let render_start = std::time::Instant::now();
+            map_renderer.render(&gpu.device, &gpu.queue, target_view, None)?;
+            sprite_renderer.render(&gpu.device, &gpu.queue, target_view, None, &sprite_instances, false)?;
+            profiler.record(Stage::RenderSubmit, render_start.elapsed().as_secs_f32() * 1000.0);
+
+            let mut timing_end_encoder = gpu.device.create_command_encoder(
+                &wgpu::CommandEncoderDescriptor { label: Some("GPU Timing End") },
+            );
+            gpu.end_gpu_timing_pipelined(&mut timing_end_encoder, slot);
+            gpu.queue.submit(Some(timing_end_encoder.finish()));
+
+            let readback_start = std::time::Instant::now();
+            let pending = gpu.submit_pixel_readback(slot);
+            profiler.record(Stage::Readback, readback_start.elapsed().as_secs_f32() * 1000.0);
+
+            in_flight.push_back((frame_number, pending));
+
+            // Once the ring is full, the oldest in-flight frame's slot is
+            // about to be reused by a future submit, so its readback must be
+            // resolved now. Until then, this frame's own pixels stay
+            // unresolved - they'll be picked up once they reach the front of
+            // `in_flight`, letting the GPU render ahead of the readback+encode.
+            if in_flight.len() >= PIPELINE_DEPTH {
+                let (resolved_frame, pending) = in_flight.pop_front().unwrap();
+                let resolved_pixels = gpu.resolve_pixel_readback(pending).await?;
+                if let Some(gpu_ms) = gpu.read_gpu_frame_time_ms_pipelined(resolved_frame).await? {
+                    profiler.record(Stage::GpuRender, gpu_ms);
+                }
+                previous_pixels = Some(resolved_pixels.clone());
+                encode_frame(&mut profiler, &resolved_pixels, &frame_tx, &args)?;
+            }
+        }
 
-        // Write to encoder
-        encoder.write_frame(&pixels)?;
+        profiler.record(Stage::TotalFrame, frame_start.elapsed().as_secs_f32() * 1000.0);
 
         // Progress logging
         if frame_number % 60 == 0 || frame_number == total_frames - 1 {
@@ -272,20 +592,61 @@ async fn render_video(
                 total_frames,
                 fps_actual,
                 eta,
-                sprite_instances.len()
+                sprite_count
             );
         }
+
+        // Per-stage profiler summary, less frequently than the progress line
+        if frame_number % 300 == 0 || frame_number == total_frames - 1 {
+            profiler.log_summary(frame_number);
+        }
+    }
+
+    // Flush any frames still in the pipeline once the main loop is done
+    while let Some((in_flight_frame, pending)) = in_flight.pop_front() {
+        let resolved_pixels = gpu.resolve_pixel_readback(pending).await?;
+        if let Some(gpu_ms) = gpu.read_gpu_frame_time_ms_pipelined(in_flight_frame).await? {
+            profiler.record(Stage::GpuRender, gpu_ms);
+        }
+        previous_pixels = Some(resolved_pixels.clone());
+        encode_frame(&mut profiler, &resolved_pixels, &frame_tx, &args)?;
     }
 
     let elapsed = start_time.elapsed();
     log::info!(
-        "Rendering complete! Total time: {:.2}s ({:.2} fps)",
+        "Rendering complete! Total time: {:.2}s ({:.2} fps) | Skipped {}/{} idle frames",
         elapsed.as_secs_f32(),
-        total_frames as f32 / elapsed.as_secs_f32()
+        total_frames as f32 / elapsed.as_secs_f32(),
+        skipped_frames,
+        total_frames
     );
+    profiler.log_final_report();
 
-    // Finalize encoder
-    encoder.finish()?;
+    // Signal the encoder thread to finish and surface any encoding error
+    drop(frame_tx);
+    encoder_thread
+        .join()
+        .map_err(|_| anyhow::anyhow!("Encoder thread panicked"))??;
 
     Ok(())
 }
+
+/// Draw the profiler overlay (if enabled) onto a resolved frame and hand it
+/// to the encoder thread, timing the hand-off itself as `Stage::Encode`
+fn encode_frame(
+    profiler: &mut Profiler,
+    pixels: &[u8],
+    frame_tx: &std::sync::mpsc::SyncSender<Vec<u8>>,
+    args: &Args,
+) -> Result<()> {
+    let encode_start = std::time::Instant::now();
+    let mut pixels = pixels.to_vec();
+    if args.profiler_overlay {
+        profiler.render_overlay(&mut pixels, args.width as usize, args.height as usize);
+    }
+    frame_tx
+        .send(pixels)
+        .context("Encoder thread exited before all frames were sent")?;
+    profiler.record(Stage::Encode, encode_start.elapsed().as_secs_f32() * 1000.0);
+    Ok(())
+}
@@ -0,0 +1,260 @@
+use std::collections::VecDeque;
+
+/// Pipeline stage tracked by the profiler. Add new stages here and to
+/// `STAGES` / `stage_name` together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Interpolation,
+    RenderSubmit,
+    GpuRender,
+    Readback,
+    Encode,
+    TotalFrame,
+    LiveSpriteCount,
+}
+
+const STAGES: [Stage; 7] = [
+    Stage::Interpolation,
+    Stage::RenderSubmit,
+    Stage::GpuRender,
+    Stage::Readback,
+    Stage::Encode,
+    Stage::TotalFrame,
+    Stage::LiveSpriteCount,
+];
+
+fn stage_name(stage: Stage) -> &'static str {
+    match stage {
+        Stage::Interpolation => "interpolation",
+        Stage::RenderSubmit => "render_submit",
+        Stage::GpuRender => "gpu_render",
+        Stage::Readback => "readback",
+        Stage::Encode => "encode",
+        Stage::TotalFrame => "total_frame",
+        Stage::LiveSpriteCount => "live_sprite_count",
+    }
+}
+
+/// Whether a stage's values are milliseconds (so they can be compared
+/// against the frame budget and get a budget marker line in the overlay) or
+/// some other unit (e.g. a sprite count).
+fn stage_is_time_based(stage: Stage) -> bool {
+    !matches!(stage, Stage::LiveSpriteCount)
+}
+
+/// Rolling average/max over a sliding window of samples. Gaps are fine -
+/// a counter with no samples this frame simply isn't recorded.
+struct Counter {
+    samples: VecDeque<f32>,
+    window: usize,
+    lifetime_count: u64,
+    lifetime_sum_ms: f64,
+}
+
+impl Counter {
+    fn new(window: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(window),
+            window,
+            lifetime_count: 0,
+            lifetime_sum_ms: 0.0,
+        }
+    }
+
+    fn record(&mut self, ms: f32) {
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(ms);
+        self.lifetime_count += 1;
+        self.lifetime_sum_ms += ms as f64;
+    }
+
+    fn avg(&self) -> f32 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples.iter().sum::<f32>() / self.samples.len() as f32
+    }
+
+    fn max(&self) -> f32 {
+        self.samples.iter().cloned().fold(0.0, f32::max)
+    }
+
+    fn lifetime_avg(&self) -> f32 {
+        if self.lifetime_count == 0 {
+            return 0.0;
+        }
+        (self.lifetime_sum_ms / self.lifetime_count as f64) as f32
+    }
+
+    fn samples(&self) -> &VecDeque<f32> {
+        &self.samples
+    }
+}
+
+/// Pixel dimensions of each per-counter panel the overlay draws, and the
+/// margin between panels/from the canvas edge.
+const PANEL_WIDTH: usize = 160;
+const PANEL_HEIGHT: usize = 60;
+const PANEL_MARGIN: usize = 6;
+
+/// Per-stage render/encode profiler with rolling average/max counters over a
+/// sliding window, reported relative to the `1000/fps` frame budget.
+pub struct Profiler {
+    counters: [Counter; STAGES.len()],
+    frame_budget_ms: f32,
+}
+
+impl Profiler {
+    pub fn new(fps: f32, window: usize) -> Self {
+        Self {
+            counters: STAGES.map(|_| Counter::new(window)),
+            frame_budget_ms: 1000.0 / fps,
+        }
+    }
+
+    fn index(stage: Stage) -> usize {
+        STAGES.iter().position(|&s| s == stage).expect("stage missing from STAGES")
+    }
+
+    pub fn record(&mut self, stage: Stage, ms: f32) {
+        self.counters[Self::index(stage)].record(ms);
+    }
+
+    /// Compact periodic summary: avg/max per stage as a fraction of the frame budget
+    pub fn log_summary(&self, frame_number: usize) {
+        let mut parts = Vec::with_capacity(STAGES.len());
+        for &stage in &STAGES {
+            let counter = &self.counters[Self::index(stage)];
+            parts.push(format!(
+                "{}={:.2}/{:.2}ms({:.0}%)",
+                stage_name(stage),
+                counter.avg(),
+                counter.max(),
+                counter.avg() / self.frame_budget_ms * 100.0
+            ));
+        }
+        log::info!(
+            "[profiler frame {}] budget={:.2}ms {}",
+            frame_number,
+            self.frame_budget_ms,
+            parts.join(" ")
+        );
+    }
+
+    /// Full per-stage summary table using lifetime averages, meant for the end of a run
+    pub fn log_final_report(&self) {
+        log::info!("=== Profiler final report (frame budget: {:.2}ms) ===", self.frame_budget_ms);
+        log::info!("  {:<18} {:>10} {:>10} {:>10}", "stage", "avg", "max", "% budget");
+        for &stage in &STAGES {
+            let counter = &self.counters[Self::index(stage)];
+            if stage_is_time_based(stage) {
+                log::info!(
+                    "  {:<18} {:>7.3}ms {:>7.3}ms {:>9.1}%",
+                    stage_name(stage),
+                    counter.lifetime_avg(),
+                    counter.max(),
+                    counter.lifetime_avg() / self.frame_budget_ms * 100.0
+                );
+            } else {
+                log::info!(
+                    "  {:<18} {:>9.1} {:>9.1} {:>10}",
+                    stage_name(stage),
+                    counter.lifetime_avg(),
+                    counter.max(),
+                    "n/a"
+                );
+            }
+        }
+    }
+
+    /// Rasterize a small per-counter sparkline graph for each stage into the
+    /// top-left corner of an RGBA8 frame buffer, with a horizontal marker
+    /// line at the `1000/fps` frame budget on time-based counters, so frames
+    /// that blow the real-time budget are visually obvious in the output
+    /// video. Counters with fewer than two samples (a gap) are skipped.
+    pub fn render_overlay(&self, pixels: &mut [u8], canvas_width: usize, canvas_height: usize) {
+        let mut x_offset = PANEL_MARGIN;
+        for &stage in &STAGES {
+            if x_offset + PANEL_WIDTH + PANEL_MARGIN > canvas_width || PANEL_HEIGHT + 2 * PANEL_MARGIN > canvas_height {
+                break;
+            }
+            let counter = &self.counters[Self::index(stage)];
+            self.draw_panel(pixels, canvas_width, canvas_height, x_offset, PANEL_MARGIN, stage, counter);
+            x_offset += PANEL_WIDTH + PANEL_MARGIN;
+        }
+    }
+
+    fn draw_panel(
+        &self,
+        pixels: &mut [u8],
+        canvas_width: usize,
+        canvas_height: usize,
+        panel_x: usize,
+        panel_y: usize,
+        stage: Stage,
+        counter: &Counter,
+    ) {
+        let samples = counter.samples();
+        if samples.len() < 2 {
+            return;
+        }
+
+        // Semi-transparent black backdrop so the graph reads over any frame content
+        for dy in 0..PANEL_HEIGHT {
+            for dx in 0..PANEL_WIDTH {
+                blend_pixel(pixels, canvas_width, canvas_height, panel_x + dx, panel_y + dy, [0, 0, 0], 0.55);
+            }
+        }
+
+        let sample_max = samples.iter().cloned().fold(0.0f32, f32::max).max(1e-6);
+        let scale = if stage_is_time_based(stage) {
+            sample_max.max(self.frame_budget_ms * 1.2)
+        } else {
+            sample_max
+        };
+
+        let to_panel_y = |value: f32| -> usize {
+            let t = (value / scale).clamp(0.0, 1.0);
+            (PANEL_HEIGHT - 1).saturating_sub((t * (PANEL_HEIGHT - 1) as f32).round() as usize)
+        };
+
+        if stage_is_time_based(stage) {
+            let budget_y = to_panel_y(self.frame_budget_ms);
+            for dx in 0..PANEL_WIDTH {
+                set_pixel(pixels, canvas_width, canvas_height, panel_x + dx, panel_y + budget_y, [220, 60, 60]);
+            }
+        }
+
+        for dx in 0..PANEL_WIDTH {
+            let sample_index = dx * samples.len() / PANEL_WIDTH;
+            let value = samples[sample_index.min(samples.len() - 1)];
+            let y = to_panel_y(value);
+            set_pixel(pixels, canvas_width, canvas_height, panel_x + dx, panel_y + y, [80, 220, 120]);
+        }
+    }
+}
+
+fn set_pixel(pixels: &mut [u8], canvas_width: usize, canvas_height: usize, x: usize, y: usize, rgb: [u8; 3]) {
+    if x >= canvas_width || y >= canvas_height {
+        return;
+    }
+    let offset = (y * canvas_width + x) * 4;
+    pixels[offset] = rgb[0];
+    pixels[offset + 1] = rgb[1];
+    pixels[offset + 2] = rgb[2];
+    pixels[offset + 3] = 255;
+}
+
+fn blend_pixel(pixels: &mut [u8], canvas_width: usize, canvas_height: usize, x: usize, y: usize, rgb: [u8; 3], alpha: f32) {
+    if x >= canvas_width || y >= canvas_height {
+        return;
+    }
+    let offset = (y * canvas_width + x) * 4;
+    for c in 0..3 {
+        let existing = pixels[offset + c] as f32;
+        pixels[offset + c] = (existing * (1.0 - alpha) + rgb[c] as f32 * alpha).round() as u8;
+    }
+    pixels[offset + 3] = 255;
+}
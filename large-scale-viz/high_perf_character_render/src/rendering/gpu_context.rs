@@ -1,5 +1,126 @@
 use anyhow::{Context, Result};
 use wgpu;
+use wgpu::util::DeviceExt;
+
+/// Depth of the pipelined render-target/staging-buffer ring used by
+/// `pipeline_target_view`/`submit_pixel_readback`. Frame K+1 can be submitted
+/// into slot `(K+1) % PIPELINE_DEPTH` while frame K's slot is still being
+/// mapped for readback, so the GPU queue and the CPU-side encoder don't
+/// serialize on each other the way a single render target/staging buffer would.
+pub const PIPELINE_DEPTH: usize = 3;
+
+/// Texture format all offscreen render targets share (the final RGBA buffer
+/// `read_pixels` hands to `ProResEncoder`).
+const RENDER_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Highest-to-lowest sample counts wgpu exposes multisample support flags
+/// for, paired with the flag that gates each one.
+const MSAA_CANDIDATES: &[(u32, wgpu::TextureFormatFeatureFlags)] = &[
+    (16, wgpu::TextureFormatFeatureFlags::MULTISAMPLE_X16),
+    (8, wgpu::TextureFormatFeatureFlags::MULTISAMPLE_X8),
+    (4, wgpu::TextureFormatFeatureFlags::MULTISAMPLE_X4),
+    (2, wgpu::TextureFormatFeatureFlags::MULTISAMPLE_X2),
+];
+
+/// Picks the highest sample count no greater than `requested` that `flags`
+/// actually supports, falling back to 1 (no MSAA) if `requested <= 1` or
+/// nothing the adapter supports is low enough.
+pub(crate) fn resolve_sample_count(flags: wgpu::TextureFormatFeatureFlags, requested: u32) -> u32 {
+    MSAA_CANDIDATES
+        .iter()
+        .filter(|&&(count, _)| count <= requested)
+        .find(|&&(_, flag)| flags.contains(flag))
+        .map(|&(count, _)| count)
+        .unwrap_or(1)
+}
+
+/// Rounds `value` up to the next multiple of `alignment` (`alignment` must be
+/// a power of two), matching wgpu's `COPY_BYTES_PER_ROW_ALIGNMENT` requirement
+/// on `bytes_per_row` for `copy_texture_to_buffer`.
+fn align_up(value: u32, alignment: u32) -> u32 {
+    (value + alignment - 1) & !(alignment - 1)
+}
+
+/// Compute shader (modeled on Vello's writeback pass) that splits a packed
+/// RGBA readback buffer into tightly packed RGB and alpha-mask planes on the
+/// GPU, so `ProResEncoder` no longer needs a per-pixel CPU loop. Each
+/// invocation handles 4 horizontally consecutive pixels at once so its writes
+/// land on whole `u32` words of the output planes without racing neighboring
+/// invocations - `read_pixels_split` requires `width % 4 == 0` for this to
+/// divide evenly (every canvas size this crate renders at does).
+const WRITEBACK_SHADER_SOURCE: &str = r#"
+struct Params {
+    width: u32,
+    height: u32,
+    padded_words_per_row: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+
+@group(0) @binding(1)
+var<storage, read> input_pixels: array<u32>;
+
+@group(0) @binding(2)
+var<storage, read_write> rgb_output: array<u32>;
+
+@group(0) @binding(3)
+var<storage, read_write> mask_output: array<u32>;
+
+@compute @workgroup_size(8, 8)
+fn writeback(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let group_x = global_id.x;
+    let y = global_id.y;
+    if (group_x * 4u >= params.width || y >= params.height) {
+        return;
+    }
+
+    var rgb_words = array<u32, 3>(0u, 0u, 0u);
+    var mask_word: u32 = 0u;
+
+    for (var i: u32 = 0u; i < 4u; i = i + 1u) {
+        let x = group_x * 4u + i;
+        if (x >= params.width) {
+            break;
+        }
+
+        let input_index = y * params.padded_words_per_row + x;
+        let rgba = unpack4x8unorm(input_pixels[input_index]);
+        let r = u32(round(rgba.r * 255.0));
+        let g = u32(round(rgba.g * 255.0));
+        let b = u32(round(rgba.b * 255.0));
+        let a = u32(round(rgba.a * 255.0));
+
+        let r_byte = i * 3u;
+        let g_byte = i * 3u + 1u;
+        let b_byte = i * 3u + 2u;
+        rgb_words[r_byte / 4u] |= (r << ((r_byte % 4u) * 8u));
+        rgb_words[g_byte / 4u] |= (g << ((g_byte % 4u) * 8u));
+        rgb_words[b_byte / 4u] |= (b << ((b_byte % 4u) * 8u));
+
+        mask_word |= (a << (i * 8u));
+    }
+
+    let rgb_row_words = params.width * 3u / 4u;
+    let mask_row_words = params.width / 4u;
+
+    let rgb_base = y * rgb_row_words + group_x * 3u;
+    rgb_output[rgb_base] = rgb_words[0];
+    rgb_output[rgb_base + 1u] = rgb_words[1];
+    rgb_output[rgb_base + 2u] = rgb_words[2];
+
+    mask_output[y * mask_row_words + group_x] = mask_word;
+}
+"#;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct WritebackParams {
+    width: u32,
+    height: u32,
+    padded_words_per_row: u32,
+    _padding: u32,
+}
 
 pub struct GpuContext {
     pub device: wgpu::Device,
@@ -8,11 +129,88 @@ pub struct GpuContext {
     pub render_texture_view: wgpu::TextureView,
     pub width: u32,
     pub height: u32,
+    /// Sample count actually in effect (after falling back to the highest
+    /// count the adapter supports), 1 meaning MSAA is off.
+    pub sample_count: u32,
+    // Multisampled color target sprites/map quads actually draw into when
+    // `sample_count > 1`; resolved into `render_texture` before `read_pixels`.
+    // `None` when MSAA is off, in which case callers render into
+    // `render_texture_view` directly (see `color_attachment_target`).
+    msaa_texture_view: Option<wgpu::TextureView>,
+    // Reusable mapped-read staging buffer for `read_pixels`, padded per-row to
+    // `COPY_BYTES_PER_ROW_ALIGNMENT` (see `align_up`). Allocated once here
+    // instead of per frame, since a render loop calls `read_pixels` thousands
+    // of times at the same fixed `width`/`height`.
+    read_pixels_buffer: wgpu::Buffer,
+    read_pixels_padded_bytes_per_row: u32,
+    // GPU writeback pass (see `WRITEBACK_SHADER_SOURCE`) that splits the
+    // rendered frame into `writeback_rgb_buffer`/`writeback_mask_buffer`
+    // without a CPU loop; those are then copied into the two staging buffers
+    // below for `read_pixels_split` to map. `writeback_input_buffer` is a
+    // separate copy target from `read_pixels_buffer` because wgpu buffers
+    // can't combine `MAP_READ` with `STORAGE_BINDING` usage.
+    writeback_pipeline: wgpu::ComputePipeline,
+    writeback_bind_group: wgpu::BindGroup,
+    writeback_input_buffer: wgpu::Buffer,
+    writeback_rgb_buffer: wgpu::Buffer,
+    writeback_mask_buffer: wgpu::Buffer,
+    writeback_rgb_staging_buffer: wgpu::Buffer,
+    writeback_mask_staging_buffer: wgpu::Buffer,
+    writeback_dispatch: (u32, u32),
+    timestamp_query_set: Option<wgpu::QuerySet>,
+    timestamp_resolve_buffer: Option<wgpu::Buffer>,
+    timestamp_readback_buffer: Option<wgpu::Buffer>,
+    timestamp_period_ns: f32,
+    // Ring buffers backing the pipelined readback path (see `PIPELINE_DEPTH`).
+    // Kept separate from the single `render_texture` above so callers that
+    // don't need pipelining (most of the `src/bin` tools) are unaffected.
+    pipeline_textures: Vec<wgpu::Texture>,
+    pipeline_texture_views: Vec<wgpu::TextureView>,
+    pipeline_staging_buffers: Vec<wgpu::Buffer>,
+    pipeline_timestamp_query_set: Option<wgpu::QuerySet>,
+    pipeline_timestamp_resolve_buffers: Vec<wgpu::Buffer>,
+    pipeline_timestamp_readback_buffers: Vec<wgpu::Buffer>,
+}
+
+/// A readback submitted via `GpuContext::submit_pixel_readback` whose
+/// `map_async` callback has been registered but not yet awaited. Resolve it
+/// with `GpuContext::resolve_pixel_readback` once the caller actually needs
+/// the pixels (e.g. when the pipeline ring is full and a slot must be freed).
+pub struct PendingPixelReadback {
+    slot: usize,
+    receiver: futures_intrusive::channel::shared::OneshotReceiver<Result<(), wgpu::BufferAsyncError>>,
 }
 
 impl GpuContext {
-    /// Initialize WGPU in headless mode for offscreen rendering
-    pub async fn new(width: u32, height: u32) -> Result<Self> {
+    /// Initialize WGPU in headless mode for offscreen rendering. `sample_count`
+    /// requests MSAA for the offscreen color target (Ruffle uses 4 as its
+    /// `DEFAULT_SAMPLE_COUNT`); pass 1 to render aliased. The requested count is
+    /// validated against the adapter's supported multisample counts for
+    /// `RENDER_TEXTURE_FORMAT` and silently lowered to the highest one it
+    /// actually supports (see `resolve_sample_count`). Equivalent to
+    /// `new_with_backend(width, height, sample_count, false)`; see `new_cpu`
+    /// for a software-rendering fallback.
+    pub async fn new(width: u32, height: u32, sample_count: u32) -> Result<Self> {
+        Self::new_with_backend(width, height, sample_count, false).await
+    }
+
+    /// Like `new`, but rasterizes on the CPU instead of a GPU (wgpu's
+    /// `force_fallback_adapter`, backed by Mesa llvmpipe/SwiftShader on
+    /// whatever `wgpu::Backends` is available). Everything downstream -
+    /// `SpriteRenderer`, `MapRenderer`, `read_pixels`, `ProResEncoder` - is
+    /// unchanged and produces identical pixels, just much slower, since it's
+    /// still the exact same wgpu render graph running on a software adapter
+    /// rather than a hand-rolled second rasterizer. Useful on headless CI or
+    /// cloud boxes with no GPU.
+    pub async fn new_cpu(width: u32, height: u32, sample_count: u32) -> Result<Self> {
+        Self::new_with_backend(width, height, sample_count, true).await
+    }
+
+    /// Shared implementation behind `new`/`new_cpu`. When `force_cpu` is
+    /// false, tries a hardware adapter first and falls back to a software one
+    /// if none is found (e.g. headless CI with no GPU) rather than failing
+    /// outright.
+    async fn new_with_backend(width: u32, height: u32, sample_count: u32, force_cpu: bool) -> Result<Self> {
         log::info!("Initializing GPU context ({}x{})", width, height);
 
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
@@ -20,24 +218,59 @@ impl GpuContext {
             ..Default::default()
         });
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: None,
-                force_fallback_adapter: false,
-            })
-            .await
-            .context("Failed to find a suitable GPU adapter")?;
+        let hardware_adapter = if force_cpu {
+            None
+        } else {
+            instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::HighPerformance,
+                    compatible_surface: None,
+                    force_fallback_adapter: false,
+                })
+                .await
+        };
+
+        let adapter = match hardware_adapter {
+            Some(adapter) => adapter,
+            None => {
+                if !force_cpu {
+                    log::warn!("No hardware GPU adapter found; falling back to software rendering");
+                }
+                instance
+                    .request_adapter(&wgpu::RequestAdapterOptions {
+                        power_preference: wgpu::PowerPreference::HighPerformance,
+                        compatible_surface: None,
+                        force_fallback_adapter: true,
+                    })
+                    .await
+                    .context("Failed to find a suitable GPU adapter (hardware or software fallback)")?
+            }
+        };
 
         log::info!("Using GPU: {:?}", adapter.get_info());
 
+        let format_features = adapter.get_texture_format_features(RENDER_TEXTURE_FORMAT);
+        let sample_count = resolve_sample_count(format_features.flags, sample_count.max(1));
+        if sample_count > 1 {
+            log::info!("MSAA enabled: {}x", sample_count);
+        }
+
         let max_buffer_size = adapter.limits().max_buffer_size;
 
+        // Opt into GPU timestamp queries when the adapter supports them so render
+        // time can be measured on-device rather than around the wall-clock submit.
+        let supports_timestamps = adapter.features().contains(wgpu::Features::TIMESTAMP_QUERY);
+        let required_features = if supports_timestamps {
+            wgpu::Features::TIMESTAMP_QUERY
+        } else {
+            wgpu::Features::empty()
+        };
+
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: Some("Render Device"),
-                    required_features: wgpu::Features::empty(),
+                    required_features,
                     required_limits: wgpu::Limits {
                         max_texture_dimension_2d: 8192,
                         max_buffer_size,
@@ -50,7 +283,35 @@ impl GpuContext {
             .await
             .context("Failed to create device")?;
 
-        // Create render texture
+        let timestamp_period_ns = queue.get_timestamp_period();
+
+        let (timestamp_query_set, timestamp_resolve_buffer, timestamp_readback_buffer) =
+            if supports_timestamps {
+                let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("Frame Timestamp Query Set"),
+                    ty: wgpu::QueryType::Timestamp,
+                    count: 2,
+                });
+                let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Timestamp Resolve Buffer"),
+                    size: 2 * std::mem::size_of::<u64>() as u64,
+                    usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                });
+                let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Timestamp Readback Buffer"),
+                    size: 2 * std::mem::size_of::<u64>() as u64,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                });
+                (Some(query_set), Some(resolve_buffer), Some(readback_buffer))
+            } else {
+                log::warn!("GPU does not support TIMESTAMP_QUERY; falling back to wall-clock timing");
+                (None, None, None)
+            };
+
+        // Create render texture (single-sample; this is what read_pixels copies
+        // out of, and the resolve target when MSAA is enabled below)
         let render_texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Render Texture"),
             size: wgpu::Extent3d {
@@ -61,13 +322,253 @@ impl GpuContext {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format: RENDER_TEXTURE_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[],
         });
 
         let render_texture_view = render_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
+        // Multisampled color target actually drawn into when MSAA is enabled;
+        // resolved into `render_texture_view` by the render pass's
+        // `resolve_target` (see `color_attachment_target`). Multisampled
+        // textures can't be copied to a buffer directly, so this has no
+        // COPY_SRC usage and is never read back itself.
+        let msaa_texture_view = if sample_count > 1 {
+            let msaa_texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("MSAA Render Texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: RENDER_TEXTURE_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+            Some(msaa_texture.create_view(&wgpu::TextureViewDescriptor::default()))
+        } else {
+            None
+        };
+
+        // Staging buffer `read_pixels` maps every frame, padded to wgpu's
+        // row-alignment requirement (see `align_up`)
+        let read_pixels_padded_bytes_per_row = align_up(width * 4, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let read_pixels_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Read Pixels Staging Buffer"),
+            size: (read_pixels_padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        // GPU writeback pass: splits the rendered frame into RGB/mask planes
+        // on-device instead of `ProResEncoder::write_frame`'s old per-pixel
+        // CPU loop (see `WRITEBACK_SHADER_SOURCE`). The shader packs 4 pixels
+        // per `u32` row-word, which only covers every pixel when `width` is a
+        // multiple of 4 - buffers/dispatch are sized for that case here, but
+        // the constructor itself doesn't require it, since the pipelined
+        // (`submit_pixel_readback`/`resolve_pixel_readback`) path never
+        // touches these buffers at all. `read_pixels_split` enforces the
+        // constraint at the point of actual use instead.
+        let writeback_padded_words_per_row = read_pixels_padded_bytes_per_row / 4;
+        let writeback_input_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Writeback Input Buffer"),
+            size: (read_pixels_padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let writeback_rgb_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Writeback RGB Buffer"),
+            size: (width * height * 3) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let writeback_mask_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Writeback Mask Buffer"),
+            size: (width * height) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let writeback_rgb_staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Writeback RGB Staging Buffer"),
+            size: writeback_rgb_buffer.size(),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let writeback_mask_staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Writeback Mask Staging Buffer"),
+            size: writeback_mask_buffer.size(),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let writeback_params = WritebackParams {
+            width,
+            height,
+            padded_words_per_row: writeback_padded_words_per_row,
+            _padding: 0,
+        };
+        let writeback_params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Writeback Params Buffer"),
+            contents: bytemuck::bytes_of(&writeback_params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let writeback_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Writeback Shader"),
+            source: wgpu::ShaderSource::Wgsl(WRITEBACK_SHADER_SOURCE.into()),
+        });
+        let writeback_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Writeback Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let writeback_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Writeback Bind Group"),
+            layout: &writeback_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: writeback_params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: writeback_input_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: writeback_rgb_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: writeback_mask_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let writeback_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Writeback Pipeline Layout"),
+            bind_group_layouts: &[&writeback_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let writeback_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Writeback Pipeline"),
+            layout: Some(&writeback_pipeline_layout),
+            module: &writeback_shader,
+            entry_point: Some("writeback"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let writeback_dispatch = (
+            (width / 4 + 7) / 8,
+            (height + 7) / 8,
+        );
+
+        // Ring of offscreen render targets + staging buffers for the
+        // pipelined readback path (see `PIPELINE_DEPTH`)
+        let mut pipeline_textures = Vec::with_capacity(PIPELINE_DEPTH);
+        let mut pipeline_texture_views = Vec::with_capacity(PIPELINE_DEPTH);
+        let mut pipeline_staging_buffers = Vec::with_capacity(PIPELINE_DEPTH);
+        // Padded like `read_pixels_padded_bytes_per_row` - `copy_texture_to_buffer`
+        // requires `bytes_per_row` be a multiple of `COPY_BYTES_PER_ROW_ALIGNMENT`,
+        // which `width * 4` isn't for an arbitrary `--width` (see `align_up`)
+        let pixel_buffer_size = (read_pixels_padded_bytes_per_row * height) as u64;
+        for _ in 0..PIPELINE_DEPTH {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Pipelined Render Texture"),
+                size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Pipelined Staging Buffer"),
+                size: pixel_buffer_size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            pipeline_textures.push(texture);
+            pipeline_texture_views.push(view);
+            pipeline_staging_buffers.push(staging_buffer);
+        }
+
+        // Independent timestamp query/resolve/readback buffers per ring slot
+        // so a frame's timing readback never races the next frame's
+        // write_timestamp into the same buffer (see `PIPELINE_DEPTH`)
+        let (pipeline_timestamp_query_set, pipeline_timestamp_resolve_buffers, pipeline_timestamp_readback_buffers) =
+            if supports_timestamps {
+                let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("Pipelined Frame Timestamp Query Set"),
+                    ty: wgpu::QueryType::Timestamp,
+                    count: 2 * PIPELINE_DEPTH as u32,
+                });
+                let mut resolve_buffers = Vec::with_capacity(PIPELINE_DEPTH);
+                let mut readback_buffers = Vec::with_capacity(PIPELINE_DEPTH);
+                for _ in 0..PIPELINE_DEPTH {
+                    resolve_buffers.push(device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("Pipelined Timestamp Resolve Buffer"),
+                        size: 2 * std::mem::size_of::<u64>() as u64,
+                        usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    }));
+                    readback_buffers.push(device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("Pipelined Timestamp Readback Buffer"),
+                        size: 2 * std::mem::size_of::<u64>() as u64,
+                        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                        mapped_at_creation: false,
+                    }));
+                }
+                (Some(query_set), resolve_buffers, readback_buffers)
+            } else {
+                (None, Vec::new(), Vec::new())
+            };
+
         log::info!("GPU context initialized successfully");
 
         Ok(Self {
@@ -77,19 +578,101 @@ impl GpuContext {
             render_texture_view,
             width,
             height,
+            sample_count,
+            msaa_texture_view,
+            read_pixels_buffer,
+            read_pixels_padded_bytes_per_row,
+            writeback_pipeline,
+            writeback_bind_group,
+            writeback_input_buffer,
+            writeback_rgb_buffer,
+            writeback_mask_buffer,
+            writeback_rgb_staging_buffer,
+            writeback_mask_staging_buffer,
+            writeback_dispatch,
+            timestamp_query_set,
+            timestamp_resolve_buffer,
+            timestamp_readback_buffer,
+            timestamp_period_ns,
+            pipeline_textures,
+            pipeline_texture_views,
+            pipeline_staging_buffers,
+            pipeline_timestamp_query_set,
+            pipeline_timestamp_resolve_buffers,
+            pipeline_timestamp_readback_buffers,
         })
     }
 
-    /// Read pixels from render texture
-    pub async fn read_pixels(&self) -> Result<Vec<u8>> {
-        let buffer_size = (self.width * self.height * 4) as u64;
-        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Pixel Buffer"),
-            size: buffer_size,
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-            mapped_at_creation: false,
+    /// Write the "render start" timestamp into `encoder`, if the GPU supports it
+    pub fn begin_gpu_timing(&self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(query_set) = &self.timestamp_query_set {
+            encoder.write_timestamp(query_set, 0);
+        }
+    }
+
+    /// Write the "render end" timestamp and resolve it into the readback buffer
+    pub fn end_gpu_timing(&self, encoder: &mut wgpu::CommandEncoder) {
+        if let (Some(query_set), Some(resolve_buffer), Some(readback_buffer)) = (
+            &self.timestamp_query_set,
+            &self.timestamp_resolve_buffer,
+            &self.timestamp_readback_buffer,
+        ) {
+            encoder.write_timestamp(query_set, 1);
+            encoder.resolve_query_set(query_set, 0..2, resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(
+                resolve_buffer,
+                0,
+                readback_buffer,
+                0,
+                2 * std::mem::size_of::<u64>() as u64,
+            );
+        }
+    }
+
+    /// Read back the last recorded GPU render time in milliseconds, once the
+    /// command buffer containing `begin_gpu_timing`/`end_gpu_timing` has been submitted
+    pub async fn read_gpu_frame_time_ms(&self) -> Result<Option<f32>> {
+        let Some(readback_buffer) = &self.timestamp_readback_buffer else {
+            return Ok(None);
+        };
+
+        let buffer_slice = readback_buffer.slice(..);
+        let (tx, rx) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            tx.send(result).ok();
         });
 
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.receive().await.context("Failed to map timestamp readback buffer")??;
+
+        let data = buffer_slice.get_mapped_range();
+        let start = u64::from_le_bytes(data[0..8].try_into().unwrap());
+        let end = u64::from_le_bytes(data[8..16].try_into().unwrap());
+        drop(data);
+        readback_buffer.unmap();
+
+        let elapsed_ns = end.saturating_sub(start) as f32 * self.timestamp_period_ns;
+        Ok(Some(elapsed_ns / 1_000_000.0))
+    }
+
+    /// The view + resolve target a render pass color attachment should use
+    /// this frame: the multisampled view with `render_texture_view` as its
+    /// resolve target when `sample_count > 1`, or `render_texture_view`
+    /// directly with no resolve target otherwise. `read_pixels` always reads
+    /// from `render_texture`, so callers must draw through this (not
+    /// `render_texture_view` directly) for MSAA to take effect.
+    pub fn color_attachment_target(&self) -> (&wgpu::TextureView, Option<&wgpu::TextureView>) {
+        match &self.msaa_texture_view {
+            Some(msaa_view) => (msaa_view, Some(&self.render_texture_view)),
+            None => (&self.render_texture_view, None),
+        }
+    }
+
+    /// Read pixels from the render texture as a tightly packed `width*4*height`
+    /// RGBA buffer. Copies through `read_pixels_buffer`, a reusable staging
+    /// buffer padded to wgpu's `COPY_BYTES_PER_ROW_ALIGNMENT`, stripping the
+    /// per-row padding back out before returning.
+    pub async fn read_pixels(&self) -> Result<Vec<u8>> {
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Read Pixels Encoder"),
         });
@@ -102,10 +685,10 @@ impl GpuContext {
                 aspect: wgpu::TextureAspect::All,
             },
             wgpu::ImageCopyBuffer {
-                buffer: &buffer,
+                buffer: &self.read_pixels_buffer,
                 layout: wgpu::ImageDataLayout {
                     offset: 0,
-                    bytes_per_row: Some(self.width * 4),
+                    bytes_per_row: Some(self.read_pixels_padded_bytes_per_row),
                     rows_per_image: Some(self.height),
                 },
             },
@@ -118,7 +701,7 @@ impl GpuContext {
 
         self.queue.submit(Some(encoder.finish()));
 
-        let buffer_slice = buffer.slice(..);
+        let buffer_slice = self.read_pixels_buffer.slice(..);
         let (tx, rx) = futures_intrusive::channel::shared::oneshot_channel();
         buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
             tx.send(result).ok();
@@ -128,8 +711,241 @@ impl GpuContext {
 
         rx.receive().await.context("Failed to map buffer")??;
 
+        let unpadded_bytes_per_row = (self.width * 4) as usize;
+        let padded_bytes_per_row = self.read_pixels_padded_bytes_per_row as usize;
+
+        let data = buffer_slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * self.height as usize);
+        for row in data.chunks(padded_bytes_per_row) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+        }
+        drop(data);
+        self.read_pixels_buffer.unmap();
+
+        Ok(pixels)
+    }
+
+    /// Read the rendered frame already split into an RGB plane and an alpha
+    /// mask plane (see `WRITEBACK_SHADER_SOURCE`), for `ProResEncoder` to feed
+    /// straight into its two ffmpeg stdin pipes with no CPU-side split. Not a
+    /// replacement for `read_pixels`: callers that need the combined RGBA
+    /// buffer (e.g. `BlockCodecEncoder`) still use that instead.
+    pub async fn read_pixels_split(&self) -> Result<(Vec<u8>, Vec<u8>)> {
+        anyhow::ensure!(
+            self.width % 4 == 0,
+            "read_pixels_split requires width to be a multiple of 4 for the writeback compute pass, got {}",
+            self.width
+        );
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Writeback Encoder"),
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.render_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &self.writeback_input_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.read_pixels_padded_bytes_per_row),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Writeback Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.writeback_pipeline);
+            pass.set_bind_group(0, &self.writeback_bind_group, &[]);
+            pass.dispatch_workgroups(self.writeback_dispatch.0, self.writeback_dispatch.1, 1);
+        }
+
+        encoder.copy_buffer_to_buffer(
+            &self.writeback_rgb_buffer,
+            0,
+            &self.writeback_rgb_staging_buffer,
+            0,
+            self.writeback_rgb_buffer.size(),
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.writeback_mask_buffer,
+            0,
+            &self.writeback_mask_staging_buffer,
+            0,
+            self.writeback_mask_buffer.size(),
+        );
+
+        self.queue.submit(Some(encoder.finish()));
+
+        let rgb_slice = self.writeback_rgb_staging_buffer.slice(..);
+        let mask_slice = self.writeback_mask_staging_buffer.slice(..);
+        let (rgb_tx, rgb_rx) = futures_intrusive::channel::shared::oneshot_channel();
+        let (mask_tx, mask_rx) = futures_intrusive::channel::shared::oneshot_channel();
+        rgb_slice.map_async(wgpu::MapMode::Read, move |result| {
+            rgb_tx.send(result).ok();
+        });
+        mask_slice.map_async(wgpu::MapMode::Read, move |result| {
+            mask_tx.send(result).ok();
+        });
+
+        self.device.poll(wgpu::Maintain::Wait);
+
+        rgb_rx.receive().await.context("Failed to map writeback RGB buffer")??;
+        mask_rx.receive().await.context("Failed to map writeback mask buffer")??;
+
+        let rgb_data = rgb_slice.get_mapped_range();
+        let rgb_pixels = rgb_data.to_vec();
+        drop(rgb_data);
+        self.writeback_rgb_staging_buffer.unmap();
+
+        let mask_data = mask_slice.get_mapped_range();
+        let mask_pixels = mask_data.to_vec();
+        drop(mask_data);
+        self.writeback_mask_staging_buffer.unmap();
+
+        Ok((rgb_pixels, mask_pixels))
+    }
+
+    /// Render target for pipelined ring slot `slot % PIPELINE_DEPTH`
+    pub fn pipeline_target_view(&self, slot: usize) -> &wgpu::TextureView {
+        &self.pipeline_texture_views[slot % PIPELINE_DEPTH]
+    }
+
+    /// Write the "render start" timestamp for a pipeline ring slot, if the GPU supports it
+    pub fn begin_gpu_timing_pipelined(&self, encoder: &mut wgpu::CommandEncoder, slot: usize) {
+        if let Some(query_set) = &self.pipeline_timestamp_query_set {
+            encoder.write_timestamp(query_set, (2 * (slot % PIPELINE_DEPTH)) as u32);
+        }
+    }
+
+    /// Write the "render end" timestamp and resolve it into the slot's own
+    /// readback buffer, so it doesn't race the next in-flight frame's timing
+    pub fn end_gpu_timing_pipelined(&self, encoder: &mut wgpu::CommandEncoder, slot: usize) {
+        let slot = slot % PIPELINE_DEPTH;
+        if let Some(query_set) = &self.pipeline_timestamp_query_set {
+            let resolve_buffer = &self.pipeline_timestamp_resolve_buffers[slot];
+            let readback_buffer = &self.pipeline_timestamp_readback_buffers[slot];
+            let first_query = (2 * slot) as u32;
+            encoder.write_timestamp(query_set, first_query + 1);
+            encoder.resolve_query_set(query_set, first_query..first_query + 2, resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(
+                resolve_buffer,
+                0,
+                readback_buffer,
+                0,
+                2 * std::mem::size_of::<u64>() as u64,
+            );
+        }
+    }
+
+    /// Read back a pipeline ring slot's last recorded GPU render time in milliseconds
+    pub async fn read_gpu_frame_time_ms_pipelined(&self, slot: usize) -> Result<Option<f32>> {
+        let slot = slot % PIPELINE_DEPTH;
+        if self.pipeline_timestamp_query_set.is_none() {
+            return Ok(None);
+        }
+        let readback_buffer = &self.pipeline_timestamp_readback_buffers[slot];
+
+        let buffer_slice = readback_buffer.slice(..);
+        let (tx, rx) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            tx.send(result).ok();
+        });
+
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.receive().await.context("Failed to map pipelined timestamp readback buffer")??;
+
         let data = buffer_slice.get_mapped_range();
-        let pixels = data.to_vec();
+        let start = u64::from_le_bytes(data[0..8].try_into().unwrap());
+        let end = u64::from_le_bytes(data[8..16].try_into().unwrap());
+        drop(data);
+        readback_buffer.unmap();
+
+        let elapsed_ns = end.saturating_sub(start) as f32 * self.timestamp_period_ns;
+        Ok(Some(elapsed_ns / 1_000_000.0))
+    }
+
+    /// Submit a non-blocking copy of a pipeline ring slot's render target into
+    /// its staging buffer and register a `map_async` callback. Does not block
+    /// on the mapping completing - the caller can go on to submit the next
+    /// frame's render work immediately, and only needs to await the returned
+    /// `PendingPixelReadback` once it actually needs the pixels (typically
+    /// once the ring is full and a slot must be freed).
+    pub fn submit_pixel_readback(&self, slot: usize) -> PendingPixelReadback {
+        let slot = slot % PIPELINE_DEPTH;
+        let texture = &self.pipeline_textures[slot];
+        let buffer = &self.pipeline_staging_buffers[slot];
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Pipelined Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.read_pixels_padded_bytes_per_row),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            wgpu::Extent3d { width: self.width, height: self.height, depth_or_array_layers: 1 },
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = buffer.slice(..);
+        let (tx, rx) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            tx.send(result).ok();
+        });
+
+        // Pump already-completed callbacks without blocking, so slots that
+        // finished mapping while we were busy rendering get drained promptly
+        self.device.poll(wgpu::Maintain::Poll);
+
+        PendingPixelReadback { slot, receiver: rx }
+    }
+
+    /// Block until a previously-submitted pipelined readback's pixels are
+    /// mapped and return them, unmapping the staging buffer so its ring slot
+    /// can be reused by a later frame
+    pub async fn resolve_pixel_readback(&self, pending: PendingPixelReadback) -> Result<Vec<u8>> {
+        self.device.poll(wgpu::Maintain::Wait);
+        pending
+            .receiver
+            .receive()
+            .await
+            .context("Failed to map pipelined readback buffer")??;
+
+        let buffer = &self.pipeline_staging_buffers[pending.slot];
+        let data = buffer.slice(..).get_mapped_range();
+
+        // Strip the row padding `submit_pixel_readback` copied in with (see
+        // `read_pixels`'s matching unpad loop)
+        let unpadded_bytes_per_row = (self.width * 4) as usize;
+        let padded_bytes_per_row = self.read_pixels_padded_bytes_per_row as usize;
+        let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * self.height as usize);
+        for row in data.chunks(padded_bytes_per_row) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+        }
         drop(data);
         buffer.unmap();
 
@@ -0,0 +1,675 @@
+use crate::rendering::pipeline::SpriteInstance;
+use anyhow::Result;
+use bytemuck::{Pod, Zeroable};
+use wgpu;
+use wgpu::util::DeviceExt;
+
+/// Alternate to `SpritePipeline` for visit-frequency visualization: instead of
+/// alpha-blending every instanced quad (cost scales with overdraw, and dense
+/// regions just saturate to solid color), this accumulates a per-pixel visit
+/// count in a storage buffer via `atomicAdd` and colormaps it in a second
+/// pass, so cost scales with visited pixels rather than instance count and
+/// density reads out independent of draw order.
+///
+/// Three GPU passes, run in order every time a caller wants an up-to-date
+/// heatmap:
+/// 1. `accumulate` (compute) - one invocation per sprite instance, each
+///    `atomicAdd`s into the count buffer at its downscaled pixel coordinate.
+/// 2. `reduce_max` (compute) - one invocation per count-buffer pixel,
+///    `atomicMax`s into a single-element max buffer for the log normalization
+///    below (avoids a CPU round-trip just to find the max).
+/// 3. `colormap_fs` (fullscreen fragment pass) - reads both buffers, computes
+///    `log(1+count)/log(1+max)`, and maps it through a small viridis lookup
+///    (see `VIRIDIS_STOPS`) into `output_view`.
+///
+/// `accumulate` can be called multiple times (e.g. once per chunk of a
+/// streamed run) before a single `render_to_texture`; call `reset` in
+/// between runs that shouldn't share a count buffer.
+const ACCUMULATE_SHADER_SOURCE: &str = r#"
+struct AccumulateParams {
+    canvas_width: u32,
+    canvas_height: u32,
+    downscale: u32,
+    sprite_count: u32,
+    camera_offset: vec2<f32>,
+};
+
+// `array<f32, 4>` rather than `vec4<f32>` so the storage layout keeps
+// `SpriteInstance`'s packed 48-byte `#[repr(C)]` stride (a vec4 would be
+// 16-byte aligned and pad the struct to 64 bytes).
+struct SpriteInstanceGPU {
+    position: vec2<f32>,
+    tex_rect: array<f32, 4>,
+    layer: u32,
+    alpha: f32,
+    tint: array<f32, 4>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: AccumulateParams;
+
+@group(0) @binding(1)
+var<storage, read> instances: array<SpriteInstanceGPU>;
+
+@group(0) @binding(2)
+var<storage, read_write> counts: array<atomic<u32>>;
+
+// Matches `data::coordinate_mapper::INVALID_MAP_ID_FLAG`, the sentinel
+// position written for off-route/invalid-map-id coordinates.
+const INVALID_COORD: f32 = 117117.0;
+
+@compute @workgroup_size(64)
+fn accumulate(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let idx = global_id.x;
+    if (idx >= params.sprite_count) {
+        return;
+    }
+
+    let inst = instances[idx];
+    if (inst.position.x == INVALID_COORD && inst.position.y == INVALID_COORD) {
+        return;
+    }
+
+    // Quad's top-left is `inst.position`; use its center (see
+    // `pipeline::VS_MAIN_SNIPPET`'s `sprite_size`) as the sample point.
+    let sprite_center = inst.position + vec2<f32>(8.0, 8.0) - params.camera_offset;
+    if (sprite_center.x < 0.0 || sprite_center.y < 0.0) {
+        return;
+    }
+
+    let downscale_width = (params.canvas_width + params.downscale - 1u) / params.downscale;
+    let downscale_height = (params.canvas_height + params.downscale - 1u) / params.downscale;
+    let px = u32(sprite_center.x) / params.downscale;
+    let py = u32(sprite_center.y) / params.downscale;
+    if (px >= downscale_width || py >= downscale_height) {
+        return;
+    }
+
+    atomicAdd(&counts[py * downscale_width + px], 1u);
+}
+"#;
+
+const REDUCE_MAX_SHADER_SOURCE: &str = r#"
+struct ReduceParams {
+    pixel_count: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> reduce_params: ReduceParams;
+
+@group(0) @binding(1)
+var<storage, read> counts: array<u32>;
+
+@group(0) @binding(2)
+var<storage, read_write> max_count: array<atomic<u32>>;
+
+@compute @workgroup_size(64)
+fn reduce_max(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let idx = global_id.x;
+    if (idx >= reduce_params.pixel_count) {
+        return;
+    }
+    atomicMax(&max_count[0], counts[idx]);
+}
+"#;
+
+const COLORMAP_SHADER_SOURCE: &str = r#"
+struct ColormapParams {
+    downscale_width: u32,
+    downscale_height: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> colormap_params: ColormapParams;
+
+@group(0) @binding(1)
+var<storage, read> counts: array<u32>;
+
+@group(0) @binding(2)
+var<storage, read> max_count: array<u32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+// Fullscreen triangle covering clip space with no vertex/index buffer -
+// the standard "big triangle" trick, derived purely from `vertex_index`.
+@vertex
+fn fullscreen_vs(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let x = f32(i32(vertex_index / 2u)) * 4.0 - 1.0;
+    let y = f32(i32(vertex_index % 2u)) * 4.0 - 1.0;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    return out;
+}
+
+// 9-stop approximation of matplotlib's viridis, linearly interpolated -
+// dense enough that banding isn't visible once spread across a log-scaled
+// count (see `colormap_fs`).
+fn viridis(t: f32) -> vec3<f32> {
+    let stops = array<vec3<f32>, 9>(
+        vec3<f32>(0.267004, 0.004874, 0.329415),
+        vec3<f32>(0.282623, 0.140926, 0.457517),
+        vec3<f32>(0.253935, 0.265254, 0.529983),
+        vec3<f32>(0.206756, 0.371758, 0.553117),
+        vec3<f32>(0.163625, 0.471133, 0.558148),
+        vec3<f32>(0.127568, 0.566949, 0.550556),
+        vec3<f32>(0.134692, 0.658636, 0.517649),
+        vec3<f32>(0.477504, 0.821444, 0.318195),
+        vec3<f32>(0.993248, 0.906157, 0.143936),
+    );
+    let scaled = clamp(t, 0.0, 1.0) * 8.0;
+    let lo = u32(floor(scaled));
+    let hi = min(lo + 1u, 8u);
+    return mix(stops[lo], stops[hi], scaled - f32(lo));
+}
+
+@fragment
+fn colormap_fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    let px = u32(in.clip_position.x);
+    let py = u32(in.clip_position.y);
+    if (px >= colormap_params.downscale_width || py >= colormap_params.downscale_height) {
+        discard;
+    }
+
+    let count = counts[py * colormap_params.downscale_width + px];
+    let max_c = max(max_count[0], 1u);
+    let normalized = log(1.0 + f32(count)) / log(1.0 + f32(max_c));
+    return vec4<f32>(viridis(normalized), 1.0);
+}
+"#;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct AccumulateParams {
+    canvas_width: u32,
+    canvas_height: u32,
+    downscale: u32,
+    sprite_count: u32,
+    camera_offset: [f32; 2],
+    _padding: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct ReduceParams {
+    pixel_count: u32,
+    _padding: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct ColormapParams {
+    downscale_width: u32,
+    downscale_height: u32,
+    _padding: [u32; 2],
+}
+
+pub struct HeatmapPipeline {
+    canvas_width: u32,
+    canvas_height: u32,
+    downscale: u32,
+    downscale_width: u32,
+    downscale_height: u32,
+    max_sprites: usize,
+
+    instance_buffer: wgpu::Buffer,
+    count_buffer: wgpu::Buffer,
+    max_count_buffer: wgpu::Buffer,
+    accumulate_params_buffer: wgpu::Buffer,
+
+    accumulate_pipeline: wgpu::ComputePipeline,
+    accumulate_bind_group_layout: wgpu::BindGroupLayout,
+    accumulate_bind_group: wgpu::BindGroup,
+
+    reduce_max_pipeline: wgpu::ComputePipeline,
+    reduce_max_bind_group: wgpu::BindGroup,
+
+    colormap_pipeline: wgpu::RenderPipeline,
+    colormap_bind_group: wgpu::BindGroup,
+}
+
+impl HeatmapPipeline {
+    /// `downscale` trades output resolution for count-buffer memory: an
+    /// 8192x8192 canvas at `downscale: 1` needs a ~256MB count buffer (one
+    /// `u32` per pixel), which is fine, but a caller rendering at that size
+    /// who wants to keep the heatmap buffer smaller can pass e.g. `4` to
+    /// shrink it 16x at the cost of a blockier-looking result.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        canvas_width: u32,
+        canvas_height: u32,
+        downscale: u32,
+        max_sprites: usize,
+        output_format: wgpu::TextureFormat,
+    ) -> Result<Self> {
+        let downscale = downscale.max(1);
+        let downscale_width = (canvas_width + downscale - 1) / downscale;
+        let downscale_height = (canvas_height + downscale - 1) / downscale;
+        let pixel_count = (downscale_width * downscale_height) as usize;
+
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Heatmap Instance Buffer"),
+            size: (std::mem::size_of::<SpriteInstance>() * max_sprites) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let count_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Heatmap Count Buffer"),
+            size: (pixel_count * std::mem::size_of::<u32>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let max_count_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Heatmap Max Count Buffer"),
+            size: std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let accumulate_params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Heatmap Accumulate Params Buffer"),
+            size: std::mem::size_of::<AccumulateParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let reduce_params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Heatmap Reduce Params Buffer"),
+            contents: bytemuck::bytes_of(&ReduceParams {
+                pixel_count: pixel_count as u32,
+                _padding: [0; 3],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let colormap_params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Heatmap Colormap Params Buffer"),
+            contents: bytemuck::bytes_of(&ColormapParams {
+                downscale_width,
+                downscale_height,
+                _padding: [0; 2],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let accumulate_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Heatmap Accumulate Shader"),
+            source: wgpu::ShaderSource::Wgsl(ACCUMULATE_SHADER_SOURCE.into()),
+        });
+        let accumulate_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Heatmap Accumulate Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let accumulate_bind_group = Self::create_accumulate_bind_group(
+            device,
+            &accumulate_bind_group_layout,
+            &accumulate_params_buffer,
+            &instance_buffer,
+            &count_buffer,
+        );
+        let accumulate_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Heatmap Accumulate Pipeline Layout"),
+            bind_group_layouts: &[&accumulate_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let accumulate_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Heatmap Accumulate Pipeline"),
+            layout: Some(&accumulate_pipeline_layout),
+            module: &accumulate_shader,
+            entry_point: Some("accumulate"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let reduce_max_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Heatmap Reduce Max Shader"),
+            source: wgpu::ShaderSource::Wgsl(REDUCE_MAX_SHADER_SOURCE.into()),
+        });
+        let reduce_max_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Heatmap Reduce Max Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let reduce_max_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Heatmap Reduce Max Bind Group"),
+            layout: &reduce_max_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: reduce_params_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: count_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: max_count_buffer.as_entire_binding() },
+            ],
+        });
+        let reduce_max_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Heatmap Reduce Max Pipeline Layout"),
+            bind_group_layouts: &[&reduce_max_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let reduce_max_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Heatmap Reduce Max Pipeline"),
+            layout: Some(&reduce_max_pipeline_layout),
+            module: &reduce_max_shader,
+            entry_point: Some("reduce_max"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let colormap_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Heatmap Colormap Shader"),
+            source: wgpu::ShaderSource::Wgsl(COLORMAP_SHADER_SOURCE.into()),
+        });
+        let colormap_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Heatmap Colormap Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let colormap_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Heatmap Colormap Bind Group"),
+            layout: &colormap_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: colormap_params_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: count_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: max_count_buffer.as_entire_binding() },
+            ],
+        });
+        let colormap_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Heatmap Colormap Pipeline Layout"),
+            bind_group_layouts: &[&colormap_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let colormap_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Heatmap Colormap Pipeline"),
+            layout: Some(&colormap_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &colormap_shader,
+                entry_point: Some("fullscreen_vs"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &colormap_shader,
+                entry_point: Some("colormap_fs"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: output_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        queue.write_buffer(&count_buffer, 0, &vec![0u8; count_buffer.size() as usize]);
+        queue.write_buffer(&max_count_buffer, 0, &[0u8; 4]);
+
+        Ok(Self {
+            canvas_width,
+            canvas_height,
+            downscale,
+            downscale_width,
+            downscale_height,
+            max_sprites,
+            instance_buffer,
+            count_buffer,
+            max_count_buffer,
+            accumulate_params_buffer,
+            accumulate_pipeline,
+            accumulate_bind_group_layout,
+            accumulate_bind_group,
+            reduce_max_pipeline,
+            reduce_max_bind_group,
+            colormap_pipeline,
+            colormap_bind_group,
+        })
+    }
+
+    fn create_accumulate_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        params_buffer: &wgpu::Buffer,
+        instance_buffer: &wgpu::Buffer,
+        count_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Heatmap Accumulate Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: params_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: instance_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: count_buffer.as_entire_binding() },
+            ],
+        })
+    }
+
+    /// The size of `count_buffer` (and the output `render_to_texture` draws
+    /// into) after `downscale` is applied to the canvas size.
+    pub fn downscaled_size(&self) -> (u32, u32) {
+        (self.downscale_width, self.downscale_height)
+    }
+
+    /// Grow `instance_buffer` (to double its capacity, or to `needed` if
+    /// that's larger; no ceiling) if `needed` instances wouldn't fit,
+    /// rebuilding `accumulate_bind_group` since it binds the old buffer.
+    fn ensure_capacity(&mut self, device: &wgpu::Device, needed: usize) {
+        if needed <= self.max_sprites {
+            return;
+        }
+
+        let new_capacity = (self.max_sprites * 2).max(needed);
+        log::info!(
+            "Growing heatmap instance buffer: {} -> {} sprites",
+            self.max_sprites,
+            new_capacity
+        );
+
+        self.instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Heatmap Instance Buffer"),
+            size: (std::mem::size_of::<SpriteInstance>() * new_capacity) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        self.accumulate_bind_group = Self::create_accumulate_bind_group(
+            device,
+            &self.accumulate_bind_group_layout,
+            &self.accumulate_params_buffer,
+            &self.instance_buffer,
+            &self.count_buffer,
+        );
+        self.max_sprites = new_capacity;
+    }
+
+    /// Clear the accumulated counts, for callers that want a fresh heatmap
+    /// rather than continuing to accumulate across calls (e.g. re-rendering
+    /// after scrubbing back to the start of a run).
+    pub fn reset(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.count_buffer, 0, &vec![0u8; self.count_buffer.size() as usize]);
+        queue.write_buffer(&self.max_count_buffer, 0, &[0u8; 4]);
+    }
+
+    /// `atomicAdd`s `sprites` into the count buffer; call once per chunk of
+    /// sprites to accumulate (run extraction streams these in pieces - see
+    /// `bin/extract_compact_runs`) before a single `render_to_texture`.
+    /// `camera_offset` matches the one `SpriteRenderer::render_layer` writes
+    /// to `Uniforms`, so the sampled pixel lines up with where the sprite
+    /// would actually be drawn.
+    pub fn accumulate(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sprites: &[SpriteInstance],
+        camera_offset: [f32; 2],
+    ) -> Result<()> {
+        if sprites.is_empty() {
+            return Ok(());
+        }
+
+        self.ensure_capacity(device, sprites.len());
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(sprites));
+
+        let params = AccumulateParams {
+            canvas_width: self.canvas_width,
+            canvas_height: self.canvas_height,
+            downscale: self.downscale,
+            sprite_count: sprites.len() as u32,
+            camera_offset,
+            _padding: [0.0; 2],
+        };
+        queue.write_buffer(&self.accumulate_params_buffer, 0, bytemuck::bytes_of(&params));
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Heatmap Accumulate Encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Heatmap Accumulate Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.accumulate_pipeline);
+            pass.set_bind_group(0, &self.accumulate_bind_group, &[]);
+            let workgroups = (sprites.len() as u32 + 63) / 64;
+            pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        Ok(())
+    }
+
+    /// Reduces the max count and colormaps the result into `output_view`,
+    /// which must be sized `downscaled_size()` (see that method) and created
+    /// with the `output_format` passed to `new`.
+    pub fn render_to_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue, output_view: &wgpu::TextureView) -> Result<()> {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Heatmap Colormap Encoder"),
+        });
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Heatmap Reduce Max Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.reduce_max_pipeline);
+            pass.set_bind_group(0, &self.reduce_max_bind_group, &[]);
+            let pixel_count = self.downscale_width * self.downscale_height;
+            let workgroups = (pixel_count + 63) / 64;
+            pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Heatmap Colormap Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&self.colormap_pipeline);
+            pass.set_bind_group(0, &self.colormap_bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        queue.submit(Some(encoder.finish()));
+
+        Ok(())
+    }
+}
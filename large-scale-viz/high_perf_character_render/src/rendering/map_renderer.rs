@@ -0,0 +1,351 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use wgpu;
+use wgpu::util::DeviceExt;
+
+use crate::rendering::pipeline::Vertex;
+
+// Full-canvas quad in clip space (Y-up), with texture v=0 at the top row of the
+// image so a map image lines up with the same top-left pixel convention
+// `CoordinateMapper::convert_coords` uses for sprite positions.
+const MAP_QUAD_VERTICES: &[Vertex] = &[
+    Vertex { position: [-1.0, 1.0], tex_coords: [0.0, 0.0] },
+    Vertex { position: [1.0, 1.0], tex_coords: [1.0, 0.0] },
+    Vertex { position: [1.0, -1.0], tex_coords: [1.0, 1.0] },
+    Vertex { position: [-1.0, -1.0], tex_coords: [0.0, 1.0] },
+];
+
+const MAP_QUAD_INDICES: &[u16] = &[
+    0, 1, 2,
+    0, 2, 3,
+];
+
+const SHADER_SOURCE: &str = r#"
+struct Uniforms {
+    // x = opacity, y = uv_scale (1/zoom), z/w = uv_offset (top-left of the
+    // zoomed-in sub-region, in 0..1 texture space)
+    opacity_scale: vec4<f32>,
+    // xyz = tint color the sampled map pixel is multiplied by, w unused
+    tint: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+@group(0) @binding(1)
+var map_texture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var map_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+@vertex
+fn vs_main(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(vertex.position, 0.0, 1.0);
+    out.tex_coords = vertex.tex_coords * uniforms.opacity_scale.y + uniforms.opacity_scale.zw;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(map_texture, map_sampler, in.tex_coords);
+    color.a = color.a * uniforms.opacity_scale.x;
+    color = vec4<f32>(color.rgb * uniforms.tint.rgb, color.a);
+    return color;
+}
+"#;
+
+/// Renders the background map layer behind sprites. Clears `gpu.render_texture_view`
+/// each frame and, if a map image was supplied, draws it as a single quad stretched
+/// across the full canvas (the canvas already represents the whole world in the
+/// same coordinate space `CoordinateMapper` maps sprite positions into).
+pub struct MapRenderer {
+    pipeline: Option<wgpu::RenderPipeline>,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl MapRenderer {
+    /// Load an optional map image and opacity/tint for the background layer. Pass
+    /// `map_image: None` to just clear the canvas with no background drawn.
+    ///
+    /// `zoom` crops the map to a `1/zoom`-sized sub-region centered on `center`
+    /// (in 0..1 texture-space, `[0.5, 0.5]` being the middle of the map) instead
+    /// of stretching the whole map across the canvas; `zoom <= 1.0` frames the
+    /// whole map. `tint` multiplies the sampled map color, e.g. `[0.5, 0.5, 0.5]`
+    /// to dim it so sprite trails read clearly on top.
+    pub fn new<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_format: wgpu::TextureFormat,
+        map_image: Option<P>,
+        opacity: f32,
+        tint: [f32; 3],
+        zoom: f32,
+        center: [f32; 2],
+        sample_count: u32,
+    ) -> Result<Self> {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Map Vertex Buffer"),
+            contents: bytemuck::cast_slice(MAP_QUAD_VERTICES),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Map Index Buffer"),
+            contents: bytemuck::cast_slice(MAP_QUAD_INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let Some(map_image) = map_image else {
+            return Ok(Self {
+                pipeline: None,
+                vertex_buffer,
+                index_buffer,
+                bind_group: None,
+            });
+        };
+
+        let img = image::open(map_image.as_ref())
+            .context("Failed to open map image")?
+            .to_rgba8();
+        let dimensions = img.dimensions();
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Map Texture"),
+            size: wgpu::Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &img,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * dimensions.0),
+                rows_per_image: Some(dimensions.1),
+            },
+            wgpu::Extent3d {
+                width: dimensions.0,
+                height: dimensions.1,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Map Shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER_SOURCE.into()),
+        });
+
+        let uv_scale = 1.0 / zoom.max(1.0);
+        let uv_offset = [
+            (center[0] - 0.5 * uv_scale).clamp(0.0, 1.0 - uv_scale),
+            (center[1] - 0.5 * uv_scale).clamp(0.0, 1.0 - uv_scale),
+        ];
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Map Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[
+                opacity, uv_scale, uv_offset[0], uv_offset[1],
+                tint[0], tint[1], tint[2], 0.0,
+            ]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Map Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Map Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Map Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Map Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: texture_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        log::info!(
+            "Loaded map background: {}x{} from {:?} (opacity {:.2}, zoom {:.2}, center [{:.2}, {:.2}])",
+            dimensions.0,
+            dimensions.1,
+            map_image.as_ref(),
+            opacity,
+            zoom,
+            center[0],
+            center[1]
+        );
+
+        Ok(Self {
+            pipeline: Some(pipeline),
+            vertex_buffer,
+            index_buffer,
+            bind_group: Some(bind_group),
+        })
+    }
+
+    /// Clear `target` and draw the map background, if one was loaded. This must run
+    /// before `SpriteRenderer::render` each frame, which should then be called with
+    /// `clear: false` so sprites composite on top instead of wiping the background.
+    /// `resolve_target` should be `Some(&gpu.render_texture_view)` when `target` is a
+    /// multisampled view (see `GpuContext::color_attachment_target`), or `None` for a
+    /// single-sample target.
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+    ) -> Result<()> {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Map Render Encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Map Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            if let (Some(pipeline), Some(bind_group)) = (&self.pipeline, &self.bind_group) {
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(0, bind_group, &[]);
+                render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                render_pass.draw_indexed(0..MAP_QUAD_INDICES.len() as u32, 0, 0..1);
+            }
+        }
+
+        queue.submit(Some(encoder.finish()));
+
+        Ok(())
+    }
+}
@@ -1,9 +1,17 @@
 pub mod gpu_context;
+pub mod heatmap_pipeline;
+pub mod map_renderer;
 pub mod pipeline;
+pub mod shader_preprocessor;
+pub mod sprite_animator;
 pub mod sprite_renderer;
 pub mod texture_atlas;
 
-pub use gpu_context::GpuContext;
-pub use pipeline::{SpritePipeline, SpriteInstance, Vertex};
-pub use sprite_renderer::SpriteRenderer;
-pub use texture_atlas::TextureAtlas;
+pub use gpu_context::{GpuContext, PendingPixelReadback, PIPELINE_DEPTH};
+pub use heatmap_pipeline::HeatmapPipeline;
+pub use map_renderer::MapRenderer;
+pub use pipeline::{default_shader_config, SpriteInstance, SpritePipeline, Vertex, HEATMAP_DEBUG_DEFINE};
+pub use shader_preprocessor::ShaderConfig;
+pub use sprite_animator::SpriteAnimator;
+pub use sprite_renderer::{RenderLayer, SpriteRenderer};
+pub use texture_atlas::{SheetRange, SpriteSheetManifest, TextureAtlas};
@@ -1,3 +1,4 @@
+use crate::rendering::shader_preprocessor::ShaderConfig;
 use anyhow::Result;
 use bytemuck::{Pod, Zeroable};
 use wgpu;
@@ -29,12 +30,18 @@ impl Vertex {
 pub struct SpriteInstance {
     pub position: [f32; 2],    // Position in pixels
     pub tex_rect: [f32; 4],    // Texture coordinates (u_min, v_min, u_max, v_max)
+    pub layer: u32,            // Which texture array layer (sprite sheet) to sample
+    pub alpha: f32,            // Opacity, 0.0 (invisible) to 1.0 (opaque) - used for warp fades
+    pub tint: [f32; 4],        // Multiplied into the sampled color - [1.0, 1.0, 1.0, 1.0] for no-op
 }
 
 impl SpriteInstance {
-    const ATTRIBS: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+    const ATTRIBS: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
         2 => Float32x2,
         3 => Float32x4,
+        4 => Uint32,
+        5 => Float32,
+        6 => Float32x4,
     ];
 
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
@@ -46,21 +53,46 @@ impl SpriteInstance {
     }
 }
 
-// Shader source code
-const SHADER_SOURCE: &str = r#"
+// Shader source, split into named snippets spliced together by `ShaderConfig`
+// (see `rendering::shader_preprocessor`) instead of one monolithic string, so
+// alternate visual modes can `#ifdef` their way into the same source rather
+// than forking it.
+const ROOT_SHADER_SOURCE: &str = r#"
+#include "uniforms"
+#include "io"
+#include "vs_main"
+#include "fs_main"
+"#;
+
+const UNIFORMS_SNIPPET: &str = r#"
 struct Uniforms {
     canvas_size: vec2<f32>,
+    // World-space offset subtracted from every sprite position before the
+    // canvas-space conversion below, scaled per render layer by that layer's
+    // parallax coefficient (see `rendering::sprite_renderer::RenderLayer`)
+    // so far-off layers drift slower than the camera and near layers track it.
+    camera_offset: vec2<f32>,
+    // Explicit mip level `fs_main` samples the sprite atlas at (see
+    // `SpritePipeline::new`'s `mip_filtering` flag and `RenderLayer::lod`).
+    // 0.0 is the full-res base level - the only level a non-mipmapped atlas
+    // has, so this is a no-op unless the atlas was built with mipmaps.
+    lod: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
 };
 
 @group(0) @binding(0)
 var<uniform> uniforms: Uniforms;
 
 @group(0) @binding(1)
-var texture: texture_2d<f32>;
+var texture: texture_2d_array<f32>;
 
 @group(0) @binding(2)
 var tex_sampler: sampler;
+"#;
 
+const IO_SNIPPET: &str = r#"
 struct VertexInput {
     @location(0) position: vec2<f32>,
     @location(1) tex_coords: vec2<f32>,
@@ -69,13 +101,21 @@ struct VertexInput {
 struct InstanceInput {
     @location(2) sprite_position: vec2<f32>,
     @location(3) tex_rect: vec4<f32>,
+    @location(4) layer: u32,
+    @location(5) alpha: f32,
+    @location(6) tint: vec4<f32>,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) layer: u32,
+    @location(2) alpha: f32,
+    @location(3) tint: vec4<f32>,
 };
+"#;
 
+const VS_MAIN_SNIPPET: &str = r#"
 @vertex
 fn vs_main(
     vertex: VertexInput,
@@ -87,8 +127,8 @@ fn vs_main(
     let sprite_size = vec2<f32>(16.0, 16.0);
     let scaled_pos = vertex.position * sprite_size;
 
-    // Add instance position
-    let world_pos = scaled_pos + instance.sprite_position;
+    // Add instance position, shifted by this layer's parallax-scaled camera offset
+    let world_pos = scaled_pos + instance.sprite_position - uniforms.camera_offset;
 
     // Convert to NDC (Normalized Device Coordinates)
     // Map [0, canvas_size] to [-1, 1]
@@ -101,23 +141,60 @@ fn vs_main(
     let u = mix(instance.tex_rect.x, instance.tex_rect.z, vertex.tex_coords.x);
     let v = mix(instance.tex_rect.y, instance.tex_rect.w, vertex.tex_coords.y);
     out.tex_coords = vec2<f32>(u, v);
+    out.layer = instance.layer;
+    out.alpha = instance.alpha;
+    out.tint = instance.tint;
 
     return out;
 }
+"#;
 
+const FS_MAIN_SNIPPET: &str = r#"
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let color = textureSample(texture, tex_sampler, in.tex_coords);
+    // Explicit-LOD sample (rather than `textureSample`'s derivative-based
+    // auto LOD) so a single `uniforms.lod` drives the whole frame's mip
+    // level uniformly - works with both the default NonFiltering sampler
+    // (lod always 0.0, identical to the old `textureSample` call) and a
+    // Filtering/trilinear one when `SpritePipeline::new`'s `mip_filtering`
+    // flag is set.
+    var color = textureSampleLevel(texture, tex_sampler, in.tex_coords, i32(in.layer), uniforms.lod);
+    color.a = color.a * in.alpha;
+    color = color * in.tint;
 
     // Discard fully transparent pixels
     if (color.a < 0.01) {
         discard;
     }
 
+#ifdef HEATMAP_DEBUG
+    // Debug visualization: remap luminance to a red(low)->green(high) heat
+    // gradient instead of the sampled sprite color, useful for spotting
+    // overdraw/density without re-authoring sprite sheets.
+    let luminance = dot(color.rgb, vec3<f32>(0.299, 0.587, 0.114));
+    color = vec4<f32>(1.0 - luminance, luminance, 0.0, color.a);
+#endif
+
     return color;
 }
 "#;
 
+/// `HEATMAP_DEBUG` toggles `fs_main`'s density-heatmap debug path (see
+/// `FS_MAIN_SNIPPET`) in place of sampling the sprite sheet directly.
+pub const HEATMAP_DEBUG_DEFINE: &str = "HEATMAP_DEBUG";
+
+/// Builds the default `ShaderConfig` registry `SpritePipeline` assembles its
+/// shader from - the snippets `ROOT_SHADER_SOURCE` expects to be able to
+/// `#include`. Callers add `with_define(...)` on top to opt into a visual
+/// mode (e.g. `HEATMAP_DEBUG_DEFINE`) without forking any of these strings.
+pub fn default_shader_config() -> ShaderConfig {
+    ShaderConfig::new()
+        .with_snippet("uniforms", UNIFORMS_SNIPPET)
+        .with_snippet("io", IO_SNIPPET)
+        .with_snippet("vs_main", VS_MAIN_SNIPPET)
+        .with_snippet("fs_main", FS_MAIN_SNIPPET)
+}
+
 pub struct SpritePipeline {
     pub pipeline: wgpu::RenderPipeline,
     pub bind_group_layout: wgpu::BindGroupLayout,
@@ -125,32 +202,50 @@ pub struct SpritePipeline {
 }
 
 impl SpritePipeline {
+    /// `shader_config` selects which visual mode to assemble (see
+    /// `default_shader_config`/`HEATMAP_DEBUG_DEFINE`) - pass
+    /// `default_shader_config()` for the plain sprite shader.
+    ///
+    /// `mip_filtering` switches the atlas texture/sampler bind group layout
+    /// entries from `NonFiltering`/non-filterable (the default, pixel-exact
+    /// full-res path, unchanged from before mipmaps existed) to
+    /// `Filtering`/filterable, which is required to bind a sampler with
+    /// `mipmap_filter: Linear` - pass `true` only when pairing with a
+    /// `TextureAtlas` built via `load_with_mipmaps`/`load_from_bytes_with_mipmaps`,
+    /// for trilinear-filtered downscaled/zoomed-out output.
     pub fn new(
         device: &wgpu::Device,
         texture_format: wgpu::TextureFormat,
         canvas_width: u32,
         canvas_height: u32,
+        sample_count: u32,
+        shader_config: &ShaderConfig,
+        mip_filtering: bool,
     ) -> Result<Self> {
+        let shader_source = shader_config.preprocess(ROOT_SHADER_SOURCE)?;
+
         // Create shader module
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Sprite Shader"),
-            source: wgpu::ShaderSource::Wgsl(SHADER_SOURCE.into()),
+            source: wgpu::ShaderSource::Wgsl(shader_source.into()),
         });
 
-        // Create uniform buffer
-        let canvas_size = [canvas_width as f32, canvas_height as f32];
-        let canvas_size_bytes = bytemuck::cast_slice(&canvas_size);
+        // Create uniform buffer - canvas_size (vec2) + camera_offset (vec2) +
+        // lod (f32) + padding (3x f32) = 32 bytes, two uniform-alignment
+        // chunks so no extra padding beyond the explicit one is needed.
         let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Uniform Buffer"),
-            size: 16, // vec2<f32> with padding
+            size: 32,
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
-        // Write canvas size to uniform buffer
-        // Note: need to pad to 16 bytes for uniform alignment
-        let mut uniform_data = [0u8; 16];
-        uniform_data[0..8].copy_from_slice(canvas_size_bytes);
+        let sample_type = wgpu::TextureSampleType::Float { filterable: mip_filtering };
+        let sampler_binding_type = if mip_filtering {
+            wgpu::SamplerBindingType::Filtering
+        } else {
+            wgpu::SamplerBindingType::NonFiltering
+        };
 
         // Create bind group layout
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -170,8 +265,8 @@ impl SpritePipeline {
                     binding: 1,
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
-                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
                         multisampled: false,
                     },
                     count: None,
@@ -179,7 +274,7 @@ impl SpritePipeline {
                 wgpu::BindGroupLayoutEntry {
                     binding: 2,
                     visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                    ty: wgpu::BindingType::Sampler(sampler_binding_type),
                     count: None,
                 },
             ],
@@ -223,7 +318,7 @@ impl SpritePipeline {
             },
             depth_stencil: None,
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
@@ -247,12 +342,7 @@ impl SpritePipeline {
         canvas_width: u32,
         canvas_height: u32,
     ) -> wgpu::BindGroup {
-        // Update uniform buffer with canvas size
-        let mut uniform_data = [0u8; 16];
-        let canvas_size = [canvas_width as f32, canvas_height as f32];
-        let canvas_size_bytes = bytemuck::cast_slice(&canvas_size);
-        uniform_data[0..8].copy_from_slice(canvas_size_bytes);
-        queue.write_buffer(&self.uniform_buffer, 0, &uniform_data);
+        self.write_uniforms(queue, canvas_width, canvas_height, [0.0, 0.0], 0.0);
 
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Sprite Bind Group"),
@@ -273,6 +363,27 @@ impl SpritePipeline {
             ],
         })
     }
+
+    /// Update the shared uniform buffer's canvas size, camera offset, and
+    /// sample LOD. Called once per render layer (with that layer's
+    /// parallax-scaled `camera_offset` and its own `lod`) since all layers
+    /// draw through the same pipeline/bind group layout but at different
+    /// effective camera positions and, for zoomed-out/parallax layers,
+    /// potentially different mip levels.
+    pub fn write_uniforms(
+        &self,
+        queue: &wgpu::Queue,
+        canvas_width: u32,
+        canvas_height: u32,
+        camera_offset: [f32; 2],
+        lod: f32,
+    ) {
+        let mut uniform_data = [0u8; 32];
+        uniform_data[0..8].copy_from_slice(bytemuck::cast_slice(&[canvas_width as f32, canvas_height as f32]));
+        uniform_data[8..16].copy_from_slice(bytemuck::cast_slice(&camera_offset));
+        uniform_data[16..20].copy_from_slice(bytemuck::cast_slice(&[lod]));
+        queue.write_buffer(&self.uniform_buffer, 0, &uniform_data);
+    }
 }
 
 // Quad vertices (two triangles forming a square)
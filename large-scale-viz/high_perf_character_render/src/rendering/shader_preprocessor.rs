@@ -0,0 +1,161 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::{HashMap, HashSet};
+
+/// A small WGSL preprocessor so `SpritePipeline` (and future pipelines) can
+/// assemble a shader from reusable named snippets instead of maintaining one
+/// monolithic source string per visual mode.
+///
+/// Supports two directives, each on its own line:
+/// - `#include "name"` - recursively splices in the snippet registered under
+///   `name` (cycle-checked).
+/// - `#ifdef FLAG` / `#endif` - keeps the enclosed lines only if `FLAG` is in
+///   `defines`, otherwise strips them (directive lines are always removed).
+#[derive(Debug, Clone, Default)]
+pub struct ShaderConfig {
+    snippets: HashMap<String, String>,
+    defines: HashSet<String>,
+}
+
+impl ShaderConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a named snippet `#include "name"` can pull in.
+    pub fn with_snippet(mut self, name: &str, source: &str) -> Self {
+        self.snippets.insert(name.to_string(), source.to_string());
+        self
+    }
+
+    /// Enable a `#ifdef` flag.
+    pub fn with_define(mut self, flag: &str) -> Self {
+        self.defines.insert(flag.to_string());
+        self
+    }
+
+    /// Expand `#include`s in `root` against the registered snippets, then
+    /// strip `#ifdef`/`#endif` blocks whose flag isn't enabled, returning
+    /// the final WGSL source ready for `create_shader_module`.
+    pub fn preprocess(&self, root: &str) -> Result<String> {
+        let mut include_stack = Vec::new();
+        let expanded = self.expand_includes(root, &mut include_stack)?;
+        Ok(strip_ifdefs(&expanded, &self.defines))
+    }
+
+    fn expand_includes(&self, source: &str, include_stack: &mut Vec<String>) -> Result<String> {
+        let mut out = String::with_capacity(source.len());
+
+        for line in source.lines() {
+            match line.trim_start().strip_prefix("#include ") {
+                Some(rest) => {
+                    let name = rest.trim().trim_matches('"');
+
+                    if include_stack.iter().any(|included| included == name) {
+                        bail!(
+                            "Cyclic #include detected: {} -> {}",
+                            include_stack.join(" -> "),
+                            name
+                        );
+                    }
+
+                    let snippet = self
+                        .snippets
+                        .get(name)
+                        .ok_or_else(|| anyhow!("Unknown shader include {:?}", name))?;
+
+                    include_stack.push(name.to_string());
+                    out.push_str(&self.expand_includes(snippet, include_stack)?);
+                    include_stack.pop();
+                }
+                None => {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+/// Strips `#ifdef FLAG` / `#endif` blocks whose `FLAG` isn't in `defines`.
+/// Doesn't track which flag opened a skipped block - a bare depth counter is
+/// enough since a block is only ever fully kept or fully dropped.
+fn strip_ifdefs(source: &str, defines: &HashSet<String>) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut skip_depth = 0usize;
+
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+
+        if let Some(flag) = trimmed.strip_prefix("#ifdef ") {
+            if skip_depth > 0 || !defines.contains(flag.trim()) {
+                skip_depth += 1;
+            }
+            continue;
+        }
+
+        if trimmed.starts_with("#endif") {
+            skip_depth = skip_depth.saturating_sub(1);
+            continue;
+        }
+
+        if skip_depth == 0 {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_include_expansion() {
+        let config = ShaderConfig::new().with_snippet("greeting", "hello");
+        let result = config.preprocess("#include \"greeting\"\nworld").unwrap();
+        assert_eq!(result.trim(), "hello\nworld");
+    }
+
+    #[test]
+    fn test_nested_include_expansion() {
+        let config = ShaderConfig::new()
+            .with_snippet("inner", "inner body")
+            .with_snippet("outer", "#include \"inner\"");
+        let result = config.preprocess("#include \"outer\"").unwrap();
+        assert_eq!(result.trim(), "inner body");
+    }
+
+    #[test]
+    fn test_cyclic_include_detected() {
+        let config = ShaderConfig::new()
+            .with_snippet("a", "#include \"b\"")
+            .with_snippet("b", "#include \"a\"");
+        assert!(config.preprocess("#include \"a\"").is_err());
+    }
+
+    #[test]
+    fn test_unknown_include_errors() {
+        let config = ShaderConfig::new();
+        assert!(config.preprocess("#include \"missing\"").is_err());
+    }
+
+    #[test]
+    fn test_ifdef_strips_disabled_block() {
+        let config = ShaderConfig::new();
+        let source = "before\n#ifdef FEATURE\nmiddle\n#endif\nafter";
+        let result = config.preprocess(source).unwrap();
+        assert_eq!(result.trim(), "before\nafter");
+    }
+
+    #[test]
+    fn test_ifdef_keeps_enabled_block() {
+        let config = ShaderConfig::new().with_define("FEATURE");
+        let source = "before\n#ifdef FEATURE\nmiddle\n#endif\nafter";
+        let result = config.preprocess(source).unwrap();
+        assert_eq!(result.trim(), "before\nmiddle\nafter");
+    }
+}
@@ -0,0 +1,65 @@
+use crate::data::Direction;
+use crate::rendering::texture_atlas::TextureAtlas;
+
+/// Per-sprite walk-cycle automaton driven directly by raw per-step coordinate
+/// deltas, rather than `AnimationInterpolator`'s time-based interpolation
+/// between parquet/JSONL frames. Meant for callers that only have "where did
+/// this sprite move since the last step" (e.g. a quick preview tool, or a
+/// live streaming feed with no fixed `interval_ms`): facing direction comes
+/// from the sign of the movement vector, and the walk-cycle frame advances
+/// once every `distance_per_frame` pixels of accumulated movement, holding
+/// the idle pose while the sprite is stationary - the same automaton shape
+/// sprite/ship engines use to drive facing + gait from raw position deltas.
+pub struct SpriteAnimator {
+    direction: Direction,
+    frame_count: u32,
+    distance_per_frame: f32,
+    accumulated_distance: f32,
+    frame_index: u32,
+}
+
+impl SpriteAnimator {
+    /// `frame_count` is the walk cycle's frame span (2-4 is typical for this
+    /// sprite sheet); `distance_per_frame` is how many pixels of accumulated
+    /// movement the sprite needs to cover before the cycle steps one frame.
+    pub fn new(frame_count: u32, distance_per_frame: f32) -> Self {
+        Self {
+            direction: Direction::Down,
+            frame_count: frame_count.max(1),
+            distance_per_frame: distance_per_frame.max(1.0),
+            accumulated_distance: 0.0,
+            frame_index: 0,
+        }
+    }
+
+    /// Advance the automaton by one step given the pixel delta since the last
+    /// step. Facing only changes while actually moving, so a sprite that
+    /// stops keeps facing the way it was last walking.
+    pub fn step(&mut self, dx: f32, dy: f32) {
+        let distance = dx.hypot(dy);
+
+        if distance > 0.0 {
+            self.direction = Direction::from_movement(dx, dy);
+            self.accumulated_distance += distance;
+            let frames_advanced = (self.accumulated_distance / self.distance_per_frame) as u32;
+            self.frame_index = frames_advanced % self.frame_count;
+        } else {
+            // Stationary: reset to the idle pose instead of freezing mid-stride.
+            self.accumulated_distance = 0.0;
+            self.frame_index = 0;
+        }
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn frame_index(&self) -> u32 {
+        self.frame_index
+    }
+
+    /// Sample the atlas tex-rect for the automaton's current pose.
+    pub fn tex_coords(&self, atlas: &TextureAtlas, layer: u32, sprite_id: u8) -> [f32; 4] {
+        atlas.get_sprite_tex_coords(layer, sprite_id, self.direction, self.frame_index)
+    }
+}
@@ -1,32 +1,115 @@
 use crate::data::SpriteInstance as DataSpriteInstance;
-use crate::rendering::pipeline::{SpritePipeline, SpriteInstance, Vertex, QUAD_INDICES, QUAD_VERTICES};
+use crate::rendering::pipeline::{
+    default_shader_config, SpriteInstance, SpritePipeline, Vertex, QUAD_INDICES, QUAD_VERTICES,
+};
+use crate::rendering::shader_preprocessor::ShaderConfig;
 use crate::rendering::texture_atlas::TextureAtlas;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use wgpu;
 
+/// One instanced-sprite batch to draw into a frame, rendered in its own pass
+/// with its own camera offset so it can sit at a different apparent "depth"
+/// than the rest of the scene (starfield-style parallax compositing).
+pub struct RenderLayer {
+    /// Sprites to draw this frame for this layer.
+    pub sprites: Vec<SpriteInstance>,
+    /// Index into the `texture_atlases` slice `SpriteRenderer::new` was
+    /// built with - which atlas this layer samples from.
+    pub atlas_index: usize,
+    /// Scales how much of the camera's offset this layer actually moves by:
+    /// `0.0` stays fixed regardless of camera (e.g. a UI overlay), `1.0`
+    /// tracks the camera exactly (the sprite/run layer), and values in
+    /// between drift slower, reading as "further away" the closer to `0.0`.
+    pub parallax: f32,
+    /// Draw order - layers are drawn back-to-front (ascending `z_order`),
+    /// so a lower `z_order` sits behind higher ones.
+    pub z_order: i32,
+    /// Explicit mip level to sample the atlas at (see `pipeline::Uniforms::lod`).
+    /// `0.0` is pixel-exact full resolution, the only sensible value unless
+    /// this layer's atlas was built with `TextureAtlas::load_with_mipmaps`/
+    /// `load_from_bytes_with_mipmaps` and `SpriteRenderer` was constructed
+    /// with `mip_filtering: true` - a parallax background layer zoomed out
+    /// far enough to alias is the main use case for a non-zero value here.
+    pub lod: f32,
+}
+
+struct AtlasBinding {
+    bind_group: wgpu::BindGroup,
+}
+
+/// Hard ceiling on how large `instance_buffer` will ever grow, regardless
+/// of how many sprites a frame asks to draw - keeps a runaway frame (or a
+/// caller with no sensible bound of its own) from driving unbounded GPU
+/// allocation. Override per-instance with `set_max_sprites_ceiling`.
+const DEFAULT_MAX_SPRITES_CEILING: usize = 1_000_000;
+
 pub struct SpriteRenderer {
     pipeline: SpritePipeline,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     instance_buffer: wgpu::Buffer,
-    bind_group: wgpu::BindGroup,
+    atlases: Vec<AtlasBinding>,
     max_sprites: usize,
+    max_sprites_ceiling: usize,
+    canvas_width: u32,
+    canvas_height: u32,
+    /// `Some` only when `device` was created with `wgpu::Features::TIMESTAMP_QUERY`
+    /// (see `GpuContext::new_with_backend` for the analogous adapter-feature
+    /// check) - `render_layer` no-ops its timing writes when this is `None`.
+    timestamp_query_set: Option<wgpu::QuerySet>,
+    timestamp_resolve_buffer: Option<wgpu::Buffer>,
+    timestamp_readback_buffer: Option<wgpu::Buffer>,
+    timestamp_period_ns: f32,
 }
 
 impl SpriteRenderer {
     pub fn new(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        texture_atlas: &TextureAtlas,
+        texture_atlases: &[&TextureAtlas],
+        canvas_width: u32,
+        canvas_height: u32,
+        max_sprites: usize,
+        sample_count: u32,
+    ) -> Result<Self> {
+        Self::with_shader_config(
+            device,
+            queue,
+            texture_atlases,
+            canvas_width,
+            canvas_height,
+            max_sprites,
+            sample_count,
+            &default_shader_config(),
+            false,
+        )
+    }
+
+    /// Like `new`, but lets the caller select a non-default visual mode
+    /// (see `pipeline::HEATMAP_DEBUG_DEFINE`) by passing a `ShaderConfig`
+    /// with extra `#ifdef` flags defined on top of the default registry, and
+    /// opt into a filterable/mipmapped atlas binding via `mip_filtering` (see
+    /// `SpritePipeline::new`) - pass `false` unless pairing with a
+    /// `TextureAtlas` built via `load_with_mipmaps`/`load_from_bytes_with_mipmaps`.
+    pub fn with_shader_config(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_atlases: &[&TextureAtlas],
         canvas_width: u32,
         canvas_height: u32,
         max_sprites: usize,
+        sample_count: u32,
+        shader_config: &ShaderConfig,
+        mip_filtering: bool,
     ) -> Result<Self> {
         let pipeline = SpritePipeline::new(
             device,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             canvas_width,
             canvas_height,
+            sample_count,
+            shader_config,
+            mip_filtering,
         )?;
 
         // Create vertex buffer
@@ -47,7 +130,9 @@ impl SpriteRenderer {
         });
         queue.write_buffer(&index_buffer, 0, bytemuck::cast_slice(QUAD_INDICES));
 
-        // Create instance buffer (large enough for max_sprites)
+        // Create instance buffer (large enough for max_sprites) - shared
+        // across layers, since layers draw one at a time in their own pass
+        // rather than simultaneously.
         let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Instance Buffer"),
             size: (std::mem::size_of::<SpriteInstance>() * max_sprites) as u64,
@@ -55,48 +140,134 @@ impl SpriteRenderer {
             mapped_at_creation: false,
         });
 
-        // Create bind group
-        let bind_group = pipeline.create_bind_group(
-            device,
-            queue,
-            &texture_atlas.view,
-            &texture_atlas.sampler,
-            canvas_width,
-            canvas_height,
-        );
+        let atlases = texture_atlases
+            .iter()
+            .map(|atlas| AtlasBinding {
+                bind_group: pipeline.create_bind_group(
+                    device,
+                    queue,
+                    &atlas.view,
+                    &atlas.sampler,
+                    canvas_width,
+                    canvas_height,
+                ),
+            })
+            .collect();
+
+        let (timestamp_query_set, timestamp_resolve_buffer, timestamp_readback_buffer) =
+            if device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+                let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("Sprite Layer Timestamp Query Set"),
+                    ty: wgpu::QueryType::Timestamp,
+                    count: 2,
+                });
+                let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Sprite Layer Timestamp Resolve Buffer"),
+                    size: 2 * std::mem::size_of::<u64>() as u64,
+                    usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                });
+                let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Sprite Layer Timestamp Readback Buffer"),
+                    size: 2 * std::mem::size_of::<u64>() as u64,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                });
+                (Some(query_set), Some(resolve_buffer), Some(readback_buffer))
+            } else {
+                log::warn!(
+                    "GPU does not support TIMESTAMP_QUERY; SpriteRenderer::last_frame_gpu_time will always return None"
+                );
+                (None, None, None)
+            };
+        let timestamp_period_ns = queue.get_timestamp_period();
 
         Ok(Self {
             pipeline,
             vertex_buffer,
             index_buffer,
             instance_buffer,
-            bind_group,
+            atlases,
             max_sprites,
+            max_sprites_ceiling: DEFAULT_MAX_SPRITES_CEILING,
+            canvas_width,
+            canvas_height,
+            timestamp_query_set,
+            timestamp_resolve_buffer,
+            timestamp_readback_buffer,
+            timestamp_period_ns,
         })
     }
 
-    /// Render a batch of sprites
-    pub fn render(
-        &self,
+    /// Override the default hard ceiling (`DEFAULT_MAX_SPRITES_CEILING`) on
+    /// how large `ensure_capacity` will ever grow `instance_buffer`.
+    pub fn set_max_sprites_ceiling(&mut self, ceiling: usize) {
+        self.max_sprites_ceiling = ceiling;
+    }
+
+    /// Grow `instance_buffer` to the next power-of-two size that fits
+    /// `needed` instances, capped at `max_sprites_ceiling`, if `needed`
+    /// wouldn't fit in the current allocation. A frame whose `needed`
+    /// exceeds the ceiling still gets a buffer grown up to the ceiling -
+    /// `render_layer` is responsible for truncating the sprites it writes
+    /// to whatever capacity actually results.
+    fn ensure_capacity(&mut self, device: &wgpu::Device, needed: usize) {
+        if needed <= self.max_sprites {
+            return;
+        }
+
+        let new_capacity = needed.min(self.max_sprites_ceiling).next_power_of_two().min(self.max_sprites_ceiling);
+        if new_capacity <= self.max_sprites {
+            return;
+        }
+
+        log::info!(
+            "Growing sprite instance buffer: {} -> {} sprites",
+            self.max_sprites,
+            new_capacity
+        );
+
+        self.instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (std::mem::size_of::<SpriteInstance>() * new_capacity) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        self.max_sprites = new_capacity;
+    }
+
+    /// Render one layer's sprites into `target` in their own render pass.
+    /// `load_op` controls whether this pass clears the target first (the
+    /// first layer of a frame) or composites on top of whatever's already
+    /// there (every subsequent layer, and `MapRenderer`'s background drawn
+    /// ahead of this call).
+    fn render_layer(
+        &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         target: &wgpu::TextureView,
-        sprites: &[SpriteInstance],
+        resolve_target: Option<&wgpu::TextureView>,
+        layer: &RenderLayer,
+        camera: [f32; 2],
+        load_op: wgpu::LoadOp<wgpu::Color>,
     ) -> Result<()> {
-        if sprites.is_empty() {
-            // Still need to clear the render target
+        let camera_offset = [camera[0] * layer.parallax, camera[1] * layer.parallax];
+        self.pipeline
+            .write_uniforms(queue, self.canvas_width, self.canvas_height, camera_offset, layer.lod);
+
+        if layer.sprites.is_empty() {
             let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Clear Encoder"),
+                label: Some("Sprite Layer Clear Encoder"),
             });
 
             {
                 let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("Clear Pass"),
+                    label: Some("Sprite Layer Clear Pass"),
                     color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                         view: target,
-                        resolve_target: None,
+                        resolve_target,
                         ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            load: load_op,
                             store: wgpu::StoreOp::Store,
                         },
                     })],
@@ -110,46 +281,198 @@ impl SpriteRenderer {
             return Ok(());
         }
 
-        let sprite_count = sprites.len().min(self.max_sprites);
+        self.ensure_capacity(device, layer.sprites.len());
 
-        // Update instance buffer
-        queue.write_buffer(
-            &self.instance_buffer,
-            0,
-            bytemuck::cast_slice(&sprites[..sprite_count]),
-        );
+        // ensure_capacity caps growth at max_sprites_ceiling, so a layer
+        // requesting more sprites than the ceiling still can't fit - rather
+        // than overflow instance_buffer, draw only as many as fit and say so.
+        let draw_count = layer.sprites.len().min(self.max_sprites);
+        if draw_count < layer.sprites.len() {
+            log::warn!(
+                "Layer requested {} sprites but the instance buffer is capped at {} (max_sprites_ceiling) - dropping {}",
+                layer.sprites.len(),
+                self.max_sprites_ceiling,
+                layer.sprites.len() - draw_count
+            );
+        }
+        let sprites = &layer.sprites[..draw_count];
+
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(sprites));
+
+        let bind_group = &self.atlases[layer.atlas_index].bind_group;
 
-        // Create command encoder
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Sprite Render Encoder"),
+            label: Some("Sprite Layer Render Encoder"),
+        });
+
+        let timestamp_writes = self.timestamp_query_set.as_ref().map(|query_set| {
+            wgpu::RenderPassTimestampWrites {
+                query_set,
+                beginning_of_pass_write_index: Some(0),
+                end_of_pass_write_index: Some(1),
+            }
         });
 
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Sprite Render Pass"),
+                label: Some("Sprite Layer Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view: target,
-                    resolve_target: None,
+                    resolve_target,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        load: load_op,
                         store: wgpu::StoreOp::Store,
                     },
                 })],
                 depth_stencil_attachment: None,
-                timestamp_writes: None,
+                timestamp_writes,
                 occlusion_query_set: None,
             });
 
             render_pass.set_pipeline(&self.pipeline.pipeline);
-            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            render_pass.set_bind_group(0, bind_group, &[]);
             render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
             render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
             render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..QUAD_INDICES.len() as u32, 0, 0..sprite_count as u32);
+            render_pass.draw_indexed(0..QUAD_INDICES.len() as u32, 0, 0..sprites.len() as u32);
+        }
+
+        if let (Some(query_set), Some(resolve_buffer), Some(readback_buffer)) = (
+            &self.timestamp_query_set,
+            &self.timestamp_resolve_buffer,
+            &self.timestamp_readback_buffer,
+        ) {
+            encoder.resolve_query_set(query_set, 0..2, resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(
+                resolve_buffer,
+                0,
+                readback_buffer,
+                0,
+                2 * std::mem::size_of::<u64>() as u64,
+            );
         }
 
         queue.submit(Some(encoder.finish()));
 
         Ok(())
     }
+
+    /// Read back the GPU time spent in the most recent non-empty layer's
+    /// sprite render pass, in milliseconds, or `None` if the device doesn't
+    /// support `wgpu::Features::TIMESTAMP_QUERY` (see `GpuContext::read_gpu_frame_time_ms`
+    /// for the equivalent on the `GpuContext`-owned render path). Must be
+    /// called after the command buffer containing that pass has been
+    /// submitted via `render`/`render_layers`.
+    pub async fn last_frame_gpu_time(&self, device: &wgpu::Device) -> Result<Option<f32>> {
+        let Some(readback_buffer) = &self.timestamp_readback_buffer else {
+            return Ok(None);
+        };
+
+        let buffer_slice = readback_buffer.slice(..);
+        let (tx, rx) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            tx.send(result).ok();
+        });
+
+        device.poll(wgpu::Maintain::Wait);
+        rx.receive().await.context("Failed to map sprite layer timestamp readback buffer")??;
+
+        let data = buffer_slice.get_mapped_range();
+        let start = u64::from_le_bytes(data[0..8].try_into().unwrap());
+        let end = u64::from_le_bytes(data[8..16].try_into().unwrap());
+        drop(data);
+        readback_buffer.unmap();
+
+        let elapsed_ns = end.saturating_sub(start) as f32 * self.timestamp_period_ns;
+        Ok(Some(elapsed_ns / 1_000_000.0))
+    }
+
+    /// Render a batch of sprites. Pass `clear: false` to draw on top of a background
+    /// layer (e.g. `MapRenderer`) already rendered into `target` this frame, or
+    /// `true` to clear `target` to transparent first, as a standalone renderer would.
+    /// `resolve_target` should be `Some(&gpu.render_texture_view)` when `target` is a
+    /// multisampled view (see `GpuContext::color_attachment_target`), or `None` for a
+    /// single-sample target.
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+        sprites: &[SpriteInstance],
+        clear: bool,
+    ) -> Result<()> {
+        let layer = RenderLayer {
+            sprites: sprites.to_vec(),
+            atlas_index: 0,
+            parallax: 1.0,
+            z_order: 0,
+            lod: 0.0,
+        };
+        let load_op = if clear {
+            wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
+        } else {
+            wgpu::LoadOp::Load
+        };
+        self.render_layer(device, queue, target, resolve_target, &layer, [0.0, 0.0], load_op)
+    }
+
+    /// Render a set of ordered, parallax-scrolled layers (background map-tile
+    /// layer, the sprite/run layer, an optional decorative layer, etc) into
+    /// `target` in a single composited pass-per-layer, back-to-front by
+    /// `RenderLayer::z_order`. `camera` is the world-space camera position;
+    /// each layer is offset by `camera * layer.parallax`, so layers with a
+    /// lower parallax coefficient appear to sit further away. Pass
+    /// `clear: false` to composite on top of content already in `target`
+    /// (e.g. drawn by `MapRenderer`) instead of clearing it first.
+    pub fn render_layers(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+        camera: [f32; 2],
+        layers: &[RenderLayer],
+        clear: bool,
+    ) -> Result<()> {
+        if layers.is_empty() {
+            if clear {
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Sprite Layers Clear Encoder"),
+                });
+                {
+                    let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("Sprite Layers Clear Pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: target,
+                            resolve_target,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                                store: wgpu::StoreOp::Store,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                        timestamp_writes: None,
+                        occlusion_query_set: None,
+                    });
+                }
+                queue.submit(Some(encoder.finish()));
+            }
+            return Ok(());
+        }
+
+        let mut order: Vec<usize> = (0..layers.len()).collect();
+        order.sort_by_key(|&i| layers[i].z_order);
+
+        for (pass_idx, &layer_idx) in order.iter().enumerate() {
+            let load_op = if pass_idx == 0 && clear {
+                wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
+            } else {
+                wgpu::LoadOp::Load
+            };
+            self.render_layer(device, queue, target, resolve_target, &layers[layer_idx], camera, load_op)?;
+        }
+
+        Ok(())
+    }
 }
@@ -1,34 +1,432 @@
 use crate::data::Direction;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use image::RgbaImage;
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
 use wgpu;
 
+/// Current schema version for [`SpriteSheetManifest`] JSON files, bumped
+/// whenever the manifest format changes incompatibly.
+pub const SPRITE_SHEET_MANIFEST_VERSION: u32 = 1;
+
+/// Fullscreen-triangle passthrough blit used by `from_images_with_mipmaps`
+/// to downsample one mip level into the next.
+const MIP_BLIT_SHADER_SOURCE: &str = r#"
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    out.tex_coords = vec2<f32>(uv.x, 1.0 - uv.y);
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+@group(0) @binding(0) var src_texture: texture_2d<f32>;
+@group(0) @binding(1) var src_sampler: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(src_texture, src_sampler, in.tex_coords);
+}
+"#;
+
+/// One entry in a [`SpriteSheetManifest`]: a named sheet image and the
+/// inclusive `sprite_id` range it supplies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SheetRange {
+    pub name: String,
+    pub path: PathBuf,
+    pub sprite_id_min: u8,
+    pub sprite_id_max: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SpriteSheetManifestFile {
+    version: u32,
+    sheets: Vec<SheetRange>,
+}
+
+/// Pairs `sprite_id` ranges with the sprite sheets that should render them,
+/// so an overlay of many runs can draw visually distinct sprites (e.g. one
+/// sheet per agent/model) from a single `TextureAtlas`. Sheets are uploaded
+/// as array layers in manifest order, so a sheet's index in `sheets` is also
+/// its layer index.
+pub struct SpriteSheetManifest {
+    pub sheets: Vec<SheetRange>,
+}
+
+impl SpriteSheetManifest {
+    /// Load a manifest JSON file of the form:
+    /// `{"version": 1, "sheets": [{"name": ..., "path": ..., "sprite_id_min": ..., "sprite_id_max": ...}]}`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = fs::read_to_string(path.as_ref())
+            .with_context(|| format!("Failed to read sprite sheet manifest {:?}", path.as_ref()))?;
+
+        let file: SpriteSheetManifestFile = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse sprite sheet manifest {:?}", path.as_ref()))?;
+
+        if file.version != SPRITE_SHEET_MANIFEST_VERSION {
+            bail!(
+                "Unsupported sprite sheet manifest version {} (expected {})",
+                file.version,
+                SPRITE_SHEET_MANIFEST_VERSION
+            );
+        }
+
+        log::info!("Loaded sprite sheet manifest with {} sheet(s) from {:?}", file.sheets.len(), path.as_ref());
+
+        Ok(Self { sheets: file.sheets })
+    }
+
+    /// Look up the array layer to draw `sprite_id` from, based on which
+    /// sheet's range contains it. Falls back to layer 0 if no range matches.
+    pub fn layer_for_sprite_id(&self, sprite_id: u8) -> u32 {
+        self.sheets
+            .iter()
+            .position(|sheet| sprite_id >= sheet.sprite_id_min && sprite_id <= sheet.sprite_id_max)
+            .map(|index| index as u32)
+            .unwrap_or(0)
+    }
+
+    /// Paths of every sheet, in layer order, for `TextureAtlas::load`
+    pub fn paths(&self) -> Vec<&Path> {
+        self.sheets.iter().map(|sheet| sheet.path.as_path()).collect()
+    }
+}
+
+/// One or more sprite sheets uploaded as layers of a single
+/// `TEXTURE_2D_ARRAY`, so player sprites, NPC sheets, and map-tile sheets can
+/// all be sampled from one bind group without rebinding textures per draw.
+/// All layers share `width`/`height`; sheets smaller than the largest one are
+/// padded with transparent pixels in their bottom-right corner.
 pub struct TextureAtlas {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
     pub width: u32,
     pub height: u32,
+    pub layer_count: u32,
 }
 
 impl TextureAtlas {
-    /// Load sprite sheet texture from file
-    pub fn load<P: AsRef<Path>>(
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        path: P,
-    ) -> Result<Self> {
-        let img = image::open(path.as_ref())
-            .context("Failed to open sprite sheet")?
-            .to_rgba8();
-
-        let dimensions = img.dimensions();
+    /// Load one or more sprite sheets, each becoming an array layer in upload order
+    pub fn load<P: AsRef<Path>>(device: &wgpu::Device, queue: &wgpu::Queue, paths: &[P]) -> Result<Self> {
+        if paths.is_empty() {
+            bail!("TextureAtlas::load requires at least one sprite sheet");
+        }
+
+        let images: Vec<RgbaImage> = paths
+            .iter()
+            .map(|path| {
+                image::open(path.as_ref())
+                    .with_context(|| format!("Failed to open sprite sheet {:?}", path.as_ref()))
+                    .map(|img| img.to_rgba8())
+            })
+            .collect::<Result<_>>()?;
+
+        Self::from_images(device, queue, images)
+    }
+
+    /// Like `load`, but decodes already-fetched bytes instead of reading from
+    /// the filesystem - for callers with no `std::fs` (e.g. `web::WebViewer`
+    /// decoding a sprite sheet fetched over HTTP into the browser).
+    pub fn load_from_bytes(device: &wgpu::Device, queue: &wgpu::Queue, sheets: &[&[u8]]) -> Result<Self> {
+        if sheets.is_empty() {
+            bail!("TextureAtlas::load_from_bytes requires at least one sprite sheet");
+        }
+
+        let images: Vec<RgbaImage> = sheets
+            .iter()
+            .map(|bytes| {
+                image::load_from_memory(bytes)
+                    .context("Failed to decode sprite sheet bytes")
+                    .map(|img| img.to_rgba8())
+            })
+            .collect::<Result<_>>()?;
+
+        Self::from_images(device, queue, images)
+    }
+
+    /// Like `load`, but builds a full mip chain (down to 1x1) and a
+    /// `Linear`-filtered sampler instead of the single-level, `Nearest`
+    /// pixel-exact path `load` uses - for layers drawn zoomed out far enough
+    /// that nearest-neighbor sampling aliases (e.g. a parallax background
+    /// layer). Pair with a `SpriteRenderer`/`SpritePipeline` constructed with
+    /// `mip_filtering: true` and a non-zero `RenderLayer::lod`; `load`'s
+    /// default full-res path is unaffected and remains the right choice for
+    /// the foreground sprite/run layer.
+    pub fn load_with_mipmaps<P: AsRef<Path>>(device: &wgpu::Device, queue: &wgpu::Queue, paths: &[P]) -> Result<Self> {
+        if paths.is_empty() {
+            bail!("TextureAtlas::load_with_mipmaps requires at least one sprite sheet");
+        }
+
+        let images: Vec<RgbaImage> = paths
+            .iter()
+            .map(|path| {
+                image::open(path.as_ref())
+                    .with_context(|| format!("Failed to open sprite sheet {:?}", path.as_ref()))
+                    .map(|img| img.to_rgba8())
+            })
+            .collect::<Result<_>>()?;
+
+        Self::from_images_with_mipmaps(device, queue, images)
+    }
+
+    /// Like `load_from_bytes`, but builds a full mip chain - see
+    /// `load_with_mipmaps` for when to use this over `load_from_bytes`.
+    pub fn load_from_bytes_with_mipmaps(device: &wgpu::Device, queue: &wgpu::Queue, sheets: &[&[u8]]) -> Result<Self> {
+        if sheets.is_empty() {
+            bail!("TextureAtlas::load_from_bytes_with_mipmaps requires at least one sprite sheet");
+        }
+
+        let images: Vec<RgbaImage> = sheets
+            .iter()
+            .map(|bytes| {
+                image::load_from_memory(bytes)
+                    .context("Failed to decode sprite sheet bytes")
+                    .map(|img| img.to_rgba8())
+            })
+            .collect::<Result<_>>()?;
+
+        Self::from_images_with_mipmaps(device, queue, images)
+    }
+
+    /// Uploads each of `images` as its own array layer of mip level 0, then generates
+    /// every smaller mip level by blitting the previous level through a
+    /// minimal fullscreen-triangle pipeline sampling with `Linear` - one
+    /// blit pass per mip level per array layer, same fullscreen-triangle
+    /// trick `heatmap_pipeline`'s colormap pass uses to avoid a vertex
+    /// buffer. The chain ends with a 1x1 level.
+    fn from_images_with_mipmaps(device: &wgpu::Device, queue: &wgpu::Queue, images: Vec<RgbaImage>) -> Result<Self> {
+        let width = images.iter().map(|img| img.dimensions().0).max().unwrap();
+        let height = images.iter().map(|img| img.dimensions().1).max().unwrap();
+        let layer_count = images.len() as u32;
+        let mip_level_count = width.max(height).ilog2() + 1;
 
         let size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
-            depth_or_array_layers: 1,
+            width,
+            height,
+            depth_or_array_layers: layer_count,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Sprite Texture Atlas (mipmapped)"),
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        for (layer_index, img) in images.iter().enumerate() {
+            let layer_data = if img.dimensions() == (width, height) {
+                img.clone()
+            } else {
+                let mut padded = RgbaImage::new(width, height);
+                image::imageops::overlay(&mut padded, img, 0, 0);
+                padded
+            };
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: layer_index as u32 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &layer_data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            );
+        }
+
+        let blit_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let blit_pipeline = Self::create_blit_pipeline(device);
+        let blit_bind_group_layout = blit_pipeline.get_bind_group_layout(0);
+
+        for mip_level in 1..mip_level_count {
+            for layer_index in 0..layer_count {
+                // One array layer's slice of the previous mip level, matching
+                // `dst_view`'s shape - the blit shader/bind group layout
+                // declare `texture_2d<f32>` (`D2`), not `texture_2d_array<f32>`.
+                let src_view = texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_mip_level: mip_level - 1,
+                    mip_level_count: Some(1),
+                    base_array_layer: layer_index,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                });
+
+                let dst_view = texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_mip_level: mip_level,
+                    mip_level_count: Some(1),
+                    base_array_layer: layer_index,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                });
+
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Mip Blit Bind Group"),
+                    layout: &blit_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&src_view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&blit_sampler),
+                        },
+                    ],
+                });
+
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Mip Blit Encoder"),
+                });
+                {
+                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("Mip Blit Pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: &dst_view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                                store: wgpu::StoreOp::Store,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                        timestamp_writes: None,
+                        occlusion_query_set: None,
+                    });
+                    render_pass.set_pipeline(&blit_pipeline);
+                    render_pass.set_bind_group(0, &bind_group, &[]);
+                    render_pass.draw(0..3, 0..1);
+                }
+                queue.submit(Some(encoder.finish()));
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        log::info!(
+            "Loaded sprite atlas: {}x{} x{} layer(s), {} mip level(s)",
+            width,
+            height,
+            layer_count,
+            mip_level_count
+        );
+
+        Ok(Self {
+            texture,
+            view,
+            sampler: blit_sampler,
+            width,
+            height,
+            layer_count,
+        })
+    }
+
+    /// Fullscreen-triangle pass (via `vertex_index`, no vertex/index buffer
+    /// needed) that samples `binding 0`/`binding 1` with a `Linear` sampler
+    /// and writes the result unmodified - used once per mip level to
+    /// downsample the previous level into the next.
+    fn create_blit_pipeline(device: &wgpu::Device) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mip Blit Shader"),
+            source: wgpu::ShaderSource::Wgsl(MIP_BLIT_SHADER_SOURCE.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Mip Blit Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mip Blit Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mip Blit Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    fn from_images(device: &wgpu::Device, queue: &wgpu::Queue, images: Vec<RgbaImage>) -> Result<Self> {
+        let width = images.iter().map(|img| img.dimensions().0).max().unwrap();
+        let height = images.iter().map(|img| img.dimensions().1).max().unwrap();
+        let layer_count = images.len() as u32;
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: layer_count,
         };
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
@@ -42,23 +440,36 @@ impl TextureAtlas {
             view_formats: &[],
         });
 
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &img,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            size,
-        );
+        for (layer_index, img) in images.iter().enumerate() {
+            let layer_data = if img.dimensions() == (width, height) {
+                img.clone()
+            } else {
+                let mut padded = RgbaImage::new(width, height);
+                image::imageops::overlay(&mut padded, img, 0, 0);
+                padded
+            };
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: layer_index as u32 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &layer_data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
 
         // Use nearest-neighbor filtering for pixel-perfect sprites
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
@@ -71,26 +482,27 @@ impl TextureAtlas {
             ..Default::default()
         });
 
-        log::info!(
-            "Loaded sprite atlas: {}x{} from {:?}",
-            dimensions.0,
-            dimensions.1,
-            path.as_ref()
-        );
+        log::info!("Loaded sprite atlas: {}x{} x{} layer(s)", width, height, layer_count);
 
         Ok(Self {
             texture,
             view,
             sampler,
-            width: dimensions.0,
-            height: dimensions.1,
+            width,
+            height,
+            layer_count,
         })
     }
 
-    /// Get texture coordinates for a sprite
+    /// Get texture coordinates for a sprite on a given sheet/layer
     /// Formula from JS: sx = 9 + 17 * x, sy = 34 + 17 * y, width = 16, height = 16
-    pub fn get_sprite_tex_coords(&self, sprite_id: u8, direction: Direction) -> [f32; 4] {
-        let x = direction.column_index();
+    /// `walk_frame` selects one of the walk-cycle columns to the right of a
+    /// direction's base (idle) column - 0 is idle, 1/2 are the left-foot/
+    /// right-foot stepping frames.
+    pub fn get_sprite_tex_coords(&self, layer: u32, sprite_id: u8, direction: Direction, walk_frame: u32) -> [f32; 4] {
+        debug_assert!(layer < self.layer_count, "layer {} out of range (have {})", layer, self.layer_count);
+
+        let x = direction.column_index() + walk_frame as usize;
         let y = sprite_id as usize;
 
         let sx = 9.0 + 17.0 * x as f32;
@@ -0,0 +1,128 @@
+use anyhow::{bail, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// Size of each independently-compressed page, in decompressed bytes. A
+/// reader only ever has to decompress one page to serve a byte range, rather
+/// than the whole file up to that point.
+pub const PAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Trailing magic identifying a seek-table footer written by `encode_seekable`.
+/// Same idea as the upstream zstd seekable format (independent frames plus a
+/// skippable seek-table frame at the end), but with our own compact page
+/// table rather than the libzstd-seekable C library's binary layout, since
+/// nothing else in this crate links that library.
+const SEEK_TABLE_MAGIC: u64 = 0x315F4B4545535F5A; // "Z_SEEK_1" as little-endian bytes
+
+const ENTRY_SIZE: u64 = 24; // 3x u64: compressed_offset, compressed_len, decompressed_len
+const FOOTER_TAIL_SIZE: u64 = 16; // entry_count: u64, then SEEK_TABLE_MAGIC: u64
+
+#[derive(Debug, Clone, Copy)]
+pub struct SeekTableEntry {
+    pub compressed_offset: u64,
+    pub compressed_len: u64,
+    pub decompressed_len: u64,
+}
+
+/// Compress `input` to `output` as a sequence of independent `PAGE_SIZE`-byte
+/// zstd frames followed by a seek-table footer mapping each page to its
+/// compressed byte range, so a reader can decompress one page at a time
+/// instead of re-streaming from the start of the file.
+pub fn encode_seekable<R: Read, W: Write>(mut input: R, mut output: W, level: i32) -> Result<()> {
+    let mut entries = Vec::new();
+    let mut buffer = vec![0u8; PAGE_SIZE];
+    let mut compressed_offset = 0u64;
+
+    loop {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            let n = input.read(&mut buffer[filled..])?;
+            if n == 0 {
+                break;
+            }
+            filled += n;
+        }
+        if filled == 0 {
+            break;
+        }
+
+        let compressed = zstd::bulk::compress(&buffer[..filled], level)?;
+        output.write_all(&compressed)?;
+        entries.push(SeekTableEntry {
+            compressed_offset,
+            compressed_len: compressed.len() as u64,
+            decompressed_len: filled as u64,
+        });
+        compressed_offset += compressed.len() as u64;
+
+        if filled < buffer.len() {
+            break;
+        }
+    }
+
+    for entry in &entries {
+        output.write_all(&entry.compressed_offset.to_le_bytes())?;
+        output.write_all(&entry.compressed_len.to_le_bytes())?;
+        output.write_all(&entry.decompressed_len.to_le_bytes())?;
+    }
+    output.write_all(&(entries.len() as u64).to_le_bytes())?;
+    output.write_all(&SEEK_TABLE_MAGIC.to_le_bytes())?;
+
+    Ok(())
+}
+
+/// Try to read the seek-table footer from the end of a file. Returns `None`
+/// (not an error) when the file doesn't end with our magic - e.g. a plain
+/// single-stream zstd file predating this format - so callers can fall back
+/// to sequential decoding.
+pub fn read_seek_table(file: &mut File) -> Result<Option<Vec<SeekTableEntry>>> {
+    let file_len = file.metadata()?.len();
+    if file_len < FOOTER_TAIL_SIZE {
+        return Ok(None);
+    }
+
+    file.seek(SeekFrom::End(-(FOOTER_TAIL_SIZE as i64)))?;
+    let mut tail = [0u8; FOOTER_TAIL_SIZE as usize];
+    file.read_exact(&mut tail)?;
+    let entry_count = u64::from_le_bytes(tail[0..8].try_into().unwrap());
+    let magic = u64::from_le_bytes(tail[8..16].try_into().unwrap());
+    if magic != SEEK_TABLE_MAGIC {
+        return Ok(None);
+    }
+
+    let table_size = entry_count * ENTRY_SIZE;
+    let footer_size = table_size + FOOTER_TAIL_SIZE;
+    if footer_size > file_len {
+        return Ok(None);
+    }
+
+    file.seek(SeekFrom::End(-(footer_size as i64)))?;
+    let mut entries = Vec::with_capacity(entry_count as usize);
+    for _ in 0..entry_count {
+        let mut buf = [0u8; ENTRY_SIZE as usize];
+        file.read_exact(&mut buf)?;
+        entries.push(SeekTableEntry {
+            compressed_offset: u64::from_le_bytes(buf[0..8].try_into().unwrap()),
+            compressed_len: u64::from_le_bytes(buf[8..16].try_into().unwrap()),
+            decompressed_len: u64::from_le_bytes(buf[16..24].try_into().unwrap()),
+        });
+    }
+
+    Ok(Some(entries))
+}
+
+/// Decompress a single page given its seek-table entry.
+pub fn decode_page(file: &mut File, entry: &SeekTableEntry) -> Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(entry.compressed_offset))?;
+    let mut compressed = vec![0u8; entry.compressed_len as usize];
+    file.read_exact(&mut compressed)?;
+    let decompressed = zstd::bulk::decompress(&compressed, entry.decompressed_len as usize)?;
+    if decompressed.len() as u64 != entry.decompressed_len {
+        bail!(
+            "seek table page length mismatch: expected {} decompressed bytes, got {}",
+            entry.decompressed_len,
+            decompressed.len()
+        );
+    }
+    Ok(decompressed)
+}
@@ -0,0 +1,186 @@
+use anyhow::{Context, Result};
+use rav1e::config::SpeedSettings;
+use rav1e::prelude::*;
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// IVF's fixed file header size in bytes (see `write_ivf_header`).
+const IVF_HEADER_SIZE: usize = 32;
+/// IVF's fixed per-frame header size in bytes: a 4-byte frame size plus an
+/// 8-byte presentation timestamp.
+const IVF_FRAME_HEADER_SIZE: usize = 12;
+/// Offset of the frame-count field in the IVF header, rewritten by `finish`
+/// once the real count is known.
+const IVF_FRAME_COUNT_OFFSET: u64 = 24;
+
+/// Self-contained alternative to `ProResEncoder`: encodes straight to AV1
+/// (via `rav1e`, no ffmpeg subprocess) wrapped in a bare IVF container,
+/// trading the dual RGB+mask streams for a single much smaller web-deliverable
+/// file. Alpha isn't carried - callers that need it should stick with
+/// `ProResEncoder`/`GifEncoder`.
+pub struct Av1Encoder {
+    context: Context<u8>,
+    file: File,
+    width: usize,
+    height: usize,
+    frame_count: u32,
+    pts: u64,
+}
+
+impl Av1Encoder {
+    pub fn new<P: AsRef<Path>>(output_path: P, width: u32, height: u32, fps: u32) -> Result<Self> {
+        let width = width as usize;
+        let height = height as usize;
+
+        let mut enc_config = EncoderConfig::default();
+        enc_config.width = width;
+        enc_config.height = height;
+        enc_config.time_base = Rational::new(1, fps as u64);
+        enc_config.speed_settings = SpeedSettings::from_preset(6);
+
+        let config = Config::new().with_encoder_config(enc_config);
+        let context: Context<u8> = config
+            .new_context()
+            .context("Failed to create rav1e encoding context")?;
+
+        let mut file = File::create(output_path).context("Failed to create AV1 output file")?;
+        write_ivf_header(&mut file, width as u16, height as u16, fps, 0)?;
+
+        Ok(Self {
+            context,
+            file,
+            width,
+            height,
+            frame_count: 0,
+            pts: 0,
+        })
+    }
+
+    /// Write a single frame (RGBA8, row-major, top-to-bottom). Converts to
+    /// planar BT.601 I420 - close enough for this crate's flat sprite/tile
+    /// colors - before handing it to rav1e.
+    pub fn write_frame(&mut self, frame_data: &[u8]) -> Result<()> {
+        let expected_size = self.width * self.height * 4;
+        if frame_data.len() != expected_size {
+            anyhow::bail!(
+                "Invalid frame size: expected {} bytes, got {}",
+                expected_size,
+                frame_data.len()
+            );
+        }
+
+        let mut frame = self.context.new_frame();
+        rgba_to_i420(frame_data, self.width, self.height, &mut frame);
+
+        self.context
+            .send_frame(frame)
+            .context("Failed to send frame to rav1e")?;
+        self.pts += 1;
+
+        self.drain_packets()
+    }
+
+    /// Drain whatever packets rav1e has ready, writing each as one IVF frame.
+    /// Called after every `send_frame` and again during `finish` to collect
+    /// packets still buffered for lookahead/reordering.
+    fn drain_packets(&mut self) -> Result<()> {
+        loop {
+            match self.context.receive_packet() {
+                Ok(packet) => {
+                    write_ivf_frame_header(&mut self.file, packet.data.len() as u32, packet.input_frameno)?;
+                    self.file
+                        .write_all(&packet.data)
+                        .context("Failed to write AV1 packet to IVF file")?;
+                    self.frame_count += 1;
+                }
+                Err(EncoderStatus::Encoded) => continue,
+                Err(EncoderStatus::NeedMoreData) | Err(EncoderStatus::LimitReached) => break,
+                Err(e) => return Err(e).context("rav1e failed to produce a packet"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush the encoder (signals end-of-stream, drains remaining packets)
+    /// and rewrite the IVF header's frame count now that it's known.
+    pub fn finish(mut self) -> Result<()> {
+        self.context.flush();
+        self.drain_packets()?;
+
+        self.file
+            .seek(SeekFrom::Start(IVF_FRAME_COUNT_OFFSET))
+            .context("Failed to seek to IVF frame count field")?;
+        self.file
+            .write_all(&self.frame_count.to_le_bytes())
+            .context("Failed to rewrite IVF frame count")?;
+
+        Ok(())
+    }
+}
+
+/// Writes the 32-byte IVF file header ("DKIF", version, header size, the
+/// `AV01` fourCC, dimensions, framerate and a frame count placeholder that
+/// `Av1Encoder::finish` rewrites once the real count is known).
+fn write_ivf_header(file: &mut File, width: u16, height: u16, fps: u32, frame_count: u32) -> Result<()> {
+    let mut header = [0u8; IVF_HEADER_SIZE];
+    header[0..4].copy_from_slice(b"DKIF");
+    header[4..6].copy_from_slice(&0u16.to_le_bytes()); // version
+    header[6..8].copy_from_slice(&(IVF_HEADER_SIZE as u16).to_le_bytes());
+    header[8..12].copy_from_slice(b"AV01");
+    header[12..14].copy_from_slice(&width.to_le_bytes());
+    header[14..16].copy_from_slice(&height.to_le_bytes());
+    header[16..20].copy_from_slice(&fps.to_le_bytes()); // framerate numerator
+    header[20..24].copy_from_slice(&1u32.to_le_bytes()); // framerate denominator
+    header[24..28].copy_from_slice(&frame_count.to_le_bytes());
+    header[28..32].copy_from_slice(&0u32.to_le_bytes()); // reserved
+
+    file.write_all(&header).context("Failed to write IVF header")?;
+    Ok(())
+}
+
+/// Writes one IVF per-frame header: a 4-byte packet size followed by an
+/// 8-byte presentation timestamp.
+fn write_ivf_frame_header(file: &mut File, packet_size: u32, pts: u64) -> Result<()> {
+    let mut header = [0u8; IVF_FRAME_HEADER_SIZE];
+    header[0..4].copy_from_slice(&packet_size.to_le_bytes());
+    header[4..12].copy_from_slice(&pts.to_le_bytes());
+    file.write_all(&header).context("Failed to write IVF frame header")?;
+    Ok(())
+}
+
+/// Converts a packed RGBA8 buffer into BT.601 I420 and fills `frame`'s three
+/// planes. Chroma is point-sampled from the top-left pixel of each 2x2 block
+/// (no averaging) - these frames are mostly flat tile colors where that
+/// loses nothing visible.
+fn rgba_to_i420(rgba: &[u8], width: usize, height: usize, frame: &mut Frame<u8>) {
+    let mut y_plane = vec![0u8; width * height];
+    let chroma_width = (width + 1) / 2;
+    let chroma_height = (height + 1) / 2;
+    let mut u_plane = vec![0u8; chroma_width * chroma_height];
+    let mut v_plane = vec![0u8; chroma_width * chroma_height];
+
+    for y in 0..height {
+        for x in 0..width {
+            let idx = (y * width + x) * 4;
+            let r = rgba[idx] as f32;
+            let g = rgba[idx + 1] as f32;
+            let b = rgba[idx + 2] as f32;
+
+            let luma = 16.0 + (65.738 * r + 129.057 * g + 25.064 * b) / 256.0;
+            y_plane[y * width + x] = luma.round().clamp(0.0, 255.0) as u8;
+
+            if x % 2 == 0 && y % 2 == 0 {
+                let cb = 128.0 + (-37.945 * r - 74.494 * g + 112.439 * b) / 256.0;
+                let cr = 128.0 + (112.439 * r - 94.154 * g - 18.285 * b) / 256.0;
+                let chroma_idx = (y / 2) * chroma_width + (x / 2);
+                u_plane[chroma_idx] = cb.round().clamp(0.0, 255.0) as u8;
+                v_plane[chroma_idx] = cr.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+
+    frame.planes[0].copy_from_raw_u8(&y_plane, width, 1);
+    frame.planes[1].copy_from_raw_u8(&u_plane, chroma_width, 1);
+    frame.planes[2].copy_from_raw_u8(&v_plane, chroma_width, 1);
+}
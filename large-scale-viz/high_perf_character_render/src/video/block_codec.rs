@@ -0,0 +1,224 @@
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const BLOCK_SIZE: usize = 4;
+const MAGIC: &[u8; 4] = b"BSK1";
+
+/// Command byte a decoder reads before each 4x4 block: reuse the same block
+/// from the previous frame verbatim.
+const CMD_COPY_PREVIOUS: u8 = 0;
+/// ...fill the block with a single RGBA color (4 bytes follow).
+const CMD_FILL: u8 = 1;
+/// ...the block didn't compress either way; raw RGBA8 bytes follow (64 for a
+/// 4x4 block).
+const CMD_RAW: u8 = 2;
+
+/// MSVideo1-style temporal block-skip encoder for frames that are mostly
+/// unchanged from the previous one - the common case here, since the map
+/// background never changes and only a handful of sprites move each frame.
+/// Not a standard video container: just a length-prefix-free stream of
+/// per-block commands (see the `CMD_*` constants) following a small header
+/// (magic, width, height, block size), meant to be replayed by a decoder
+/// that already knows the frame count.
+pub struct BlockCodecEncoder {
+    writer: BufWriter<File>,
+    width: usize,
+    height: usize,
+    quality: u8,
+    keyframe_interval: u64,
+    frame_index: u64,
+    previous_frame: Option<Vec<u8>>,
+    copied_blocks: u64,
+    filled_blocks: u64,
+    raw_blocks: u64,
+}
+
+impl BlockCodecEncoder {
+    /// `quality` is 0..=100: 0 skips/fills as aggressively as the thresholds
+    /// allow, 100 only skips blocks that are byte-identical to the previous
+    /// frame and only fills blocks that are already a single solid color.
+    /// `keyframe_interval` forces every block to be re-sent (never
+    /// `CMD_COPY_PREVIOUS`) every N frames, so a decoder can start playback
+    /// or recover from a dropped frame without replaying from frame 0; 0
+    /// disables forced keyframes (only frame 0 is one, for lack of a
+    /// previous frame to diff against).
+    pub fn new<P: AsRef<Path>>(
+        output_path: P,
+        width: u32,
+        height: u32,
+        quality: u8,
+        keyframe_interval: u64,
+    ) -> Result<Self> {
+        let mut writer =
+            BufWriter::new(File::create(output_path).context("Failed to create block-codec output file")?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&width.to_le_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+        writer.write_all(&[BLOCK_SIZE as u8])?;
+
+        Ok(Self {
+            writer,
+            width: width as usize,
+            height: height as usize,
+            quality: quality.min(100),
+            keyframe_interval,
+            frame_index: 0,
+            previous_frame: None,
+            copied_blocks: 0,
+            filled_blocks: 0,
+            raw_blocks: 0,
+        })
+    }
+
+    /// Map `quality` to the skip/fill summed-channel-distance thresholds
+    fn thresholds(&self) -> (u32, u32) {
+        let level = (self.quality as u32 / 10).min(10);
+        let factor = 10u32.saturating_sub(level);
+        (factor * 8, factor * 16)
+    }
+
+    /// Encode one RGBA8 frame (row-major, top-to-bottom) as a sequence of
+    /// per-4x4-block commands relative to the previous frame
+    pub fn write_frame(&mut self, frame_data: &[u8]) -> Result<()> {
+        let expected_size = self.width * self.height * 4;
+        if frame_data.len() != expected_size {
+            bail!(
+                "Invalid frame size: expected {} bytes, got {}",
+                expected_size,
+                frame_data.len()
+            );
+        }
+
+        let (skip_threshold, fill_threshold) = self.thresholds();
+        let blocks_x = self.width.div_ceil(BLOCK_SIZE);
+        let blocks_y = self.height.div_ceil(BLOCK_SIZE);
+
+        let is_keyframe = self.keyframe_interval > 0 && self.frame_index % self.keyframe_interval == 0;
+
+        for by in 0..blocks_y {
+            for bx in 0..blocks_x {
+                if !is_keyframe {
+                    if let Some(previous_frame) = &self.previous_frame {
+                        let distance = block_distance(previous_frame, frame_data, self.width, self.height, bx, by);
+                        if distance <= skip_threshold {
+                            self.writer.write_all(&[CMD_COPY_PREVIOUS])?;
+                            self.copied_blocks += 1;
+                            continue;
+                        }
+                    }
+                }
+
+                if let Some(fill_color) = near_solid_color(frame_data, self.width, self.height, bx, by, fill_threshold) {
+                    self.writer.write_all(&[CMD_FILL])?;
+                    self.writer.write_all(&fill_color)?;
+                    self.filled_blocks += 1;
+                    continue;
+                }
+
+                self.writer.write_all(&[CMD_RAW])?;
+                write_raw_block(&mut self.writer, frame_data, self.width, self.height, bx, by)?;
+                self.raw_blocks += 1;
+            }
+        }
+
+        self.previous_frame = Some(frame_data.to_vec());
+        self.frame_index += 1;
+        Ok(())
+    }
+
+    /// Flush the output file and log the final copy/fill/raw block breakdown
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush()?;
+        let total = self.copied_blocks + self.filled_blocks + self.raw_blocks;
+        log::info!(
+            "Block codec: {} blocks total - {} copied, {} filled, {} raw ({:.1}% not re-sent)",
+            total,
+            self.copied_blocks,
+            self.filled_blocks,
+            self.raw_blocks,
+            100.0 * self.copied_blocks as f64 / total.max(1) as f64
+        );
+        Ok(())
+    }
+}
+
+/// Summed per-channel, per-pixel distance between the same 4x4 block in two
+/// frames
+fn block_distance(prev: &[u8], curr: &[u8], width: usize, height: usize, bx: usize, by: usize) -> u32 {
+    let mut distance = 0u32;
+    for dy in 0..BLOCK_SIZE {
+        let y = by * BLOCK_SIZE + dy;
+        if y >= height {
+            break;
+        }
+        for dx in 0..BLOCK_SIZE {
+            let x = bx * BLOCK_SIZE + dx;
+            if x >= width {
+                break;
+            }
+            let idx = (y * width + x) * 4;
+            for c in 0..4 {
+                distance += (prev[idx + c] as i32 - curr[idx + c] as i32).unsigned_abs();
+            }
+        }
+    }
+    distance
+}
+
+/// If every pixel in the block is within `fill_threshold` of the first
+/// pixel, returns that color; otherwise `None`
+fn near_solid_color(data: &[u8], width: usize, height: usize, bx: usize, by: usize, fill_threshold: u32) -> Option<[u8; 4]> {
+    let mut first: Option<[u8; 4]> = None;
+    let mut max_distance = 0u32;
+
+    for dy in 0..BLOCK_SIZE {
+        let y = by * BLOCK_SIZE + dy;
+        if y >= height {
+            break;
+        }
+        for dx in 0..BLOCK_SIZE {
+            let x = bx * BLOCK_SIZE + dx;
+            if x >= width {
+                break;
+            }
+            let idx = (y * width + x) * 4;
+            let pixel = [data[idx], data[idx + 1], data[idx + 2], data[idx + 3]];
+            match first {
+                None => first = Some(pixel),
+                Some(f) => {
+                    let mut d = 0u32;
+                    for c in 0..4 {
+                        d += (f[c] as i32 - pixel[c] as i32).unsigned_abs();
+                    }
+                    max_distance = max_distance.max(d);
+                }
+            }
+        }
+    }
+
+    if max_distance <= fill_threshold {
+        first
+    } else {
+        None
+    }
+}
+
+/// Write a 4x4 block's raw RGBA8 bytes, zero-padding any rows/columns that
+/// fall past the frame edge
+fn write_raw_block(writer: &mut impl Write, data: &[u8], width: usize, height: usize, bx: usize, by: usize) -> Result<()> {
+    for dy in 0..BLOCK_SIZE {
+        let y = by * BLOCK_SIZE + dy;
+        for dx in 0..BLOCK_SIZE {
+            let x = bx * BLOCK_SIZE + dx;
+            if y < height && x < width {
+                let idx = (y * width + x) * 4;
+                writer.write_all(&data[idx..idx + 4])?;
+            } else {
+                writer.write_all(&[0u8; 4])?;
+            }
+        }
+    }
+    Ok(())
+}
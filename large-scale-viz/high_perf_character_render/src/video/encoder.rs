@@ -3,7 +3,12 @@ use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::{Child, ChildStdin, Command, Stdio};
 
-pub struct ProResEncoder {
+/// Shared plumbing for a dual H.264 (RGB + alpha mask) ffmpeg pipeline:
+/// spawning the two `ffmpeg` subprocesses, splitting/streaming raw RGBA
+/// frames into their stdin pipes, and waiting on both at the end. Both
+/// `ProResEncoder` and `CmafEncoder` are thin wrappers around this that only
+/// differ in the extra muxer args passed to `spawn`.
+struct DualStreamEncoder {
     rgb_process: Child,
     mask_process: Child,
     rgb_stdin: Option<ChildStdin>,
@@ -14,13 +19,17 @@ pub struct ProResEncoder {
     mask_buffer: Vec<u8>,
 }
 
-impl ProResEncoder {
-    /// Create a new dual H.264 encoder (RGB + mask) that streams to two files
-    pub fn new<P: AsRef<Path>>(
+impl DualStreamEncoder {
+    /// Spawn the RGB/mask `ffmpeg` pair. `extra_muxer_args` is appended right
+    /// before the output path on each invocation (e.g. `-movflags ...`) so
+    /// callers can select a container flavor without duplicating the rest of
+    /// the command line.
+    fn spawn<P: AsRef<Path>>(
         output_path: P,
         width: u32,
         height: u32,
         fps: u32,
+        extra_muxer_args: &[&str],
     ) -> Result<Self> {
         let output_path = output_path.as_ref();
 
@@ -40,21 +49,29 @@ impl ProResEncoder {
         log::info!("  Mask output: {:?}", mask_path);
 
         // Build RGB encoder (H.264, high quality)
+        let mut rgb_args = vec![
+            "-y",
+            "-f", "rawvideo",
+            "-pixel_format", "rgb24",
+        ];
+        let video_size = format!("{}x{}", width, height);
+        let framerate = format!("{}", fps);
+        rgb_args.extend(&[
+            "-video_size", &video_size,
+            "-framerate", &framerate,
+            "-i", "pipe:0",
+            "-c:v", "libx264",
+            "-preset", "slow",
+            "-crf", "15", // Near-lossless quality (comparable to ProRes 4444)
+            "-pix_fmt", "yuv444p", // 4:4:4 chroma subsampling for max quality
+            "-threads", "8",
+        ]);
+        rgb_args.extend(extra_muxer_args);
+        let rgb_path_str = rgb_path.to_str().unwrap();
+        rgb_args.push(rgb_path_str);
+
         let mut rgb_process = Command::new("ffmpeg")
-            .args(&[
-                "-y",
-                "-f", "rawvideo",
-                "-pixel_format", "rgb24",
-                "-video_size", &format!("{}x{}", width, height),
-                "-framerate", &format!("{}", fps),
-                "-i", "pipe:0",
-                "-c:v", "libx264",
-                "-preset", "slow",
-                "-crf", "15", // Near-lossless quality (comparable to ProRes 4444)
-                "-pix_fmt", "yuv444p", // 4:4:4 chroma subsampling for max quality
-                "-threads", "8",
-                rgb_path.to_str().unwrap(),
-            ])
+            .args(&rgb_args)
             .stdin(Stdio::piped())
             .stdout(Stdio::null())
             .stderr(Stdio::inherit())
@@ -62,21 +79,25 @@ impl ProResEncoder {
             .context("Failed to spawn RGB encoder")?;
 
         // Build mask encoder (H.264, grayscale)
+        let mut mask_args = vec![
+            "-y",
+            "-f", "rawvideo",
+            "-pixel_format", "gray",
+            "-video_size", &video_size,
+            "-framerate", &framerate,
+            "-i", "pipe:0",
+            "-c:v", "libx264",
+            "-preset", "slow",
+            "-crf", "15",
+            "-pix_fmt", "yuv420p",
+            "-threads", "8",
+        ];
+        mask_args.extend(extra_muxer_args);
+        let mask_path_str = mask_path.to_str().unwrap();
+        mask_args.push(mask_path_str);
+
         let mut mask_process = Command::new("ffmpeg")
-            .args(&[
-                "-y",
-                "-f", "rawvideo",
-                "-pixel_format", "gray",
-                "-video_size", &format!("{}x{}", width, height),
-                "-framerate", &format!("{}", fps),
-                "-i", "pipe:0",
-                "-c:v", "libx264",
-                "-preset", "slow",
-                "-crf", "15",
-                "-pix_fmt", "yuv420p",
-                "-threads", "8",
-                mask_path.to_str().unwrap(),
-            ])
+            .args(&mask_args)
             .stdin(Stdio::piped())
             .stdout(Stdio::null())
             .stderr(Stdio::inherit())
@@ -111,7 +132,7 @@ impl ProResEncoder {
 
     /// Write a single frame (RGBA8, row-major, top-to-bottom)
     /// Splits into RGB and alpha mask streams
-    pub fn write_frame(&mut self, frame_data: &[u8]) -> Result<()> {
+    fn write_frame(&mut self, frame_data: &[u8]) -> Result<()> {
         let expected_size = (self.width * self.height * 4) as usize;
         if frame_data.len() != expected_size {
             anyhow::bail!(
@@ -158,8 +179,48 @@ impl ProResEncoder {
         Ok(())
     }
 
+    /// Write a single frame already split into an RGB plane and an alpha
+    /// mask plane (see `GpuContext::read_pixels_split`, which does the split
+    /// on the GPU). Skips the per-pixel CPU split `write_frame` does above.
+    fn write_frame_split(&mut self, rgb: &[u8], mask: &[u8]) -> Result<()> {
+        let pixel_count = (self.width * self.height) as usize;
+
+        if rgb.len() != pixel_count * 3 {
+            anyhow::bail!(
+                "Invalid RGB plane size: expected {} bytes, got {}",
+                pixel_count * 3,
+                rgb.len()
+            );
+        }
+        if mask.len() != pixel_count {
+            anyhow::bail!(
+                "Invalid mask plane size: expected {} bytes, got {}",
+                pixel_count,
+                mask.len()
+            );
+        }
+
+        if let Some(stdin) = &mut self.rgb_stdin {
+            stdin
+                .write_all(rgb)
+                .context("Failed to write RGB frame to ffmpeg")?;
+        } else {
+            anyhow::bail!("RGB encoder stdin is closed")
+        }
+
+        if let Some(stdin) = &mut self.mask_stdin {
+            stdin
+                .write_all(mask)
+                .context("Failed to write mask frame to ffmpeg")?;
+        } else {
+            anyhow::bail!("Mask encoder stdin is closed")
+        }
+
+        Ok(())
+    }
+
     /// Finish encoding and close both files
-    pub fn finish(mut self) -> Result<()> {
+    fn finish(mut self) -> Result<()> {
         log::info!("Finalizing video encoding...");
 
         // Close stdin to signal end of input
@@ -190,10 +251,71 @@ impl ProResEncoder {
     }
 }
 
-impl Drop for ProResEncoder {
+impl Drop for DualStreamEncoder {
     fn drop(&mut self) {
         // Try to terminate both encoders if they're still running
         let _ = self.rgb_process.kill();
         let _ = self.mask_process.kill();
     }
 }
+
+pub struct ProResEncoder(DualStreamEncoder);
+
+impl ProResEncoder {
+    /// Create a new dual H.264 encoder (RGB + mask) that streams to two files
+    pub fn new<P: AsRef<Path>>(output_path: P, width: u32, height: u32, fps: u32) -> Result<Self> {
+        Ok(Self(DualStreamEncoder::spawn(output_path, width, height, fps, &[])?))
+    }
+
+    /// Write a single frame (RGBA8, row-major, top-to-bottom)
+    /// Splits into RGB and alpha mask streams
+    pub fn write_frame(&mut self, frame_data: &[u8]) -> Result<()> {
+        self.0.write_frame(frame_data)
+    }
+
+    /// Write a single frame already split into an RGB plane and an alpha
+    /// mask plane (see `GpuContext::read_pixels_split`, which does the split
+    /// on the GPU). Skips the per-pixel CPU split `write_frame` does above.
+    pub fn write_frame_split(&mut self, rgb: &[u8], mask: &[u8]) -> Result<()> {
+        self.0.write_frame_split(rgb, mask)
+    }
+
+    /// Finish encoding and close both files
+    pub fn finish(self) -> Result<()> {
+        self.0.finish()
+    }
+}
+
+pub struct CmafEncoder(DualStreamEncoder);
+
+impl CmafEncoder {
+    /// Create a new dual H.264 fragmented-MP4 (fMP4/CMAF) encoder (RGB +
+    /// mask) that streams to two files. `-movflags
+    /// frag_keyframe+empty_moov+default_base_moof+cmaf` is ffmpeg's own CMAF
+    /// recipe: it writes an `ftyp` with major brand `cmf2` and compatible
+    /// brands `iso6`/`cmfc`, then an empty `moov` up front (no sample table
+    /// to wait for) followed by one `moof`+`mdat` fragment per keyframe GOP,
+    /// each carrying its own base-media-decode-time, so a player can start
+    /// and seek before the file has finished writing - unlike
+    /// `ProResEncoder`'s single moov-at-the-end mp4.
+    pub fn new<P: AsRef<Path>>(output_path: P, width: u32, height: u32, fps: u32) -> Result<Self> {
+        Ok(Self(DualStreamEncoder::spawn(
+            output_path,
+            width,
+            height,
+            fps,
+            &["-movflags", "frag_keyframe+empty_moov+default_base_moof+cmaf", "-f", "mp4"],
+        )?))
+    }
+
+    /// Write a single frame (RGBA8, row-major, top-to-bottom)
+    /// Splits into RGB and alpha mask streams
+    pub fn write_frame(&mut self, frame_data: &[u8]) -> Result<()> {
+        self.0.write_frame(frame_data)
+    }
+
+    /// Finish encoding and close both files
+    pub fn finish(self) -> Result<()> {
+        self.0.finish()
+    }
+}
@@ -0,0 +1,77 @@
+use anyhow::{Context, Result};
+use gif::{Encoder, Frame, Repeat};
+use std::fs::File;
+use std::path::Path;
+
+/// Self-contained alternative to `ProResEncoder`/`CmafEncoder`: writes a
+/// single animated GIF with no ffmpeg subprocess, using the `gif` crate's
+/// built-in NeuQuant quantizer (the same approach as wgpu's own `gifs`
+/// example). A dependency-free, directly-shareable output for short path
+/// animations, at the cost of a 256-color palette per frame and on/off
+/// (no partial) alpha.
+pub struct GifEncoder {
+    encoder: Encoder<File>,
+    width: u16,
+    height: u16,
+    delay_centisecs: u16,
+}
+
+impl GifEncoder {
+    /// `fps` is converted to a per-frame delay in the GIF format's native
+    /// hundredths-of-a-second units, rounded to the nearest unit (GIF can't
+    /// represent arbitrary frame rates any more precisely than that).
+    pub fn new<P: AsRef<Path>>(output_path: P, width: u32, height: u32, fps: u32) -> Result<Self> {
+        let width = u16::try_from(width).context("GIF width must fit in u16")?;
+        let height = u16::try_from(height).context("GIF height must fit in u16")?;
+
+        let file = File::create(output_path).context("Failed to create GIF output file")?;
+        let mut encoder =
+            Encoder::new(file, width, height, &[]).context("Failed to initialize GIF encoder")?;
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .context("Failed to set GIF loop mode")?;
+
+        let delay_centisecs = (100.0 / fps.max(1) as f32).round().max(1.0) as u16;
+
+        Ok(Self {
+            encoder,
+            width,
+            height,
+            delay_centisecs,
+        })
+    }
+
+    /// Write a single frame (RGBA8, row-major, top-to-bottom). Quantized to a
+    /// 256-color palette per frame via `Frame::from_rgba_speed`, which also
+    /// collapses every fully-transparent pixel (alpha 0) to one transparent
+    /// palette index - GIF transparency is binary, so any pixel with nonzero
+    /// alpha, semi-transparent edges included, comes out fully opaque.
+    pub fn write_frame(&mut self, frame_data: &[u8]) -> Result<()> {
+        let expected_size = self.width as usize * self.height as usize * 4;
+        if frame_data.len() != expected_size {
+            anyhow::bail!(
+                "Invalid frame size: expected {} bytes, got {}",
+                expected_size,
+                frame_data.len()
+            );
+        }
+
+        // from_rgba_speed quantizes in place and wants ownership of the buffer.
+        let mut pixels = frame_data.to_vec();
+        let mut frame = Frame::from_rgba_speed(self.width, self.height, &mut pixels, 10);
+        frame.delay = self.delay_centisecs;
+
+        self.encoder
+            .write_frame(&frame)
+            .context("Failed to write GIF frame")?;
+
+        Ok(())
+    }
+
+    /// Finish encoding. The `gif` crate flushes the trailer on drop, so this
+    /// just exists to match the `new`/`write_frame`/`finish` shape the other
+    /// encoders share.
+    pub fn finish(self) -> Result<()> {
+        Ok(())
+    }
+}
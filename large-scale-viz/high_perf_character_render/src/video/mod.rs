@@ -0,0 +1,59 @@
+pub mod av1_encoder;
+pub mod block_codec;
+pub mod encoder;
+pub mod gif_encoder;
+pub mod msvideo1;
+pub mod soundtrack;
+
+pub use av1_encoder::Av1Encoder;
+pub use block_codec::BlockCodecEncoder;
+pub use encoder::{CmafEncoder, ProResEncoder};
+pub use gif_encoder::GifEncoder;
+pub use msvideo1::MsVideo1Encoder;
+pub use soundtrack::{mux_soundtrack, CueEvent, SoundtrackManifest};
+
+use anyhow::Result;
+
+/// Selects which encoder backend writes frames. `ProRes` always finalizes a
+/// single moov at the end (today's default, despite the name it's actually
+/// libx264 underneath - see `ProResEncoder`). `Cmaf` writes a real CMAF-brand
+/// fragmented mp4 instead (`ftyp` major brand `cmf2`, compatible brands
+/// `iso6`/`cmfc`, via ffmpeg's own `+cmaf` movflag - see `CmafEncoder`), so
+/// the output is playable and seekable in a browser before encoding
+/// finishes. `MsVideo1` skips ffmpeg entirely and
+/// writes our own temporal block codec, tuned for mostly-static scenes with
+/// a handful of moving sprites (see `MsVideo1Encoder`). `Gif` also skips
+/// ffmpeg, writing a single shareable animated GIF (see `GifEncoder`). `Av1`
+/// skips ffmpeg too, encoding straight to a bare-IVF AV1 stream via rav1e for
+/// much smaller web-deliverable clips (see `Av1Encoder`).
+pub enum VideoEncoder {
+    ProRes(ProResEncoder),
+    Cmaf(CmafEncoder),
+    MsVideo1(MsVideo1Encoder),
+    Gif(GifEncoder),
+    Av1(Av1Encoder),
+}
+
+impl VideoEncoder {
+    /// Write a single frame (RGBA8, row-major, top-to-bottom)
+    pub fn write_frame(&mut self, frame_data: &[u8]) -> Result<()> {
+        match self {
+            VideoEncoder::ProRes(encoder) => encoder.write_frame(frame_data),
+            VideoEncoder::Cmaf(encoder) => encoder.write_frame(frame_data),
+            VideoEncoder::MsVideo1(encoder) => encoder.write_frame(frame_data),
+            VideoEncoder::Gif(encoder) => encoder.write_frame(frame_data),
+            VideoEncoder::Av1(encoder) => encoder.write_frame(frame_data),
+        }
+    }
+
+    /// Finish encoding and close both files
+    pub fn finish(self) -> Result<()> {
+        match self {
+            VideoEncoder::ProRes(encoder) => encoder.finish(),
+            VideoEncoder::Cmaf(encoder) => encoder.finish(),
+            VideoEncoder::MsVideo1(encoder) => encoder.finish(),
+            VideoEncoder::Gif(encoder) => encoder.finish(),
+            VideoEncoder::Av1(encoder) => encoder.finish(),
+        }
+    }
+}
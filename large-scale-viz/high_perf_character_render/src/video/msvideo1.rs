@@ -0,0 +1,374 @@
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const BLOCK_SIZE: usize = 4;
+const MAGIC: &[u8; 4] = b"MSV1";
+
+/// Command byte a decoder reads before each 4x4 block.
+const CMD_SKIP: u8 = 0;
+/// ...fill the block with a single RGB555 color (2 bytes follow).
+const CMD_FILL: u8 = 1;
+/// ...two RGB555 colors (2 bytes each) plus a 16-bit per-pixel selector mask
+/// (bit i picks color1 for pixel i, color0 otherwise).
+const CMD_TWO_COLOR: u8 = 2;
+/// ...the 4x4 block didn't fit two colors well; four independent 2x2
+/// sub-blocks follow, each its own two-color-plus-4-bit-mask encoding.
+const CMD_FOUR_QUADRANT: u8 = 3;
+
+/// Packed 5-5-5 RGB - the representation MS Video 1's real-world inter-frame
+/// block coding (the CRAM/WHAM fourCCs) uses internally. Halves the bytes a
+/// fill/two-color block costs versus carrying full RGBA8 forward.
+type Rgb555 = u16;
+
+fn to_rgb555(r: u8, g: u8, b: u8) -> Rgb555 {
+    (((r as u16) >> 3) << 10) | (((g as u16) >> 3) << 5) | ((b as u16) >> 3)
+}
+
+fn rgb555_channels(c: Rgb555) -> (u16, u16, u16) {
+    ((c >> 10) & 0x1f, (c >> 5) & 0x1f, c & 0x1f)
+}
+
+fn luma(c: Rgb555) -> u32 {
+    let (r, g, b) = rgb555_channels(c);
+    299 * r as u32 + 587 * g as u32 + 114 * b as u32
+}
+
+/// MS Video 1 (CRAM/WHAM)-style temporal block encoder: an alternative to
+/// `ProResEncoder` that exploits how little changes frame-to-frame when the
+/// map background is static and only a handful of sprites move. Not a real
+/// AVI/CRAM bitstream - a small self-describing container (magic, width,
+/// height, fps, block size) followed by a per-frame stream of per-4x4-block
+/// commands (see the `CMD_*` constants), meant to be replayed by a decoder
+/// that already knows the frame count.
+pub struct MsVideo1Encoder {
+    writer: BufWriter<File>,
+    width: usize,
+    height: usize,
+    quality: u8,
+    previous_frame: Option<Vec<Rgb555>>,
+    skipped_blocks: u64,
+    filled_blocks: u64,
+    two_color_blocks: u64,
+    four_quadrant_blocks: u64,
+}
+
+impl MsVideo1Encoder {
+    /// `quality` is 0..=100: 0 skips/fills/clusters as aggressively as the
+    /// thresholds allow, 100 only skips blocks that are identical to the
+    /// previous frame once quantized to RGB555 (alpha is ignored) and only
+    /// fills blocks that are already a solid RGB555 color.
+    pub fn new<P: AsRef<Path>>(output_path: P, width: u32, height: u32, fps: u32, quality: u8) -> Result<Self> {
+        let mut writer =
+            BufWriter::new(File::create(output_path).context("Failed to create MS Video 1 output file")?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&width.to_le_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+        writer.write_all(&fps.to_le_bytes())?;
+        writer.write_all(&[BLOCK_SIZE as u8])?;
+
+        Ok(Self {
+            writer,
+            width: width as usize,
+            height: height as usize,
+            quality: quality.min(100),
+            previous_frame: None,
+            skipped_blocks: 0,
+            filled_blocks: 0,
+            two_color_blocks: 0,
+            four_quadrant_blocks: 0,
+        })
+    }
+
+    /// Map `quality` to the skip threshold (squared channel distance summed
+    /// over the block) and the fill threshold (largest per-pixel squared
+    /// channel distance from the block's mean color)
+    fn thresholds(&self) -> (u32, u32) {
+        let level = (self.quality as u32 / 10).min(10);
+        let factor = 10u32.saturating_sub(level);
+        (factor * 8, factor * 16)
+    }
+
+    /// Encode one RGBA8 frame (row-major, top-to-bottom) as a sequence of
+    /// per-4x4-block commands relative to the previous frame
+    pub fn write_frame(&mut self, frame_data: &[u8]) -> Result<()> {
+        let expected_size = self.width * self.height * 4;
+        if frame_data.len() != expected_size {
+            bail!(
+                "Invalid frame size: expected {} bytes, got {}",
+                expected_size,
+                frame_data.len()
+            );
+        }
+
+        let current: Vec<Rgb555> = frame_data
+            .chunks_exact(4)
+            .map(|p| to_rgb555(p[0], p[1], p[2]))
+            .collect();
+
+        let (skip_threshold, fill_threshold) = self.thresholds();
+        let blocks_x = self.width.div_ceil(BLOCK_SIZE);
+        let blocks_y = self.height.div_ceil(BLOCK_SIZE);
+
+        for by in 0..blocks_y {
+            for bx in 0..blocks_x {
+                if let Some(previous) = &self.previous_frame {
+                    let distance = block_distance(previous, &current, self.width, self.height, bx, by);
+                    if distance <= skip_threshold {
+                        self.writer.write_all(&[CMD_SKIP])?;
+                        self.skipped_blocks += 1;
+                        continue;
+                    }
+                }
+
+                let spread = block_spread(&current, self.width, self.height, bx, by);
+                if spread <= fill_threshold {
+                    let mean = block_mean(&current, self.width, self.height, bx, by);
+                    self.writer.write_all(&[CMD_FILL])?;
+                    self.writer.write_all(&mean.to_le_bytes())?;
+                    self.filled_blocks += 1;
+                    continue;
+                }
+
+                if let Some((color0, color1, mask)) =
+                    two_color_cluster(&current, self.width, self.height, bx, by, fill_threshold)
+                {
+                    self.writer.write_all(&[CMD_TWO_COLOR])?;
+                    self.writer.write_all(&color0.to_le_bytes())?;
+                    self.writer.write_all(&color1.to_le_bytes())?;
+                    self.writer.write_all(&mask.to_le_bytes())?;
+                    self.two_color_blocks += 1;
+                    continue;
+                }
+
+                self.writer.write_all(&[CMD_FOUR_QUADRANT])?;
+                write_four_quadrants(&mut self.writer, &current, self.width, self.height, bx, by)?;
+                self.four_quadrant_blocks += 1;
+            }
+        }
+
+        self.previous_frame = Some(current);
+        Ok(())
+    }
+
+    /// Flush the output file and log the final block-kind breakdown
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush()?;
+        let total =
+            self.skipped_blocks + self.filled_blocks + self.two_color_blocks + self.four_quadrant_blocks;
+        log::info!(
+            "MS Video 1 encoder: {} blocks total - {} skipped, {} filled, {} two-color, {} four-quadrant ({:.1}% not re-sent)",
+            total,
+            self.skipped_blocks,
+            self.filled_blocks,
+            self.two_color_blocks,
+            self.four_quadrant_blocks,
+            100.0 * self.skipped_blocks as f64 / total.max(1) as f64
+        );
+        Ok(())
+    }
+}
+
+fn pixel_at(data: &[Rgb555], width: usize, height: usize, x: usize, y: usize) -> Rgb555 {
+    if x < width && y < height {
+        data[y * width + x]
+    } else {
+        0
+    }
+}
+
+/// Sum over the block's 16 pixels of the squared per-channel RGB555 distance
+/// between the same position in two frames
+fn block_distance(prev: &[Rgb555], curr: &[Rgb555], width: usize, height: usize, bx: usize, by: usize) -> u32 {
+    let mut distance = 0u32;
+    for dy in 0..BLOCK_SIZE {
+        let y = by * BLOCK_SIZE + dy;
+        for dx in 0..BLOCK_SIZE {
+            let x = bx * BLOCK_SIZE + dx;
+            let p = pixel_at(prev, width, height, x, y);
+            let c = pixel_at(curr, width, height, x, y);
+            let (pr, pg, pb) = rgb555_channels(p);
+            let (cr, cg, cb) = rgb555_channels(c);
+            distance += (pr as i32 - cr as i32).pow(2) as u32;
+            distance += (pg as i32 - cg as i32).pow(2) as u32;
+            distance += (pb as i32 - cb as i32).pow(2) as u32;
+        }
+    }
+    distance
+}
+
+fn block_mean(data: &[Rgb555], width: usize, height: usize, bx: usize, by: usize) -> Rgb555 {
+    let (mut sum_r, mut sum_g, mut sum_b, mut count) = (0u32, 0u32, 0u32, 0u32);
+    for dy in 0..BLOCK_SIZE {
+        let y = by * BLOCK_SIZE + dy;
+        for dx in 0..BLOCK_SIZE {
+            let x = bx * BLOCK_SIZE + dx;
+            let (r, g, b) = rgb555_channels(pixel_at(data, width, height, x, y));
+            sum_r += r as u32;
+            sum_g += g as u32;
+            sum_b += b as u32;
+            count += 1;
+        }
+    }
+    let r = (sum_r / count.max(1)) as u16;
+    let g = (sum_g / count.max(1)) as u16;
+    let b = (sum_b / count.max(1)) as u16;
+    (r << 10) | (g << 5) | b
+}
+
+/// Greatest squared-channel distance from any pixel in the block to the
+/// block's mean color
+fn block_spread(data: &[Rgb555], width: usize, height: usize, bx: usize, by: usize) -> u32 {
+    let mean = block_mean(data, width, height, bx, by);
+    let (mr, mg, mb) = rgb555_channels(mean);
+    let mut max_distance = 0u32;
+    for dy in 0..BLOCK_SIZE {
+        let y = by * BLOCK_SIZE + dy;
+        for dx in 0..BLOCK_SIZE {
+            let x = bx * BLOCK_SIZE + dx;
+            let (r, g, b) = rgb555_channels(pixel_at(data, width, height, x, y));
+            let d = (r as i32 - mr as i32).pow(2) as u32
+                + (g as i32 - mg as i32).pow(2) as u32
+                + (b as i32 - mb as i32).pow(2) as u32;
+            max_distance = max_distance.max(d);
+        }
+    }
+    max_distance
+}
+
+/// Split the block's 16 pixels into two clusters by thresholding on luma
+/// against the block's mean luma, and return each cluster's mean color plus
+/// a 16-bit selector mask (bit i set means pixel i took `color1`). Returns
+/// `None` when either cluster is empty or the two-color fit is poor (the
+/// caller falls back to four 2x2 sub-blocks).
+fn two_color_cluster(
+    data: &[Rgb555],
+    width: usize,
+    height: usize,
+    bx: usize,
+    by: usize,
+    fill_threshold: u32,
+) -> Option<(Rgb555, Rgb555, u16)> {
+    let mut pixels = [0u16; BLOCK_SIZE * BLOCK_SIZE];
+    let mut idx = 0;
+    for dy in 0..BLOCK_SIZE {
+        let y = by * BLOCK_SIZE + dy;
+        for dx in 0..BLOCK_SIZE {
+            let x = bx * BLOCK_SIZE + dx;
+            pixels[idx] = pixel_at(data, width, height, x, y);
+            idx += 1;
+        }
+    }
+
+    let mean_luma: u32 = pixels.iter().map(|&p| luma(p)).sum::<u32>() / pixels.len() as u32;
+
+    let mut mask = 0u16;
+    let (mut sum0_r, mut sum0_g, mut sum0_b, mut count0) = (0u32, 0u32, 0u32, 0u32);
+    let (mut sum1_r, mut sum1_g, mut sum1_b, mut count1) = (0u32, 0u32, 0u32, 0u32);
+
+    for (i, &p) in pixels.iter().enumerate() {
+        let (r, g, b) = rgb555_channels(p);
+        if luma(p) >= mean_luma {
+            mask |= 1 << i;
+            sum1_r += r as u32;
+            sum1_g += g as u32;
+            sum1_b += b as u32;
+            count1 += 1;
+        } else {
+            sum0_r += r as u32;
+            sum0_g += g as u32;
+            sum0_b += b as u32;
+            count0 += 1;
+        }
+    }
+
+    if count0 == 0 || count1 == 0 {
+        return None;
+    }
+
+    let color0 = (((sum0_r / count0) as u16) << 10) | (((sum0_g / count0) as u16) << 5) | ((sum0_b / count0) as u16);
+    let color1 = (((sum1_r / count1) as u16) << 10) | (((sum1_g / count1) as u16) << 5) | ((sum1_b / count1) as u16);
+
+    let mut max_error = 0u32;
+    for (i, &p) in pixels.iter().enumerate() {
+        let assigned = if mask & (1 << i) != 0 { color1 } else { color0 };
+        let (pr, pg, pb) = rgb555_channels(p);
+        let (ar, ag, ab) = rgb555_channels(assigned);
+        let error = (pr as i32 - ar as i32).pow(2) as u32
+            + (pg as i32 - ag as i32).pow(2) as u32
+            + (pb as i32 - ab as i32).pow(2) as u32;
+        max_error = max_error.max(error);
+    }
+
+    if max_error > fill_threshold * 4 {
+        return None;
+    }
+
+    Some((color0, color1, mask))
+}
+
+/// Write the 4x4 block as four independent 2x2 sub-blocks, each with its own
+/// two-color-plus-4-bit-mask encoding - the bottom of the fallback chain, so
+/// unlike `two_color_cluster` this never rejects the fit.
+fn write_four_quadrants(
+    writer: &mut impl Write,
+    data: &[Rgb555],
+    width: usize,
+    height: usize,
+    bx: usize,
+    by: usize,
+) -> Result<()> {
+    const QUADRANT_ORIGINS: [(usize, usize); 4] = [(0, 0), (2, 0), (0, 2), (2, 2)];
+
+    for (qx, qy) in QUADRANT_ORIGINS {
+        let mut pixels = [0u16; 4];
+        let mut idx = 0;
+        for dy in 0..2 {
+            let y = by * BLOCK_SIZE + qy + dy;
+            for dx in 0..2 {
+                let x = bx * BLOCK_SIZE + qx + dx;
+                pixels[idx] = pixel_at(data, width, height, x, y);
+                idx += 1;
+            }
+        }
+
+        let mean_luma: u32 = pixels.iter().map(|&p| luma(p)).sum::<u32>() / pixels.len() as u32;
+        let mut mask = 0u8;
+        let (mut sum0_r, mut sum0_g, mut sum0_b, mut count0) = (0u32, 0u32, 0u32, 0u32);
+        let (mut sum1_r, mut sum1_g, mut sum1_b, mut count1) = (0u32, 0u32, 0u32, 0u32);
+
+        for (i, &p) in pixels.iter().enumerate() {
+            let (r, g, b) = rgb555_channels(p);
+            if luma(p) >= mean_luma {
+                mask |= 1 << i;
+                sum1_r += r as u32;
+                sum1_g += g as u32;
+                sum1_b += b as u32;
+                count1 += 1;
+            } else {
+                sum0_r += r as u32;
+                sum0_g += g as u32;
+                sum0_b += b as u32;
+                count0 += 1;
+            }
+        }
+
+        let color0 = if count0 > 0 {
+            (((sum0_r / count0) as u16) << 10) | (((sum0_g / count0) as u16) << 5) | ((sum0_b / count0) as u16)
+        } else {
+            pixels[0]
+        };
+        let color1 = if count1 > 0 {
+            (((sum1_r / count1) as u16) << 10) | (((sum1_g / count1) as u16) << 5) | ((sum1_b / count1) as u16)
+        } else {
+            pixels[0]
+        };
+
+        writer.write_all(&color0.to_le_bytes())?;
+        writer.write_all(&color1.to_le_bytes())?;
+        writer.write_all(&[mask])?;
+    }
+
+    Ok(())
+}
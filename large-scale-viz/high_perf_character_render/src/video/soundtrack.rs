@@ -0,0 +1,156 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Current schema version for [`SoundtrackManifest`] JSON files.
+pub const SOUNDTRACK_MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SoundtrackManifestFile {
+    version: u32,
+    #[serde(default)]
+    soundtracks: HashMap<String, PathBuf>,
+    #[serde(default)]
+    cue_table: HashMap<String, PathBuf>,
+}
+
+/// Names a set of looping background tracks plus a lookup table of short
+/// cues, loaded from a small JSON manifest of the form:
+/// `{"version": 1, "soundtracks": {"name": "path.mp3"}, "cue_table": {"[a]-[b]": "cue.mp3"}}`.
+/// Cue keys are expected to match the same `"[from]-[to]"` format as
+/// `warp_validator::transition_key`, so a cue can be keyed directly to the
+/// reset-map transition that triggers it.
+pub struct SoundtrackManifest {
+    pub soundtracks: HashMap<String, PathBuf>,
+    pub cue_table: HashMap<String, PathBuf>,
+}
+
+impl SoundtrackManifest {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = fs::read_to_string(path.as_ref())
+            .with_context(|| format!("Failed to read soundtrack manifest {:?}", path.as_ref()))?;
+
+        let file: SoundtrackManifestFile = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse soundtrack manifest {:?}", path.as_ref()))?;
+
+        if file.version != SOUNDTRACK_MANIFEST_VERSION {
+            bail!(
+                "Unsupported soundtrack manifest version {} (expected {})",
+                file.version,
+                SOUNDTRACK_MANIFEST_VERSION
+            );
+        }
+
+        log::info!(
+            "Loaded soundtrack manifest: {} soundtrack(s), {} cue(s) from {:?}",
+            file.soundtracks.len(),
+            file.cue_table.len(),
+            path.as_ref()
+        );
+
+        Ok(Self {
+            soundtracks: file.soundtracks,
+            cue_table: file.cue_table,
+        })
+    }
+}
+
+/// A single cue firing, keyed to whichever `cue_table` entry it should play
+/// (see `SoundtrackManifest`), at `time_sec` into the rendered video.
+pub struct CueEvent {
+    pub time_sec: f32,
+    pub cue_key: String,
+}
+
+/// Mix `soundtrack_name`'s looping background track (trimmed/faded to
+/// `duration_sec`) with any `cues` whose key is present in the manifest's
+/// `cue_table`, then mux the result onto `silent_video_path`'s video stream
+/// via ffmpeg, writing the finished, ready-to-post file to `output_path`.
+/// If no soundtrack or cues resolve, `silent_video_path` is copied through
+/// unchanged so callers always end up with a single file at `output_path`.
+pub fn mux_soundtrack(
+    silent_video_path: &Path,
+    output_path: &Path,
+    manifest: Option<&SoundtrackManifest>,
+    soundtrack_name: &str,
+    cues: &[CueEvent],
+    duration_sec: f32,
+) -> Result<()> {
+    let background = manifest.and_then(|m| m.soundtracks.get(soundtrack_name));
+    let resolved_cues: Vec<(&CueEvent, &PathBuf)> = manifest
+        .map(|m| {
+            cues
+                .iter()
+                .filter_map(|cue| m.cue_table.get(&cue.cue_key).map(|path| (cue, path)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if background.is_none() && resolved_cues.is_empty() {
+        fs::copy(silent_video_path, output_path)
+            .with_context(|| format!("Failed to copy {:?} to {:?}", silent_video_path, output_path))?;
+        return Ok(());
+    }
+
+    let mut cmd = Command::new("ffmpeg");
+    cmd.arg("-y").arg("-i").arg(silent_video_path);
+
+    if let Some(track) = background {
+        cmd.args(&["-stream_loop", "-1", "-i"]).arg(track);
+    }
+    for (_, path) in &resolved_cues {
+        cmd.arg("-i").arg(path);
+    }
+
+    // Build a filter graph that trims/fades the background to the video's
+    // duration, delays each cue to its firing time, then sums everything
+    // down to one audio stream with amix.
+    let mut filter_parts = Vec::new();
+    let mut mix_labels = Vec::new();
+    let mut next_input = 1;
+
+    if background.is_some() {
+        let fade_start = (duration_sec - 1.0).max(0.0);
+        filter_parts.push(format!(
+            "[{next_input}:a]atrim=0:{duration_sec},afade=t=out:st={fade_start}:d=1.0[bg]",
+        ));
+        mix_labels.push("[bg]".to_string());
+        next_input += 1;
+    }
+
+    for (cue_index, (cue, _)) in resolved_cues.iter().enumerate() {
+        let delay_ms = (cue.time_sec * 1000.0).max(0.0).round() as u64;
+        let label = format!("cue{cue_index}");
+        filter_parts.push(format!("[{next_input}:a]adelay={delay_ms}|{delay_ms}[{label}]"));
+        mix_labels.push(format!("[{label}]"));
+        next_input += 1;
+    }
+
+    filter_parts.push(format!(
+        "{}amix=inputs={}:duration=first:dropout_transition=0[mixed]",
+        mix_labels.join(""),
+        mix_labels.len()
+    ));
+
+    cmd.arg("-filter_complex").arg(filter_parts.join(";"));
+    cmd.args(&["-map", "0:v", "-map", "[mixed]"]);
+    cmd.args(&["-c:v", "copy", "-c:a", "aac", "-shortest"]);
+    cmd.arg(output_path);
+
+    log::info!(
+        "Muxing soundtrack (background={}, cues={}) into {:?}",
+        background.is_some(),
+        resolved_cues.len(),
+        output_path
+    );
+
+    let status = cmd.status().context("Failed to run ffmpeg for soundtrack muxing")?;
+    if !status.success() {
+        bail!("ffmpeg soundtrack mux exited with {:?}", status);
+    }
+
+    Ok(())
+}
@@ -1,6 +1,10 @@
+use anyhow::{Context, Result};
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 
 /// Represents a position in the game world as (x, y, map_id)
 pub type Position = (u8, u8, u8);
@@ -104,6 +108,83 @@ pub fn valid_coordinate_pair(a: [u8; 3], b: [u8; 3]) -> bool {
     }
 }
 
+/// Builds the string key `valid_coordinate_pair_v2`/`WARP_V2` are keyed by,
+/// matching the `"[{from_map_id}]-[{to_map_id}]"` format used when extracting runs
+pub fn transition_key(from_map_id: u8, to_map_id: u8) -> String {
+    format!("[{}]-[{}]", from_map_id, to_map_id)
+}
+
+/// Data-driven replacement for the literal `starting_maps` /
+/// `starting_and_adjacent_maps` / `map_id_order_required` arrays a route
+/// extractor used to hardcode for exactly one Kanto route. Loaded from a
+/// `--route-spec` JSON file, this lets a new route be defined as data
+/// instead of editing and recompiling the extractor binary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteGraph {
+    /// Map ids a run is allowed to start from.
+    pub starting_maps: Vec<u8>,
+    /// Map ids a transition into a `starting_maps` id is allowed to come
+    /// from without forcing a split (i.e. the starting area's own adjacent
+    /// map ids).
+    pub starting_and_adjacent_maps: Vec<u8>,
+    /// Ordered list of map ids a run is expected to progress through.
+    /// `progress_idx` looks up a map id's position in this list.
+    pub map_id_order: Vec<u8>,
+    /// `map_id_order` index a run's progress tracking starts at.
+    pub progress_init_idx: usize,
+    /// A backtrack counts as legal only to a `map_id_order` index strictly
+    /// above this one - going back to this index or further is not a legal
+    /// backtrack.
+    pub backtrack_floor_idx: usize,
+}
+
+impl RouteGraph {
+    /// Load a route graph from a JSON file (see `--route-spec`).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = fs::read_to_string(path.as_ref())
+            .context("Failed to read route spec")?;
+
+        serde_json::from_str(&content).context("Failed to parse route spec")
+    }
+
+    /// Whether `map_id` is one of `starting_maps`.
+    pub fn is_starting_map(&self, map_id: u8) -> bool {
+        self.starting_maps.contains(&map_id)
+    }
+
+    /// Whether `map_id` is one of `starting_and_adjacent_maps`.
+    pub fn is_starting_or_adjacent(&self, map_id: u8) -> bool {
+        self.starting_and_adjacent_maps.contains(&map_id)
+    }
+
+    /// `map_id`'s index in `map_id_order`, if it's part of the tracked route.
+    pub fn progress_idx(&self, map_id: u8) -> Option<usize> {
+        self.map_id_order.iter().position(|&x| x == map_id)
+    }
+
+    /// Evaluates a transition into `current_map_id` against a run's current
+    /// `progress_idx` (advancing it in place on a legal forward step), and
+    /// returns `(legal_backtrack, illegal_skip_ahead)` for the caller to
+    /// fold into its own split decision.
+    pub fn evaluate_transition(&self, current_map_id: u8, progress_idx: &mut usize) -> (bool, bool) {
+        let mut legal_backtrack = false;
+        let mut illegal_skip_ahead = false;
+
+        if let Some(current_progress_idx) = self.progress_idx(current_map_id) {
+            if current_progress_idx < *progress_idx && current_progress_idx > self.backtrack_floor_idx {
+                legal_backtrack = true;
+            }
+            if (current_progress_idx as i64) - (*progress_idx as i64) > 1 {
+                illegal_skip_ahead = true;
+            } else {
+                *progress_idx = usize::max(*progress_idx, current_progress_idx);
+            }
+        }
+
+        (legal_backtrack, illegal_skip_ahead)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,4 +225,48 @@ mod tests {
         assert!(!valid_coordinate_pair(from, to),
                 "Should not be valid with wrong destination");
     }
+
+    #[test]
+    fn test_transition_key_format() {
+        assert_eq!(transition_key(0, 37), "[0]-[37]");
+    }
+
+    fn test_route_graph() -> RouteGraph {
+        RouteGraph {
+            starting_maps: vec![0, 37, 40, 38, 39],
+            starting_and_adjacent_maps: vec![0, 37, 40, 39, 38, 12, 32],
+            map_id_order: vec![0, 37, 40, 38, 39, 12, 1, 13],
+            progress_init_idx: 5,
+            backtrack_floor_idx: 5,
+        }
+    }
+
+    #[test]
+    fn test_route_graph_forward_progress() {
+        let graph = test_route_graph();
+        let mut progress_idx = graph.progress_init_idx;
+
+        let (legal_backtrack, illegal_skip_ahead) = graph.evaluate_transition(1, &mut progress_idx);
+        assert!(!legal_backtrack);
+        assert!(!illegal_skip_ahead);
+        assert_eq!(progress_idx, 6);
+    }
+
+    #[test]
+    fn test_route_graph_illegal_skip_ahead() {
+        let graph = test_route_graph();
+        let mut progress_idx = graph.progress_init_idx;
+
+        let (_, illegal_skip_ahead) = graph.evaluate_transition(13, &mut progress_idx);
+        assert!(illegal_skip_ahead);
+    }
+
+    #[test]
+    fn test_route_graph_legal_backtrack() {
+        let graph = test_route_graph();
+        let mut progress_idx = 7;
+
+        let (legal_backtrack, _) = graph.evaluate_transition(1, &mut progress_idx);
+        assert!(legal_backtrack);
+    }
 }
@@ -0,0 +1,313 @@
+//! Browser entry point: drives the same `Vertex`/`SpriteInstance`/bind-group
+//! setup the headless `src/bin` tools use, but against a `wgpu::Surface`
+//! backed by an HTML `<canvas>` instead of `GpuContext`'s offscreen render
+//! texture, and presents every frame from a `requestAnimationFrame` loop
+//! instead of reading pixels back for `video::*` to encode. The WGSL shader
+//! (`rendering::pipeline`) is unchanged - only device/surface creation and
+//! the render loop differ from the native headless path.
+//!
+//! Gated behind `target_arch = "wasm32"`; native builds (`main.rs`, every
+//! `src/bin` tool) are unaffected.
+#![cfg(target_arch = "wasm32")]
+
+use crate::rendering::gpu_context::resolve_sample_count;
+use crate::rendering::{SpriteInstance, SpriteRenderer, TextureAtlas};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// MSAA sample count requested for the canvas surface, downgraded to
+/// whatever the adapter actually supports (or 1, disabling MSAA) by
+/// `resolve_sample_count` - same policy `GpuContext` uses for the native
+/// offscreen path.
+const REQUESTED_SAMPLE_COUNT: u32 = 4;
+
+/// Preferred surface format when the browser/backend doesn't report one -
+/// the common native swapchain format outside of sRGB-only backends.
+const FALLBACK_SURFACE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8Unorm;
+
+/// Owns the wgpu device/surface/renderer for one `<canvas>` and the sprite
+/// positions/camera it's currently drawing. Constructed once via `init`, then
+/// driven by repeated `render_frame` calls from a `requestAnimationFrame`
+/// loop (see `start_viewer`).
+#[wasm_bindgen]
+pub struct WebViewer {
+    surface: wgpu::Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    surface_format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    sample_count: u32,
+    /// Multisampled color target drawn into when `sample_count > 1`,
+    /// resolved into the surface's current texture by the render pass's
+    /// `resolve_target` (see `color_attachment_target`). Recreated by
+    /// `resize` whenever the canvas size changes.
+    msaa_texture_view: Option<wgpu::TextureView>,
+    renderer: SpriteRenderer,
+    _texture_atlas: TextureAtlas,
+    camera: [f32; 2],
+    sprites: Vec<SpriteInstance>,
+}
+
+#[wasm_bindgen]
+impl WebViewer {
+    /// Initializes wgpu against `<canvas id="{canvas_id}">`, requesting only
+    /// the WebGPU backend (no GL/WebGL2 backend is requested, so this returns
+    /// an error on browsers without WebGPU support), and builds the same `SpriteRenderer`/`TextureAtlas` pair the headless
+    /// tools use, decoding `sprite_sheet_bytes` in memory instead of reading
+    /// it from disk (see `TextureAtlas::load_from_bytes`).
+    #[wasm_bindgen(js_name = init)]
+    pub async fn init(canvas_id: String, sprite_sheet_bytes: Vec<u8>) -> Result<WebViewer, JsValue> {
+        console_error_panic_hook::set_once();
+
+        let window = web_sys::window().ok_or_else(|| JsValue::from_str("no window"))?;
+        let document = window.document().ok_or_else(|| JsValue::from_str("no document"))?;
+        let canvas = document
+            .get_element_by_id(&canvas_id)
+            .ok_or_else(|| JsValue::from_str("canvas element not found"))?
+            .dyn_into::<web_sys::HtmlCanvasElement>()?;
+        let width = canvas.width();
+        let height = canvas.height();
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::BROWSER_WEBGPU,
+            ..Default::default()
+        });
+        let surface = instance
+            .create_surface(wgpu::SurfaceTarget::Canvas(canvas))
+            .map_err(|e| JsValue::from_str(&format!("failed to create surface: {e}")))?;
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: Some(&surface),
+                force_fallback_adapter: false,
+            })
+            .await
+            .ok_or_else(|| JsValue::from_str("no suitable GPU adapter"))?;
+
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default(), None)
+            .await
+            .map_err(|e| JsValue::from_str(&format!("failed to request device: {e}")))?;
+
+        let capabilities = surface.get_capabilities(&adapter);
+        let surface_format = capabilities
+            .formats
+            .iter()
+            .copied()
+            .find(|format| !format.is_srgb())
+            .unwrap_or(FALLBACK_SURFACE_FORMAT);
+
+        surface.configure(
+            &device,
+            &wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format: surface_format,
+                width,
+                height,
+                present_mode: wgpu::PresentMode::Fifo,
+                alpha_mode: capabilities.alpha_modes[0],
+                view_formats: vec![],
+                desired_maximum_frame_latency: 2,
+            },
+        );
+
+        let format_features = adapter.get_texture_format_features(surface_format);
+        let sample_count = resolve_sample_count(format_features.flags, REQUESTED_SAMPLE_COUNT);
+        let msaa_texture_view = Self::create_msaa_texture_view(&device, surface_format, width, height, sample_count);
+
+        let texture_atlas = TextureAtlas::load_from_bytes(&device, &queue, &[&sprite_sheet_bytes])
+            .map_err(|e| JsValue::from_str(&format!("failed to decode sprite sheet: {e}")))?;
+
+        let renderer =
+            SpriteRenderer::new(&device, &queue, &[&texture_atlas], width, height, 4096, sample_count)
+                .map_err(|e| JsValue::from_str(&format!("failed to create renderer: {e}")))?;
+
+        Ok(Self {
+            surface,
+            device,
+            queue,
+            surface_format,
+            width,
+            height,
+            sample_count,
+            msaa_texture_view,
+            renderer,
+            _texture_atlas: texture_atlas,
+            camera: [0.0, 0.0],
+            sprites: Vec::new(),
+        })
+    }
+
+    /// Allocates the multisampled color target `render_frame` draws into
+    /// when `sample_count > 1`, or returns `None` (MSAA disabled/unsupported)
+    /// otherwise. Multisampled textures can't be presented directly, so this
+    /// is never bound as the surface view itself - only as the pass's `view`,
+    /// resolved into the real surface texture via `resolve_target`.
+    fn create_msaa_texture_view(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Option<wgpu::TextureView> {
+        if sample_count <= 1 {
+            return None;
+        }
+
+        let msaa_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("WebViewer MSAA Texture"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        Some(msaa_texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+
+    /// The view + resolve target a render pass color attachment should use
+    /// this frame, mirroring `GpuContext::color_attachment_target`: the
+    /// multisampled view with `surface_view` as its resolve target when MSAA
+    /// is enabled, or `surface_view` directly otherwise. A free function
+    /// (not a `&self` method) so callers can borrow `self.renderer` mutably
+    /// alongside the `self.msaa_texture_view` borrow this returns.
+    fn color_attachment_target<'a>(
+        msaa_texture_view: Option<&'a wgpu::TextureView>,
+        surface_view: &'a wgpu::TextureView,
+    ) -> (&'a wgpu::TextureView, Option<&'a wgpu::TextureView>) {
+        match msaa_texture_view {
+            Some(msaa_view) => (msaa_view, Some(surface_view)),
+            None => (surface_view, None),
+        }
+    }
+
+    /// Reconfigure the surface and recreate the MSAA texture for a new
+    /// canvas size - call this from the page's canvas-resize handler before
+    /// the next `render_frame`.
+    #[wasm_bindgen(js_name = resize)]
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == self.width && height == self.height {
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+
+        self.surface.configure(
+            &self.device,
+            &wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format: self.surface_format,
+                width,
+                height,
+                present_mode: wgpu::PresentMode::Fifo,
+                alpha_mode: wgpu::CompositeAlphaMode::Auto,
+                view_formats: vec![],
+                desired_maximum_frame_latency: 2,
+            },
+        );
+
+        self.msaa_texture_view =
+            Self::create_msaa_texture_view(&self.device, self.surface_format, width, height, self.sample_count);
+    }
+
+    /// Pan the camera by a pixel delta, e.g. from a canvas drag handler.
+    #[wasm_bindgen(js_name = pan)]
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.camera[0] += dx;
+        self.camera[1] += dy;
+    }
+
+    /// Replace the sprites drawn each frame. Takes a raw `SpriteInstance`
+    /// array reinterpreted from bytes (`bytemuck::cast_slice`, the same way
+    /// every instance buffer upload in this crate already moves
+    /// `SpriteInstance`s around) rather than marshalling a `Vec<SpriteInstance>`
+    /// across the wasm boundary - the caller's JS is expected to build this
+    /// buffer directly (e.g. from a decoded run-bundle/parquet chunk) and
+    /// hand it over as a `Uint8Array`.
+    #[wasm_bindgen(js_name = setSprites)]
+    pub fn set_sprites(&mut self, sprite_bytes: &[u8]) {
+        self.sprites = bytemuck::cast_slice(sprite_bytes).to_vec();
+    }
+
+    /// Render and present one frame. Call this from a `requestAnimationFrame`
+    /// callback (see `start_viewer`) rather than on a timer, so the browser
+    /// can skip frames when the tab isn't visible.
+    #[wasm_bindgen(js_name = renderFrame)]
+    pub fn render_frame(&mut self) -> Result<(), JsValue> {
+        let frame = self
+            .surface
+            .get_current_texture()
+            .map_err(|e| JsValue::from_str(&format!("failed to acquire frame: {e}")))?;
+        let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let (color_target, resolve_target) = Self::color_attachment_target(self.msaa_texture_view.as_ref(), &view);
+
+        self.renderer
+            .render(&self.device, &self.queue, color_target, resolve_target, &self.sprites, true)
+            .map_err(|e| JsValue::from_str(&format!("render failed: {e}")))?;
+
+        frame.present();
+        Ok(())
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+/// JS-callable entry point: initializes a `WebViewer` against `canvas_id` and
+/// drives it from a `requestAnimationFrame` loop for the lifetime of the
+/// page. Parquet frames aren't fetched here - the page is expected to fetch
+/// and decode them (or stream them incrementally) and push positions in via
+/// `WebViewer::set_sprites`/`pan` from its own JS, the same division of
+/// responsibility the headless tools have between parquet decoding and
+/// `SpriteRenderer`.
+#[wasm_bindgen(js_name = startViewer)]
+pub async fn start_viewer(canvas_id: String, sprite_sheet_bytes: Vec<u8>) -> Result<(), JsValue> {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let viewer = Rc::new(RefCell::new(WebViewer::init(canvas_id, sprite_sheet_bytes).await?));
+
+    let window = web_sys::window().ok_or_else(|| JsValue::from_str("no window"))?;
+    let frame_callback: Rc<RefCell<Option<Closure<dyn FnMut()>>>> = Rc::new(RefCell::new(None));
+    let frame_callback_slot = frame_callback.clone();
+
+    *frame_callback_slot.borrow_mut() = Some(Closure::new(move || {
+        if let Err(e) = viewer.borrow_mut().render_frame() {
+            log::error!("WebViewer::render_frame failed: {:?}", e);
+        }
+        let window = web_sys::window().expect("no window");
+        window
+            .request_animation_frame(
+                frame_callback
+                    .borrow()
+                    .as_ref()
+                    .expect("frame_callback set before first request_animation_frame")
+                    .as_ref()
+                    .unchecked_ref(),
+            )
+            .expect("request_animation_frame failed");
+    }));
+
+    window
+        .request_animation_frame(
+            frame_callback_slot
+                .borrow()
+                .as_ref()
+                .expect("frame_callback just set above")
+                .as_ref()
+                .unchecked_ref(),
+        )
+        .map_err(|_| JsValue::from_str("request_animation_frame failed"))?;
+
+    Ok(())
+}
@@ -1,16 +1,244 @@
 use std::collections::HashMap;
+use std::error::Error;
 use std::fs::File;
-use std::io::{self};
-use std::path::Path;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
 
 use chrono::{DateTime, NaiveDateTime, Utc};
-use std::str::FromStr;
 
-use csv::ReaderBuilder;
+use clap::{Args, Parser, Subcommand};
+use crossbeam_queue::ArrayQueue;
+use csv::{ReaderBuilder, Writer};
+use image::{ImageBuffer, Rgb};
 use indicatif::ProgressBar;
 use serde::Deserialize;
 use serde_json::Value;
-use image::{ImageBuffer, Rgb};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Coordinate log tools: heatmap rendering and user extraction", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Accumulate per-user coordinate counts into a CSV
+    CountUsers(CountUsersArgs),
+    /// Accumulate coordinates into decaying heatmap layers and save periodic images
+    RenderHeatmap(RenderHeatmapArgs),
+}
+
+#[derive(Args, Debug)]
+struct CountUsersArgs {
+    /// Input CSV path (defaults to stdin)
+    #[arg(long)]
+    input: Option<PathBuf>,
+
+    /// Directory to write user_coords.csv into
+    #[arg(long, default_value = ".")]
+    output_dir: PathBuf,
+
+    /// Decompression codec for --input; auto detects from the file extension
+    #[arg(long, value_enum, default_value = "auto")]
+    compression: Compression,
+
+    /// JSON field name (under "metadata") holding the username
+    #[arg(long, default_value = "user")]
+    user_field: String,
+}
+
+#[derive(Args, Debug)]
+struct RenderHeatmapArgs {
+    /// Input CSV path (defaults to stdin)
+    #[arg(long)]
+    input: Option<PathBuf>,
+
+    /// Path to map_data.json
+    #[arg(long, default_value = "../assets/map_data.json")]
+    map_data: PathBuf,
+
+    /// Side length (in pixels) of the square heatmap grid
+    #[arg(long, default_value = "768")]
+    dim: usize,
+
+    /// Seconds of log time between saved images
+    #[arg(long, default_value = "720")]
+    save_interval_secs: u64,
+
+    /// Directory to write coord_map_<layer>_<n>.exr images into
+    #[arg(long, default_value = "images")]
+    output_dir: PathBuf,
+
+    /// Number of worker threads accumulating coordinates
+    #[arg(long, default_value = "1")]
+    threads: usize,
+
+    /// Per-layer decay multiplier applied after each save, e.g. `--decay fast=0.9`.
+    /// Repeatable. Layers not given a decay default to 1.0 (no decay).
+    #[arg(long = "decay", value_parser = parse_key_val::<f64>)]
+    decay: Vec<(String, f64)>,
+
+    /// Per-layer saturation ceiling for image intensity, e.g. `--max-count fast=262144`.
+    /// Repeatable. Layers not given a max-count default to 2^20.
+    #[arg(long = "max-count", value_parser = parse_key_val::<u64>)]
+    max_count: Vec<(String, u64)>,
+
+    /// Only accumulate rows at or after this RFC3339 timestamp
+    #[arg(long, value_parser = parse_rfc3339)]
+    start: Option<DateTime<Utc>>,
+
+    /// Stop scanning once a row's timestamp passes this RFC3339 timestamp
+    #[arg(long, value_parser = parse_rfc3339)]
+    end: Option<DateTime<Utc>>,
+
+    /// Decompression codec for --input; auto detects from the file extension
+    #[arg(long, value_enum, default_value = "auto")]
+    compression: Compression,
+
+    /// JSON field name holding the coordinate array
+    #[arg(long, default_value = "coords")]
+    coords_field: String,
+
+    /// JSON field name holding a message-wide map id. When set, --coords-field
+    /// is parsed as an array of [x, y] pairs; when unset (the legacy format),
+    /// each coordinate is a [x, y, map_id] triple and this is ignored.
+    #[arg(long)]
+    map_id_field: Option<String>,
+
+    /// Perceptual colormap for an additional 8-bit PNG saved alongside each
+    /// layer's linear EXR. `none` skips the PNG (and the timelapse manifest).
+    #[arg(long, value_enum, default_value = "none")]
+    colormap: Colormap,
+
+    /// Gamma applied to normalized intensity before colormapping, to pull
+    /// detail out of the long tail of a heavily skewed visit-count distribution
+    #[arg(long, default_value = "1.0")]
+    gamma: f64,
+}
+
+/// Decompression codec to wrap the input reader in. `Auto` picks a codec
+/// from the `--input` file extension (`.gz`, `.zst`) and falls back to no
+/// decompression when the extension doesn't match (including stdin).
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Compression {
+    Auto,
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// Open `path`, or stdin when `path` is `None`, transparently decompressing
+/// according to `compression`
+fn open_input(path: &Option<PathBuf>, compression: Compression) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    let raw: Box<dyn Read> = match path {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(io::stdin()),
+    };
+
+    let resolved = match compression {
+        Compression::Auto => detect_compression(path),
+        explicit => explicit,
+    };
+
+    match resolved {
+        Compression::Gzip => Ok(Box::new(flate2::read::MultiGzDecoder::new(raw))),
+        Compression::Zstd => Ok(Box::new(zstd::Decoder::new(raw)?)),
+        Compression::None | Compression::Auto => Ok(raw),
+    }
+}
+
+/// Palette for the optional 8-bit PNG written alongside each layer's linear
+/// EXR. `None` skips PNG output entirely; `Greyscale` is the identity mapping
+/// used by the EXR today; `Viridis`/`Inferno` are perceptual colormaps that
+/// make low-but-nonzero visit counts easier to distinguish by eye.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Colormap {
+    None,
+    Greyscale,
+    Viridis,
+    Inferno,
+}
+
+/// A handful of interpolated stops approximating matplotlib's viridis, evenly
+/// spaced over [0, 1]
+const VIRIDIS_STOPS: [[u8; 3]; 5] = [
+    [68, 1, 84],
+    [59, 82, 139],
+    [33, 145, 140],
+    [94, 201, 98],
+    [253, 231, 37],
+];
+
+/// A handful of interpolated stops approximating matplotlib's inferno,
+/// evenly spaced over [0, 1]
+const INFERNO_STOPS: [[u8; 3]; 5] = [
+    [0, 0, 4],
+    [87, 16, 110],
+    [188, 55, 84],
+    [249, 142, 9],
+    [252, 255, 164],
+];
+
+/// Map a normalized intensity in [0, 1] to an RGB color under `colormap`
+fn apply_colormap(colormap: Colormap, intensity: f32) -> [u8; 3] {
+    let t = intensity.clamp(0.0, 1.0);
+    match colormap {
+        Colormap::None | Colormap::Greyscale => {
+            let v = (t * 255.0).round() as u8;
+            [v, v, v]
+        }
+        Colormap::Viridis => lerp_stops(&VIRIDIS_STOPS, t),
+        Colormap::Inferno => lerp_stops(&INFERNO_STOPS, t),
+    }
+}
+
+/// Linearly interpolate between the two stops surrounding `t` in an evenly
+/// spaced `stops` table
+fn lerp_stops(stops: &[[u8; 3]; 5], t: f32) -> [u8; 3] {
+    let scaled = t * (stops.len() - 1) as f32;
+    let i = (scaled.floor() as usize).min(stops.len() - 2);
+    let frac = scaled - i as f32;
+    let a = stops[i];
+    let b = stops[i + 1];
+    std::array::from_fn(|c| (a[c] as f32 + (b[c] as f32 - a[c] as f32) * frac).round() as u8)
+}
+
+/// Infer a compression codec from `path`'s extension; `None` (no codec) for
+/// stdin or an unrecognized extension
+fn detect_compression(path: &Option<PathBuf>) -> Compression {
+    match path.as_ref().and_then(|p| p.extension()).and_then(|ext| ext.to_str()) {
+        Some("gz") => Compression::Gzip,
+        Some("zst") => Compression::Zstd,
+        _ => Compression::None,
+    }
+}
+
+/// Parse an RFC3339 timestamp CLI argument, used for `--start`/`--end`
+fn parse_rfc3339(s: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|e| format!("invalid RFC3339 timestamp `{s}`: {e}"))
+}
+
+/// Parse a `KEY=VALUE` CLI argument into a tuple, used for the repeatable
+/// `--decay`/`--max-count` flags
+fn parse_key_val<T>(s: &str) -> Result<(String, T), String>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid KEY=VALUE: no `=` found in `{s}`"))?;
+    let value = value
+        .parse::<T>()
+        .map_err(|e| format!("invalid value for layer `{key}`: {e}"))?;
+    Ok((key.to_string(), value))
+}
 
 #[derive(Debug, Deserialize)]
 struct Record {
@@ -29,12 +257,294 @@ struct Region {
     coordinates: [i64; 2],
 }
 
-const DIM: usize = 768;
-const SAVE_INTERVAL_SECS: u64 = 12 * 60; // 12 minutes in seconds
+const QUEUE_CAPACITY: usize = 4096;
+
+/// Why a named field couldn't be pulled out of a JSON message. Kept distinct
+/// from a generic parse failure so the final summary can tell "this row just
+/// doesn't have the field" (expected, e.g. heartbeat/control messages) apart
+/// from "the field is there but isn't shaped the way we expect" (a real data
+/// problem worth calling out).
+enum FieldError {
+    Missing,
+    Malformed,
+}
+
+/// Look up `field` on a JSON object, distinguishing "not present" from
+/// later deserialization failures
+fn get_field<'a>(value: &'a Value, field: &str) -> Result<&'a Value, FieldError> {
+    value.get(field).ok_or(FieldError::Missing)
+}
+
+/// Pull the coordinate list out of a parsed message according to the
+/// configured schema: legacy `[x, y, map_id]` triples under `coords_field`
+/// when `map_id_field` is unset, or `[x, y]` pairs under `coords_field` plus
+/// a single message-wide map id under `map_id_field` when it is set.
+fn extract_coords(
+    message: &Value,
+    coords_field: &str,
+    map_id_field: &Option<String>,
+) -> Result<Vec<(i64, i64, i64)>, FieldError> {
+    let coords_value = get_field(message, coords_field)?;
+
+    match map_id_field {
+        Some(map_id_field) => {
+            let pairs: Vec<[i64; 2]> =
+                serde_json::from_value(coords_value.clone()).map_err(|_| FieldError::Malformed)?;
+            let map_id_value = get_field(message, map_id_field)?;
+            let map_id: i64 =
+                serde_json::from_value(map_id_value.clone()).map_err(|_| FieldError::Malformed)?;
+            Ok(pairs.into_iter().map(|[x, y]| (x, y, map_id)).collect())
+        }
+        None => {
+            let triples: Vec<[i64; 3]> =
+                serde_json::from_value(coords_value.clone()).map_err(|_| FieldError::Malformed)?;
+            Ok(triples.into_iter().map(|[x, y, map_id]| (x, y, map_id)).collect())
+        }
+    }
+}
+
+/// Pull the username out of a parsed message's `metadata` object, under the
+/// configured `user_field` name
+fn extract_user(message: &Value, user_field: &str) -> Result<String, FieldError> {
+    let metadata = get_field(message, "metadata")?;
+    let user_value = get_field(metadata, user_field)?;
+    serde_json::from_value(user_value.clone()).map_err(|_| FieldError::Malformed)
+}
+
+/// The historical fixed layer set, used whenever `--decay`/`--max-count`
+/// aren't given, so existing invocations keep working unchanged
+const DEFAULT_LAYERS: &[(&str, f64, u64)] = &[
+    ("full", 1.0, 1 << 26),
+    ("medium", 0.99, 1 << 22),
+    ("fast", 0.9, 1 << 18),
+    ("extra_fast", 0.5, 1 << 16),
+];
+
+const DEFAULT_DECAY: f64 = 1.0;
+const DEFAULT_MAX_COUNT: u64 = 1 << 20;
+
+#[derive(Clone, Copy)]
+struct LayerConfig {
+    decay: f64,
+    max_count: u64,
+}
+
+/// Build the layer configuration from `--decay`/`--max-count`, falling back
+/// to `DEFAULT_LAYERS` when neither flag is given
+fn build_layers(decay: &[(String, f64)], max_count: &[(String, u64)]) -> HashMap<String, LayerConfig> {
+    if decay.is_empty() && max_count.is_empty() {
+        return DEFAULT_LAYERS
+            .iter()
+            .map(|&(name, decay, max_count)| (name.to_string(), LayerConfig { decay, max_count }))
+            .collect();
+    }
+
+    let mut layers: HashMap<String, LayerConfig> = HashMap::new();
+    for (name, _) in decay {
+        layers.entry(name.clone()).or_insert(LayerConfig {
+            decay: DEFAULT_DECAY,
+            max_count: DEFAULT_MAX_COUNT,
+        });
+    }
+    for (name, _) in max_count {
+        layers.entry(name.clone()).or_insert(LayerConfig {
+            decay: DEFAULT_DECAY,
+            max_count: DEFAULT_MAX_COUNT,
+        });
+    }
+    for (name, value) in decay {
+        layers.get_mut(name).unwrap().decay = *value;
+    }
+    for (name, value) in max_count {
+        layers.get_mut(name).unwrap().max_count = *value;
+    }
+    layers
+}
+
+/// Per-layer DIM×DIM count grids, keyed by layer name
+struct LayerGrids {
+    grids: HashMap<String, Vec<Vec<u64>>>,
+}
+
+impl LayerGrids {
+    fn new(dim: usize, layer_names: impl Iterator<Item = String>) -> Self {
+        Self {
+            grids: layer_names.map(|name| (name, vec![vec![0u64; dim]; dim])).collect(),
+        }
+    }
+
+    fn record(&mut self, x: usize, y: usize) {
+        for grid in self.grids.values_mut() {
+            grid[x][y] += 1;
+        }
+    }
+
+    /// Element-wise add `other` into `self`, then zero `other` out so the
+    /// caller's thread-local grids can keep accumulating from a clean slate
+    fn merge_from(&mut self, other: &mut LayerGrids) {
+        for (name, dst) in self.grids.iter_mut() {
+            let src = other.grids.get_mut(name).expect("layer sets must match across grids");
+            for (d, s) in dst.iter_mut().flatten().zip(src.iter_mut().flatten()) {
+                *d += *s;
+                *s = 0;
+            }
+        }
+    }
+
+    fn apply_decay(&mut self, layers: &HashMap<String, LayerConfig>) {
+        for (name, grid) in self.grids.iter_mut() {
+            let decay = layers[name].decay;
+            if decay == 1.0 {
+                continue;
+            }
+            for pix in grid.iter_mut().flatten() {
+                *pix = ((*pix as f64) * decay) as u64;
+            }
+        }
+    }
+}
+
+/// A single queued row's worth of work. Save boundaries and shutdown aren't
+/// pushed as queue items (a shared queue can't guarantee one marker reaches
+/// each worker); instead they're broadcast via `boundary_generation` and
+/// `shutdown_requested` below, which every worker polls once it has drained
+/// the queue dry.
+struct QueueItem {
+    message: String,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::CountUsers(args) => run_count_users(args),
+        Command::RenderHeatmap(args) => run_render_heatmap(args),
+    }
+}
+
+fn run_count_users(args: CountUsersArgs) -> Result<(), Box<dyn Error>> {
+    const DISPLAY_INTERVAL: u64 = 20_000;
+    const OUTPUT_FILENAME: &str = "user_coords.csv";
+
+    let reader = open_input(&args.input, args.compression)?;
+
+    let mut row_count: u64 = 0;
+    let mut failed_rows: u64 = 0;
+    let mut missing_field_rows: u64 = 0;
+    let mut malformed_rows: u64 = 0;
+    let mut user_coord_counts: HashMap<String, u64> = HashMap::new();
+
+    let progress_bar = ProgressBar::new_spinner();
+    progress_bar.set_message(format!("rows: {} unique users: {} failed: {}", row_count, 0, failed_rows));
+
+    let mut csv_reader = ReaderBuilder::new().has_headers(true).from_reader(reader);
+
+    for result in csv_reader.deserialize() {
+        row_count += 1;
+
+        match result {
+            Ok(record) => {
+                let record: Record = record;
+
+                match serde_json::from_str::<Value>(&record.message) {
+                    Ok(json_value) => match extract_user(&json_value, &args.user_field) {
+                        Ok(username) => {
+                            let count = user_coord_counts.entry(username).or_insert(0);
+                            *count += 1;
+                        }
+                        Err(FieldError::Missing) => {
+                            failed_rows += 1;
+                            missing_field_rows += 1;
+                        }
+                        Err(FieldError::Malformed) => {
+                            failed_rows += 1;
+                            malformed_rows += 1;
+                        }
+                    },
+                    Err(_) => {
+                        failed_rows += 1; // Failed JSON parsing
+                        malformed_rows += 1;
+                    }
+                }
+            }
+            Err(_) => {
+                failed_rows += 1; // Failed CSV record deserialization
+            }
+        }
+
+        if row_count % 1000 == 0 {
+            progress_bar.set_message(format!(
+                "rows: {} unique users: {} failed: {}",
+                row_count,
+                user_coord_counts.len(),
+                failed_rows
+            ));
+            progress_bar.tick();
+        }
+
+        if row_count % DISPLAY_INTERVAL == 0 {
+            eprintln!("\nCurrent unique users ({}) and counts:", user_coord_counts.len());
+            let mut sorted_users: Vec<_> = user_coord_counts.iter().collect();
+            sorted_users.sort_by_key(|(k, _)| *k);
+            for (username, count) in sorted_users {
+                eprintln!("  - {}: {}", username, count);
+            }
+            eprintln!(
+                "\nProgress: rows: {} unique users: {} failed: {}",
+                row_count,
+                user_coord_counts.len(),
+                failed_rows
+            );
+        }
+    }
+
+    progress_bar.finish_with_message(format!(
+        "Processing finished. rows: {} unique users: {} failed: {}",
+        row_count,
+        user_coord_counts.len(),
+        failed_rows
+    ));
+
+    println!("\n--- Final Statistics ---");
+    println!("Total rows processed: {}", row_count);
+    println!("Total unique users found: {}", user_coord_counts.len());
+    println!(
+        "Total failed rows/parses: {} (missing field: {}, malformed: {})",
+        failed_rows, missing_field_rows, malformed_rows
+    );
+
+    std::fs::create_dir_all(&args.output_dir)?;
+    let output_path = args.output_dir.join(OUTPUT_FILENAME);
+    println!("\nWriting user coordinate counts to {:?}...", output_path);
+    {
+        let file = File::create(&output_path)?;
+        let mut wtr = Writer::from_writer(BufWriter::new(file));
+
+        wtr.write_record(&["username", "coord_count"])?;
+
+        let mut sorted_users: Vec<_> = user_coord_counts.into_iter().collect();
+        sorted_users.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (username, count) in sorted_users {
+            wtr.write_record(&[&username, &count.to_string()])?;
+        }
+
+        wtr.flush()?;
+    }
+
+    println!("Successfully wrote user counts to {:?}.", output_path);
+
+    Ok(())
+}
+
+fn run_render_heatmap(args: RenderHeatmapArgs) -> Result<(), Box<dyn Error>> {
+    let dim = args.dim;
+    let save_interval_secs = args.save_interval_secs;
+    let threads = args.threads.max(1);
+    let layers = Arc::new(build_layers(&args.decay, &args.max_count));
+    std::fs::create_dir_all(&args.output_dir)?;
 
-fn main() {
     // Read the map data from map_data.json
-    let file = File::open("../assets/map_data.json").expect("Failed to open map_data.json");
+    let file = File::open(&args.map_data).expect("Failed to open map_data.json");
     let map_data: MapData = serde_json::from_reader(file).expect("Failed to parse map_data.json");
 
     // Create a hashmap for region coordinate lookups
@@ -42,150 +552,360 @@ fn main() {
     for region in map_data.regions {
         region_map.insert(region.id.parse::<i64>().unwrap(), region.coordinates);
     }
+    let region_map = Arc::new(region_map);
 
-    // Create a buffer reader from the standard input
-    let stdin = io::stdin();
-    let reader = stdin.lock();
-
-    // Initialize counters
-    let mut row_count: u64 = 0;
-    let mut total_coords: u64 = 0;
-    let mut failed_rows: u64 = 0;
-    let mut img_count: u64 = 0;
+    let reader = open_input(&args.input, args.compression)?;
 
     // Create a progress bar
     let progress_bar = ProgressBar::new_spinner();
-
-    // Set initial state for progress bar
-    progress_bar.set_message(format!("rows: {} coords: {} failed: {}", row_count, total_coords, failed_rows));
+    progress_bar.set_message("rows: 0 coords: 0 failed: 0");
 
     // Create a CSV reader
     let mut csv_reader = ReaderBuilder::new().has_headers(true).from_reader(reader);
 
-    // Create arrays for coordinate counts
-    let mut coord_counts_full = vec![vec![0u64; DIM]; DIM];
-    let mut coord_counts_medium = vec![vec![0u64; DIM]; DIM];
-    let mut coord_counts_fast = vec![vec![0u64; DIM]; DIM];
-    let mut coord_counts_extra_fast = vec![vec![0u64; DIM]; DIM];
+    let queue: Arc<ArrayQueue<QueueItem>> = Arc::new(ArrayQueue::new(QUEUE_CAPACITY));
+    let master_grids = Arc::new(Mutex::new(LayerGrids::new(dim, layers.keys().cloned())));
+    let total_coords = Arc::new(AtomicU64::new(0));
+    let failed_rows = Arc::new(AtomicU64::new(0));
+    let missing_field_rows = Arc::new(AtomicU64::new(0));
+    let malformed_rows = Arc::new(AtomicU64::new(0));
+    let row_counter = Arc::new(AtomicU64::new(0));
+    // Bumped by the reader once every row up to a save boundary has been
+    // pushed; each worker only acts on it once its own view of the queue is
+    // empty, so every pre-boundary row is guaranteed merged first.
+    let boundary_generation = Arc::new(AtomicU64::new(0));
+    let shutdown_requested = Arc::new(AtomicBool::new(false));
+    // One extra party for the reader thread, which rendezvouses at every
+    // boundary alongside the workers before reading the merged grids.
+    let barrier = Arc::new(Barrier::new(threads + 1));
+    let img_count = Arc::new(Mutex::new(0u64));
 
-    let mut last_save_time = None;
+    let worker_handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let region_map = Arc::clone(&region_map);
+            let master_grids = Arc::clone(&master_grids);
+            let total_coords = Arc::clone(&total_coords);
+            let failed_rows = Arc::clone(&failed_rows);
+            let missing_field_rows = Arc::clone(&missing_field_rows);
+            let malformed_rows = Arc::clone(&malformed_rows);
+            let row_counter = Arc::clone(&row_counter);
+            let boundary_generation = Arc::clone(&boundary_generation);
+            let shutdown_requested = Arc::clone(&shutdown_requested);
+            let barrier = Arc::clone(&barrier);
+            let img_count = Arc::clone(&img_count);
+            let layers = Arc::clone(&layers);
+            let output_dir = args.output_dir.clone();
+            let coords_field = args.coords_field.clone();
+            let map_id_field = args.map_id_field.clone();
+            let colormap = args.colormap;
+            let gamma = args.gamma;
+            thread::spawn(move || {
+                worker_loop(
+                    queue,
+                    region_map,
+                    master_grids,
+                    total_coords,
+                    failed_rows,
+                    missing_field_rows,
+                    malformed_rows,
+                    row_counter,
+                    boundary_generation,
+                    shutdown_requested,
+                    barrier,
+                    img_count,
+                    layers,
+                    dim,
+                    output_dir,
+                    coords_field,
+                    map_id_field,
+                    colormap,
+                    gamma,
+                )
+            })
+        })
+        .collect();
 
-    // Iterate over each record in the CSV
+    let mut row_count: u64 = 0;
+    let mut rows_outside_window: u64 = 0;
+    let mut rows_inside_window: u64 = 0;
+    let mut last_save_time: Option<DateTime<Utc>> = None;
+
+    // Iterate over each record in the CSV, pushing the raw JSON message onto
+    // the bounded queue for the worker pool to parse.
     for result in csv_reader.deserialize() {
         row_count += 1;
+        row_counter.store(row_count, Ordering::Relaxed);
 
         let record: Record = match result {
             Ok(record) => record,
             Err(_) => {
-                failed_rows += 1;
+                failed_rows.fetch_add(1, Ordering::Relaxed);
                 continue;
             }
         };
 
-
         let timestamp = DateTime::<Utc>::from_utc(
             NaiveDateTime::parse_from_str(&record.timestamp, "%Y-%m-%dT%H:%M:%S%.f")
                 .expect("Invalid timestamp format"),
-                    Utc,
+            Utc,
         );
 
+        // Input is time-sorted, so rows before --start can just be skipped,
+        // and once a row passes --end there's nothing left worth reading.
+        if let Some(start) = args.start {
+            if timestamp < start {
+                rows_outside_window += 1;
+                continue;
+            }
+        }
+        if let Some(end) = args.end {
+            if timestamp > end {
+                break;
+            }
+        }
+        rows_inside_window += 1;
+
         if last_save_time.is_none() {
             last_save_time = Some(timestamp);
         }
 
-        // Parse the JSON message
-        if let Ok(json_value) = serde_json::from_str::<Value>(&record.message) {
-            if let Some(coords_array) = json_value.get("coords").and_then(|v| v.as_array()) {
-                for coords in coords_array {
-                    if let Some(coords) = coords.as_array() {
-                        if coords.len() == 3 {
-                            let x = coords[0].as_i64().unwrap_or(-1);
-                            let y = coords[1].as_i64().unwrap_or(-1);
-                            let map_id = coords[2].as_i64().unwrap_or(6666);
-
-                            if let Some(&offsets) = region_map.get(&map_id) {
-                                let global_x = x + offsets[0];
-                                let global_y = y + offsets[1];
-
-                                if global_x >= 0 && global_x < DIM as i64 && global_y >= 0 && global_y < DIM as i64 {
-                                    coord_counts_full[global_x as usize][global_y as usize] += 1;
-                                    coord_counts_medium[global_x as usize][global_y as usize] += 1;
-                                    coord_counts_fast[global_x as usize][global_y as usize] += 1;
-                                    coord_counts_extra_fast[global_x as usize][global_y as usize] += 1;
-                                    total_coords += 1;
-                                } else {
-                                    println!("bad coords {} {}", global_x, global_y);
-                                    failed_rows += 1;
-                                }
-                            } else {
-                                println!("bad map id {}", map_id);
-                                failed_rows += 1;
-                            }
-                        } else {
-                            println!("bad coords.len() {}", coords.len());
-                            failed_rows += 1;
-                        }
-                    } else {
-                        println!("no array within coords");
-                        failed_rows += 1;
-                    }
-                }
-            } else {
-                failed_rows += 1;
-            }
-        } else {
-            failed_rows += 1;
-        }
+        push_blocking(&queue, QueueItem { message: record.message });
 
-        // Check if 12 minutes have passed since the last save
+        // Check if the configured interval has passed since the last save.
+        // Bumping the generation here only happens after this row's item is
+        // already in the queue, so every worker that drains the queue dry
+        // afterward is guaranteed to have merged every row up to and
+        // including this one.
         if let Some(last_save) = last_save_time {
             let elapsed = timestamp.signed_duration_since(last_save).num_seconds();
-            if elapsed >= SAVE_INTERVAL_SECS as i64 {
-                save_map_as_image("full", u32::pow(2, 26), &coord_counts_full, row_count, img_count);
-                save_map_as_image("medium", u32::pow(2, 22), &coord_counts_medium, row_count, img_count);
-                save_map_as_image("fast", u32::pow(2, 18), &coord_counts_fast, row_count, img_count);
-                save_map_as_image("extra_fast", u32::pow(2, 16), &coord_counts_extra_fast, row_count, img_count);
-                img_count += 1; 
+            if elapsed >= save_interval_secs as i64 {
+                boundary_generation.fetch_add(1, Ordering::SeqCst);
+                barrier.wait();
                 last_save_time = Some(timestamp);
+            }
+        }
+
+        if row_count % 1000 == 0 {
+            progress_bar.set_message(format!(
+                "rows: {} coords: {} failed: {} timestamp: {}",
+                row_count,
+                total_coords.load(Ordering::Relaxed),
+                failed_rows.load(Ordering::Relaxed),
+                timestamp
+            ));
+        }
+    }
+
+    shutdown_requested.store(true, Ordering::SeqCst);
+
+    for handle in worker_handles {
+        handle.join().expect("worker thread panicked");
+    }
+
+    println!(
+        "rows: {} coords: {} failed: {} (missing field: {}, malformed: {}) inside window: {} outside window: {}",
+        row_count,
+        total_coords.load(Ordering::Relaxed),
+        failed_rows.load(Ordering::Relaxed),
+        missing_field_rows.load(Ordering::Relaxed),
+        malformed_rows.load(Ordering::Relaxed),
+        rows_inside_window,
+        rows_outside_window
+    );
+
+    Ok(())
+}
+
+/// Spin until there's room in the bounded queue. The queue capacity keeps peak
+/// memory flat; this is the backpressure that makes the reader wait for it.
+fn push_blocking(queue: &ArrayQueue<QueueItem>, mut item: QueueItem) {
+    while let Err(rejected) = queue.push(item) {
+        item = rejected;
+        thread::yield_now();
+    }
+}
 
-                for row in coord_counts_medium.iter_mut() {
-                    for pix in row.iter_mut() {
-                        *pix = ((*pix as f64) * 0.99) as u64;
+#[allow(clippy::too_many_arguments)]
+fn worker_loop(
+    queue: Arc<ArrayQueue<QueueItem>>,
+    region_map: Arc<HashMap<i64, [i64; 2]>>,
+    master_grids: Arc<Mutex<LayerGrids>>,
+    total_coords: Arc<AtomicU64>,
+    failed_rows: Arc<AtomicU64>,
+    missing_field_rows: Arc<AtomicU64>,
+    malformed_rows: Arc<AtomicU64>,
+    row_counter: Arc<AtomicU64>,
+    boundary_generation: Arc<AtomicU64>,
+    shutdown_requested: Arc<AtomicBool>,
+    barrier: Arc<Barrier>,
+    img_count: Arc<Mutex<u64>>,
+    layers: Arc<HashMap<String, LayerConfig>>,
+    dim: usize,
+    output_dir: PathBuf,
+    coords_field: String,
+    map_id_field: Option<String>,
+    colormap: Colormap,
+    gamma: f64,
+) {
+    let mut local_grids = LayerGrids::new(dim, layers.keys().cloned());
+    let mut local_generation = 0u64;
+
+    loop {
+        let item = match queue.pop() {
+            Some(item) => item,
+            None => {
+                // Nothing left for this worker to pop right now. Only act on a
+                // boundary or shutdown once the queue looks empty, so every
+                // row pushed before the signal has already been folded into
+                // local_grids by whichever worker happened to pop it.
+                let current_generation = boundary_generation.load(Ordering::SeqCst);
+                if current_generation > local_generation {
+                    {
+                        let mut master = master_grids.lock().unwrap();
+                        master.merge_from(&mut local_grids);
                     }
-                }
-                for row in coord_counts_fast.iter_mut() {
-                    for pix in row.iter_mut() {
-                        *pix = ((*pix as f64) * 0.9) as u64;
+                    barrier.wait();
+                    // Only one worker needs to actually write the images; whichever
+                    // first acquires the mutex after the barrier releases does the
+                    // save and decay (img_count tells any later acquirer this
+                    // boundary is already saved), the rest just move on to the
+                    // next batch of data.
+                    if let Ok(mut master) = master_grids.try_lock() {
+                        let mut count = img_count.lock().unwrap();
+                        if *count >= current_generation {
+                            local_generation = current_generation;
+                            continue;
+                        }
+                        let row_count = row_counter.load(Ordering::Relaxed);
+                        for (name, grid) in master.grids.iter() {
+                            save_map_as_image(
+                                &output_dir,
+                                name,
+                                layers[name].max_count,
+                                grid,
+                                row_count,
+                                *count,
+                                colormap,
+                                gamma,
+                            );
+                        }
+                        *count += 1;
+                        master.apply_decay(&layers);
                     }
+                    local_generation = current_generation;
+                    continue;
                 }
-                for row in coord_counts_extra_fast.iter_mut() {
-                    for pix in row.iter_mut() {
-                        *pix = ((*pix as f64) * 0.5) as u64;
-                    }
+
+                if shutdown_requested.load(Ordering::SeqCst) {
+                    let mut master = master_grids.lock().unwrap();
+                    master.merge_from(&mut local_grids);
+                    break;
                 }
+
+                thread::yield_now();
+                continue;
             }
+        };
+
+        let QueueItem { message } = item;
+        let mut local_coords = 0u64;
+        let mut local_failed = 0u64;
+        let mut local_missing = 0u64;
+        let mut local_malformed = 0u64;
+
+        match serde_json::from_str::<Value>(&message) {
+            Ok(json_value) => match extract_coords(&json_value, &coords_field, &map_id_field) {
+                Ok(coords) => {
+                    for (x, y, map_id) in coords {
+                        if let Some(&offsets) = region_map.get(&map_id) {
+                            let global_x = x + offsets[0];
+                            let global_y = y + offsets[1];
+
+                            if global_x >= 0
+                                && global_x < dim as i64
+                                && global_y >= 0
+                                && global_y < dim as i64
+                            {
+                                local_grids.record(global_x as usize, global_y as usize);
+                                local_coords += 1;
+                            } else {
+                                println!("bad coords {} {}", global_x, global_y);
+                                local_failed += 1;
+                            }
+                        } else {
+                            println!("bad map id {}", map_id);
+                            local_failed += 1;
+                        }
+                    }
+                }
+                Err(FieldError::Missing) => local_missing += 1,
+                Err(FieldError::Malformed) => local_malformed += 1,
+            },
+            Err(_) => local_malformed += 1,
         }
 
-        progress_bar.set_message(format!("rows: {} coords: {} failed: {} timestamp: {}", row_count, total_coords, failed_rows, timestamp));
+        total_coords.fetch_add(local_coords, Ordering::Relaxed);
+        failed_rows.fetch_add(local_failed, Ordering::Relaxed);
+        missing_field_rows.fetch_add(local_missing, Ordering::Relaxed);
+        malformed_rows.fetch_add(local_malformed, Ordering::Relaxed);
     }
-
-    println!("rows: {} coords: {} failed: {}", row_count, total_coords, failed_rows);
 }
 
-fn save_map_as_image(name: &str, max_count: u32, coord_counts: &Vec<Vec<u64>>, row_count: u64, img_count: u64) {
+fn save_map_as_image(
+    output_dir: &Path,
+    name: &str,
+    max_count: u64,
+    coord_counts: &[Vec<u64>],
+    row_count: u64,
+    img_count: u64,
+    colormap: Colormap,
+    gamma: f64,
+) {
     let cur_max_pixel = coord_counts.iter().flatten().max().cloned().unwrap_or(1);
-    println!("{} image: {} true max pixel: {} using max {} ", name, img_count, cur_max_pixel, max_count);
+    println!(
+        "{} image: {} rows: {} true max pixel: {} using max {} ",
+        name, img_count, row_count, cur_max_pixel, max_count
+    );
 
-    let mut img: ImageBuffer<Rgb<f32>, Vec<f32>> = ImageBuffer::new(DIM as u32, DIM as u32);
+    let dim = coord_counts.len() as u32;
+    let mut img: ImageBuffer<Rgb<f32>, Vec<f32>> = ImageBuffer::new(dim, dim);
+    let mut png: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(dim, dim);
 
     for (x, row) in coord_counts.iter().enumerate() {
         for (y, &count) in row.iter().enumerate() {
             let intensity = (f64::min((count as f64) / (max_count as f64), 1.0)) as f32;
             img.put_pixel(x as u32, y as u32, Rgb([intensity, intensity, intensity]));
+
+            if colormap != Colormap::None {
+                let toned = (intensity as f64).powf(1.0 / gamma) as f32;
+                png.put_pixel(x as u32, y as u32, Rgb(apply_colormap(colormap, toned)));
+            }
         }
     }
 
-    let filename = format!("images/coord_map_{name}_{}.exr", img_count);
-    img.save_with_format(Path::new(&filename), image::ImageFormat::OpenExr)
+    let filename = output_dir.join(format!("coord_map_{name}_{}.exr", img_count));
+    img.save_with_format(&filename, image::ImageFormat::OpenExr)
         .expect("Failed to save image");
+
+    if colormap != Colormap::None {
+        let png_filename = format!("coord_map_{name}_{}.png", img_count);
+        png.save_with_format(output_dir.join(&png_filename), image::ImageFormat::Png)
+            .expect("Failed to save image");
+        append_to_timelapse_manifest(output_dir, name, &png_filename);
+    }
+}
+
+/// Append this frame to `timelapse_<name>.txt`, an ffmpeg concat-demuxer
+/// manifest (`ffmpeg -f concat -safe 0 -i timelapse_<name>.txt ...`) listing
+/// every PNG saved for this layer in save order, so a timelapse can be
+/// assembled without re-deriving the frame sequence from directory listing
+fn append_to_timelapse_manifest(output_dir: &Path, name: &str, frame_filename: &str) {
+    let manifest_path = output_dir.join(format!("timelapse_{name}.txt"));
+    let mut manifest = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(manifest_path)
+        .expect("Failed to open timelapse manifest");
+    writeln!(manifest, "file '{frame_filename}'").expect("Failed to write timelapse manifest");
 }